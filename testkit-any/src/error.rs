@@ -0,0 +1,44 @@
+use thiserror::Error;
+
+#[cfg(feature = "mysql")]
+use testkit_mysql::MySqlError;
+#[cfg(feature = "postgres")]
+use testkit_postgres::PostgresError;
+
+/// Error type for the runtime-selected backend
+#[derive(Error, Debug, Clone)]
+pub enum AnyError {
+    /// Error from the PostgreSQL backend
+    #[cfg(feature = "postgres")]
+    #[error(transparent)]
+    Postgres(#[from] PostgresError),
+
+    /// Error from the MySQL backend
+    #[cfg(feature = "mysql")]
+    #[error(transparent)]
+    MySql(#[from] MySqlError),
+
+    /// The connection string doesn't point to a supported server
+    #[error("Configuration error: {0}")]
+    ConfigError(String),
+
+    /// A pool or connection was used with a backend of another dialect
+    #[error("Backend mismatch: {0}")]
+    BackendMismatch(String),
+
+    /// Any other error
+    #[error("Other error: {0}")]
+    Other(String),
+}
+
+impl From<String> for AnyError {
+    fn from(s: String) -> Self {
+        Self::Other(s)
+    }
+}
+
+impl From<&str> for AnyError {
+    fn from(s: &str) -> Self {
+        Self::Other(s.to_string())
+    }
+}
@@ -0,0 +1,475 @@
+//! Runtime selection between the PostgreSQL and MySQL backends
+//!
+//! [`AnyBackend`] implements [`DatabaseBackend`] by looking at the scheme of the configured
+//! connection string: `postgres://` and `postgresql://` use the tokio-postgres backend of
+//! testkit-postgres, `mysql://` and `mariadb://` the mysql-async backend of testkit-mysql.
+//! Test helpers written against `AnyBackend` and the [`TestConnection`] trait don't need to
+//! be generic over the backend.
+//!
+//! ```rust,no_run
+//! use testkit_any::{AnyBackend, DatabaseBackend, DatabaseConfig, TestConnection};
+//! use testkit_core::TestDatabaseInstance;
+//!
+//! # async fn example() -> Result<(), testkit_any::AnyError> {
+//! // Picks the backend from DATABASE_URL
+//! let config = DatabaseConfig::default();
+//! let backend = AnyBackend::new(config.clone()).await?;
+//! let db = TestDatabaseInstance::new(backend, config).await?;
+//!
+//! let conn = db.acquire_connection().await?;
+//! conn.batch_execute("CREATE TABLE users (id INT PRIMARY KEY)").await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Each backend is behind a feature (`postgres` and `mysql`), both enabled by default.
+//!
+//! [`AnyConnection`] forwards every [`TestConnection`] method, including the ones a
+//! backend overrides, and the helpers both backends have, such as
+//! [`load_csv`](AnyConnection::load_csv). Postgres-only helpers are forwarded too and fail
+//! with [`AnyError::BackendMismatch`] on MySQL. Features implemented for the test databases
+//! of a specific backend, like `testkit_postgres::Listen` and
+//! `testkit_postgres::LogicalDecoding`, aren't available through `AnyBackend`; use
+//! [`AnyConnection::listen`] or a concrete backend for those.
+
+#[cfg(not(any(feature = "postgres", feature = "mysql")))]
+compile_error!("testkit-any needs at least one of the `postgres` or `mysql` features");
+
+use async_trait::async_trait;
+use std::path::Path;
+
+#[cfg(feature = "mysql")]
+use testkit_mysql::{MySqlBackend, MySqlConnection, MySqlPool, MySqlTransaction};
+#[cfg(feature = "postgres")]
+use testkit_postgres::{
+    PostgresBackend, PostgresConnection, PostgresPool, PostgresTransaction, Subscriber,
+};
+
+mod error;
+pub use error::AnyError;
+
+/// Re-export the traits from testkit-core
+pub use testkit_core::{
+    CsvOptions, DatabaseBackend, DatabaseConfig, DatabaseName, DatabasePool, DatabaseTransaction,
+    IsolationLevel, Row, ScriptError, SqlDialect, SqlScript, TestConnection, TestContext,
+    TestDatabaseConnection, TestDatabaseInstance, TransactionManager, TransactionalConnection,
+    TxOptions, Value,
+};
+use testkit_core::{Migrator, WithMigrations};
+
+/// Run the same expression against whichever backend a value wraps
+macro_rules! dispatch {
+    ($value:expr, $ty:ident, $inner:ident => $body:expr) => {
+        match $value {
+            #[cfg(feature = "postgres")]
+            $ty::Postgres($inner) => $body,
+            #[cfg(feature = "mysql")]
+            $ty::MySql($inner) => $body,
+        }
+    };
+}
+
+/// A backend chosen at runtime from the scheme of the connection string
+#[derive(Debug, Clone)]
+pub enum AnyBackend {
+    /// The tokio-postgres backend
+    #[cfg(feature = "postgres")]
+    Postgres(PostgresBackend),
+    /// The mysql-async backend
+    #[cfg(feature = "mysql")]
+    MySql(MySqlBackend),
+}
+
+/// A connection pool of an [`AnyBackend`]
+#[derive(Clone)]
+pub enum AnyPool {
+    /// A PostgreSQL pool
+    #[cfg(feature = "postgres")]
+    Postgres(PostgresPool),
+    /// A MySQL pool
+    #[cfg(feature = "mysql")]
+    MySql(MySqlPool),
+}
+
+/// A connection of an [`AnyBackend`]
+#[derive(Clone)]
+pub enum AnyConnection {
+    /// A PostgreSQL connection
+    #[cfg(feature = "postgres")]
+    Postgres(PostgresConnection),
+    /// A MySQL connection
+    #[cfg(feature = "mysql")]
+    MySql(MySqlConnection),
+}
+
+/// A transaction of an [`AnyBackend`]
+pub enum AnyTransaction {
+    /// A PostgreSQL transaction
+    #[cfg(feature = "postgres")]
+    Postgres(PostgresTransaction),
+    /// A MySQL transaction
+    #[cfg(feature = "mysql")]
+    MySql(MySqlTransaction),
+}
+
+impl AnyBackend {
+    /// Returns the SQL dialect of the server this backend talks to
+    pub fn dialect(&self) -> SqlDialect {
+        match self {
+            #[cfg(feature = "postgres")]
+            AnyBackend::Postgres(_) => SqlDialect::Postgres,
+            #[cfg(feature = "mysql")]
+            AnyBackend::MySql(_) => SqlDialect::MySql,
+        }
+    }
+}
+
+impl AnyPool {
+    /// Returns the SQL dialect of the server this pool connects to
+    pub fn dialect(&self) -> SqlDialect {
+        match self {
+            #[cfg(feature = "postgres")]
+            AnyPool::Postgres(_) => SqlDialect::Postgres,
+            #[cfg(feature = "mysql")]
+            AnyPool::MySql(_) => SqlDialect::MySql,
+        }
+    }
+}
+
+fn mismatch(backend: SqlDialect, other: SqlDialect, what: &str) -> AnyError {
+    AnyError::BackendMismatch(format!(
+        "{} backend can't use a {} {}",
+        backend, other, what
+    ))
+}
+
+#[cfg(feature = "postgres")]
+impl From<PostgresBackend> for AnyBackend {
+    fn from(backend: PostgresBackend) -> Self {
+        AnyBackend::Postgres(backend)
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl From<MySqlBackend> for AnyBackend {
+    fn from(backend: MySqlBackend) -> Self {
+        AnyBackend::MySql(backend)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<PostgresPool> for AnyPool {
+    fn from(pool: PostgresPool) -> Self {
+        AnyPool::Postgres(pool)
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl From<MySqlPool> for AnyPool {
+    fn from(pool: MySqlPool) -> Self {
+        AnyPool::MySql(pool)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<PostgresConnection> for AnyConnection {
+    fn from(conn: PostgresConnection) -> Self {
+        AnyConnection::Postgres(conn)
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl From<MySqlConnection> for AnyConnection {
+    fn from(conn: MySqlConnection) -> Self {
+        AnyConnection::MySql(conn)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<PostgresTransaction> for AnyTransaction {
+    fn from(tx: PostgresTransaction) -> Self {
+        AnyTransaction::Postgres(tx)
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl From<MySqlTransaction> for AnyTransaction {
+    fn from(tx: MySqlTransaction) -> Self {
+        AnyTransaction::MySql(tx)
+    }
+}
+
+impl WithMigrations<Migrator> for AnyBackend {
+    /// Apply the migrations to every database right after it is created
+    fn with_migrations(self, migrator: Migrator) -> Self {
+        dispatch!(self, AnyBackend, backend => backend.with_migrations(migrator).into())
+    }
+}
+
+#[async_trait]
+impl DatabaseBackend for AnyBackend {
+    type Connection = AnyConnection;
+    type Pool = AnyPool;
+    type Error = AnyError;
+
+    /// Create the backend matching the scheme of the user connection string
+    async fn new(config: DatabaseConfig) -> Result<Self, Self::Error> {
+        match SqlDialect::from_url(&config.user_url) {
+            #[cfg(feature = "postgres")]
+            Some(SqlDialect::Postgres) => Ok(PostgresBackend::new(config).await?.into()),
+            #[cfg(feature = "mysql")]
+            Some(SqlDialect::MySql) => Ok(MySqlBackend::new(config).await?.into()),
+            #[allow(unreachable_patterns)]
+            Some(dialect) => Err(AnyError::ConfigError(format!(
+                "{} support is not enabled, enable the `{}` feature of testkit-any",
+                dialect,
+                match dialect {
+                    SqlDialect::Postgres => "postgres",
+                    SqlDialect::MySql => "mysql",
+                }
+            ))),
+            None => {
+                // Only show the scheme, the rest of the URL may contain a password
+                let scheme = config.user_url.split_once("://").map(|(scheme, _)| scheme);
+                Err(AnyError::ConfigError(format!(
+                    "unsupported database URL scheme {:?}, expected postgres:// or mysql://",
+                    scheme.unwrap_or_default()
+                )))
+            }
+        }
+    }
+
+    async fn create_pool(
+        &self,
+        name: &DatabaseName,
+        config: &DatabaseConfig,
+    ) -> Result<Self::Pool, Self::Error> {
+        Ok(dispatch!(self, AnyBackend, backend => {
+            backend.create_pool(name, config).await?.into()
+        }))
+    }
+
+    async fn connect(&self, name: &DatabaseName) -> Result<Self::Connection, Self::Error> {
+        Ok(dispatch!(self, AnyBackend, backend => backend.connect(name).await?.into()))
+    }
+
+    async fn connect_with_string(
+        &self,
+        connection_string: &str,
+    ) -> Result<Self::Connection, Self::Error> {
+        Ok(dispatch!(self, AnyBackend, backend => {
+            backend.connect_with_string(connection_string).await?.into()
+        }))
+    }
+
+    async fn create_database(
+        &self,
+        pool: &Self::Pool,
+        name: &DatabaseName,
+    ) -> Result<(), Self::Error> {
+        match (self, pool) {
+            #[cfg(feature = "postgres")]
+            (AnyBackend::Postgres(backend), AnyPool::Postgres(pool)) => backend
+                .create_database(pool, name)
+                .await
+                .map_err(Into::into),
+            #[cfg(feature = "mysql")]
+            (AnyBackend::MySql(backend), AnyPool::MySql(pool)) => backend
+                .create_database(pool, name)
+                .await
+                .map_err(Into::into),
+            #[allow(unreachable_patterns)]
+            (backend, pool) => Err(mismatch(backend.dialect(), pool.dialect(), "pool")),
+        }
+    }
+
+    fn drop_database(&self, name: &DatabaseName) -> Result<(), Self::Error> {
+        dispatch!(self, AnyBackend, backend => backend.drop_database(name).map_err(Into::into))
+    }
+
+    fn connection_string(&self, name: &DatabaseName) -> String {
+        dispatch!(self, AnyBackend, backend => backend.connection_string(name))
+    }
+}
+
+#[async_trait]
+impl DatabasePool for AnyPool {
+    type Connection = AnyConnection;
+    type Error = AnyError;
+
+    async fn acquire(&self) -> Result<Self::Connection, Self::Error> {
+        Ok(dispatch!(self, AnyPool, pool => pool.acquire().await?.into()))
+    }
+
+    async fn release(&self, conn: Self::Connection) -> Result<(), Self::Error> {
+        match (self, conn) {
+            #[cfg(feature = "postgres")]
+            (AnyPool::Postgres(pool), AnyConnection::Postgres(conn)) => {
+                pool.release(conn).await.map_err(Into::into)
+            }
+            #[cfg(feature = "mysql")]
+            (AnyPool::MySql(pool), AnyConnection::MySql(conn)) => {
+                pool.release(conn).await.map_err(Into::into)
+            }
+            #[allow(unreachable_patterns)]
+            (pool, conn) => Err(mismatch(pool.dialect(), conn.dialect(), "connection")),
+        }
+    }
+
+    fn connection_string(&self) -> String {
+        dispatch!(self, AnyPool, pool => pool.connection_string())
+    }
+}
+
+impl TestDatabaseConnection for AnyConnection {
+    fn connection_string(&self) -> String {
+        dispatch!(self, AnyConnection, conn => TestDatabaseConnection::connection_string(conn))
+    }
+}
+
+#[async_trait]
+impl TestConnection for AnyConnection {
+    type Error = AnyError;
+
+    fn dialect(&self) -> SqlDialect {
+        dispatch!(self, AnyConnection, conn => conn.dialect())
+    }
+
+    async fn execute(&self, sql: &str, params: &[Value]) -> Result<u64, Self::Error> {
+        Ok(dispatch!(self, AnyConnection, conn => conn.execute(sql, params).await?))
+    }
+
+    async fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>, Self::Error> {
+        Ok(dispatch!(self, AnyConnection, conn => conn.query(sql, params).await?))
+    }
+
+    async fn batch_execute(&self, sql: &str) -> Result<(), Self::Error> {
+        dispatch!(self, AnyConnection, conn => conn.batch_execute(sql).await.map_err(Into::into))
+    }
+
+    async fn execute_script<S>(&self, script: S) -> Result<(), ScriptError>
+    where
+        S: Into<SqlScript> + Send,
+    {
+        dispatch!(self, AnyConnection, conn => conn.execute_script(script).await)
+    }
+
+    async fn insert_returning(
+        &self,
+        table: &str,
+        key: &str,
+        values: &[(String, Value)],
+    ) -> Result<Option<Row>, Self::Error> {
+        Ok(dispatch!(self, AnyConnection, conn => conn.insert_returning(table, key, values).await?))
+    }
+}
+
+impl AnyConnection {
+    /// Bulk load a CSV file into a table and return the number of rows loaded
+    ///
+    /// Uses `COPY ... FROM STDIN` on PostgreSQL and `LOAD DATA LOCAL INFILE` on MySQL, see
+    /// the `load_csv` methods of the backends.
+    pub async fn load_csv(
+        &self,
+        table: &str,
+        path: impl AsRef<Path>,
+        options: CsvOptions,
+    ) -> Result<u64, AnyError> {
+        Ok(dispatch!(self, AnyConnection, conn => conn.load_csv(table, path, options).await?))
+    }
+
+    /// Listen to a `NOTIFY` channel on a new connection to the same database
+    ///
+    /// Only PostgreSQL has notifications; fails with [`AnyError::BackendMismatch`] on MySQL.
+    #[cfg(feature = "postgres")]
+    pub async fn listen(&self, channel: &str) -> Result<Subscriber, AnyError> {
+        match self {
+            AnyConnection::Postgres(conn) => Ok(conn.listen(channel).await?),
+            #[cfg(feature = "mysql")]
+            AnyConnection::MySql(_) => Err(AnyError::BackendMismatch(
+                "LISTEN/NOTIFY needs a PostgreSQL connection".to_string(),
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionalConnection for AnyConnection {
+    type Transaction = AnyTransaction;
+
+    async fn begin_transaction_with(
+        &self,
+        options: TxOptions,
+    ) -> Result<Self::Transaction, Self::Error> {
+        Ok(
+            dispatch!(self, AnyConnection, conn => conn.begin_transaction_with(options).await?.into()),
+        )
+    }
+}
+
+impl TestDatabaseConnection for AnyTransaction {
+    fn connection_string(&self) -> String {
+        dispatch!(self, AnyTransaction, tx => tx.connection_string())
+    }
+}
+
+#[async_trait]
+impl TestConnection for AnyTransaction {
+    type Error = AnyError;
+
+    fn dialect(&self) -> SqlDialect {
+        dispatch!(self, AnyTransaction, tx => tx.dialect())
+    }
+
+    async fn execute(&self, sql: &str, params: &[Value]) -> Result<u64, Self::Error> {
+        Ok(dispatch!(self, AnyTransaction, tx => tx.execute(sql, params).await?))
+    }
+
+    async fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>, Self::Error> {
+        Ok(dispatch!(self, AnyTransaction, tx => tx.query(sql, params).await?))
+    }
+
+    async fn batch_execute(&self, sql: &str) -> Result<(), Self::Error> {
+        dispatch!(self, AnyTransaction, tx => tx.batch_execute(sql).await.map_err(Into::into))
+    }
+
+    async fn execute_script<S>(&self, script: S) -> Result<(), ScriptError>
+    where
+        S: Into<SqlScript> + Send,
+    {
+        dispatch!(self, AnyTransaction, tx => tx.execute_script(script).await)
+    }
+
+    async fn insert_returning(
+        &self,
+        table: &str,
+        key: &str,
+        values: &[(String, Value)],
+    ) -> Result<Option<Row>, Self::Error> {
+        Ok(dispatch!(self, AnyTransaction, tx => tx.insert_returning(table, key, values).await?))
+    }
+}
+
+#[async_trait]
+impl DatabaseTransaction for AnyTransaction {
+    async fn commit(&mut self) -> Result<(), Self::Error> {
+        dispatch!(self, AnyTransaction, tx => tx.commit().await.map_err(Into::into))
+    }
+
+    async fn rollback(&mut self) -> Result<(), Self::Error> {
+        dispatch!(self, AnyTransaction, tx => tx.rollback().await.map_err(Into::into))
+    }
+
+    fn options(&self) -> &TxOptions {
+        dispatch!(self, AnyTransaction, tx => tx.options())
+    }
+}
+
+/// Create a backend for the server configured in the environment
+///
+/// Reads DATABASE_URL and ADMIN_DATABASE_URL like [`DatabaseConfig::from_env`].
+pub async fn any_backend() -> Result<AnyBackend, AnyError> {
+    let config = DatabaseConfig::from_env()
+        .map_err(|e| AnyError::ConfigError(format!("DATABASE_URL is not set: {}", e)))?;
+    AnyBackend::new(config).await
+}
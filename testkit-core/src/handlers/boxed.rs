@@ -1,5 +1,6 @@
 use std::fmt::Debug;
 use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 
 use crate::DatabasePool;
@@ -7,6 +8,8 @@ use crate::TestContext;
 use crate::handlers::TransactionHandler;
 use crate::testdb::DatabaseBackend;
 use crate::testdb::DatabaseConfig;
+use crate::testdb::{DatabaseTransaction, TestConnection, TransactionalConnection, TxOptions};
+use crate::testdb::{Fixtures, WithMigrations};
 use async_trait::async_trait;
 
 // Type aliases to simplify complex types
@@ -30,6 +33,56 @@ pub type BoxedTransactionFn<DB> = Box<
         + Sync,
 >;
 
+/// Transaction type started on the connections of a backend
+pub type BackendTransaction<DB> =
+    <<DB as DatabaseBackend>::Connection as TransactionalConnection>::Transaction;
+
+/// Wrap a function taking a transaction into one taking a connection
+///
+/// The returned function begins a transaction with the given options on the connection,
+/// commits it when the function succeeds and rolls it back when it fails.
+fn in_transaction<DB, F>(options: TxOptions, transaction_fn: F) -> BoxedTransactionFn<DB>
+where
+    DB: DatabaseBackend + Send + Sync + 'static,
+    DB::Connection: TransactionalConnection,
+    DB::Error: From<<DB::Connection as TestConnection>::Error>,
+    F: for<'a> FnOnce(
+            &'a mut BackendTransaction<DB>,
+        ) -> Pin<Box<dyn Future<Output = Result<(), DB::Error>> + Send + 'a>>
+        + Send
+        + Sync
+        + 'static,
+{
+    Box::new(move |conn| {
+        Box::pin(async move {
+            let mut tx = conn.begin_transaction_with(options).await?;
+            match transaction_fn(&mut tx).await {
+                Ok(()) => {
+                    tx.commit().await?;
+                    Ok(())
+                }
+                Err(e) => {
+                    let _ = tx.rollback().await;
+                    Err(e)
+                }
+            }
+        })
+    })
+}
+
+/// Read fixture files and insert their rows on the setup connection
+async fn load_fixtures<DB>(conn: &DB::Connection, paths: &[PathBuf]) -> Result<(), DB::Error>
+where
+    DB: DatabaseBackend,
+    DB::Connection: TestConnection,
+{
+    let result = match Fixtures::from_files(paths) {
+        Ok(fixtures) => fixtures.load(conn).await,
+        Err(e) => Err(e),
+    };
+    result.map_err(|e| DB::Error::from(e.to_string()))
+}
+
 /// Entry point for database operations with automatic boxing of closures
 ///
 /// This provides functionality for database operations with automatic boxing
@@ -79,6 +132,18 @@ where
         Self { backend }
     }
 
+    /// Run the given migrations on the database before anything else
+    ///
+    /// The backend applies them right after creating the database, see [`WithMigrations`].
+    pub fn migrate<M>(self, migrations: M) -> Self
+    where
+        DB: WithMigrations<M>,
+    {
+        Self {
+            backend: self.backend.with_migrations(migrations),
+        }
+    }
+
     /// Set up the database with the given function
     ///
     /// This method takes a closure that will be executed during setup.
@@ -100,6 +165,24 @@ where
         }
     }
 
+    /// Load fixture files into the database, see [`Fixtures`]
+    ///
+    /// Relative paths are resolved against the working directory, which is the crate root
+    /// under `cargo test`. The rows are inserted before any transaction function runs.
+    pub fn fixtures<I, P>(self, paths: I) -> BoxedSetupHandler<DB>
+    where
+        DB: Debug,
+        DB::Connection: TestConnection,
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let paths: Vec<PathBuf> = paths
+            .into_iter()
+            .map(|p| p.as_ref().to_path_buf())
+            .collect();
+        self.setup(move |conn| Box::pin(async move { load_fixtures::<DB>(conn, &paths).await }))
+    }
+
     /// Initialize a database with a transaction
     pub fn with_transaction<F>(self, transaction_fn: F) -> BoxedTransactionOnlyHandler<DB>
     where
@@ -117,6 +200,32 @@ where
         }
     }
 
+    /// Initialize a database with a transaction started with the given options
+    ///
+    /// The closure receives the transaction itself. It is committed when the closure
+    /// succeeds and rolled back when it returns an error.
+    pub fn transaction_with<F>(
+        self,
+        options: TxOptions,
+        transaction_fn: F,
+    ) -> BoxedTransactionOnlyHandler<DB>
+    where
+        DB::Connection: TransactionalConnection,
+        DB::Error: From<<DB::Connection as TestConnection>::Error>,
+        F: for<'a> FnOnce(
+                &'a mut BackendTransaction<DB>,
+            )
+                -> Pin<Box<dyn Future<Output = Result<(), DB::Error>> + Send + 'a>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        BoxedTransactionOnlyHandler {
+            backend: self.backend,
+            transaction_fn: in_transaction::<DB, F>(options, transaction_fn),
+        }
+    }
+
     /// Execute this handler
     pub async fn execute(self) -> Result<crate::TestContext<DB>, DB::Error> {
         // Create the database instance
@@ -235,6 +344,32 @@ impl<DB> BoxedSetupHandler<DB>
 where
     DB: DatabaseBackend + Send + Sync + Debug + 'static,
 {
+    /// Load fixture files into the database after the setup function, see [`Fixtures`]
+    ///
+    /// Relative paths are resolved against the working directory, which is the crate root
+    /// under `cargo test`.
+    pub fn fixtures<I, P>(self, paths: I) -> BoxedSetupHandler<DB>
+    where
+        DB::Connection: TestConnection,
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let paths: Vec<PathBuf> = paths
+            .into_iter()
+            .map(|p| p.as_ref().to_path_buf())
+            .collect();
+        let setup_fn = self.setup_fn;
+        BoxedSetupHandler {
+            backend: self.backend,
+            setup_fn: Box::new(move |conn| {
+                Box::pin(async move {
+                    setup_fn(conn).await?;
+                    load_fixtures::<DB>(conn, &paths).await
+                })
+            }),
+        }
+    }
+
     /// Add a transaction function
     ///
     /// This method takes a closure that will be executed during transaction.
@@ -257,6 +392,33 @@ where
         }
     }
 
+    /// Add a transaction started with the given options
+    ///
+    /// The closure receives the transaction itself. It is committed when the closure
+    /// succeeds and rolled back when it returns an error.
+    pub fn transaction_with<F>(
+        self,
+        options: TxOptions,
+        transaction_fn: F,
+    ) -> BoxedTransactionHandler<DB>
+    where
+        DB::Connection: TransactionalConnection,
+        DB::Error: From<<DB::Connection as TestConnection>::Error>,
+        F: for<'a> FnOnce(
+                &'a mut BackendTransaction<DB>,
+            )
+                -> Pin<Box<dyn Future<Output = Result<(), DB::Error>> + Send + 'a>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        BoxedTransactionHandler {
+            backend: self.backend,
+            setup_fn: self.setup_fn,
+            transaction_fn: in_transaction::<DB, F>(options, transaction_fn),
+        }
+    }
+
     /// Execute this handler
     pub async fn execute(self) -> Result<crate::TestContext<DB>, DB::Error> {
         // Create the database instance
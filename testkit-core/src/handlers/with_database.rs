@@ -1,6 +1,6 @@
 use crate::{
-    DatabaseBackend, DatabasePool, TestContext, TestDatabaseInstance, TransactionStarter,
-    transaction::DBTransactionManager,
+    DatabaseBackend, DatabasePool, TestContext, TestDatabaseInstance,
+    transaction::TransactionManager,
 };
 
 use async_trait::async_trait;
@@ -65,17 +65,12 @@ where
     S: FnOnce(&mut <DB::Pool as DatabasePool>::Connection) -> Fut + Send + Sync + 'static,
     Fut: std::future::Future<Output = Result<(), DB::Error>> + Send + 'static,
     TxFut: std::future::Future<Output = Result<(), DB::Error>> + Send + 'static,
-    for<'tx> TFn: FnOnce(&'tx mut <TestContext<DB> as TransactionStarter<DB>>::Transaction) -> TxFut
+    for<'tx> TFn: FnOnce(&'tx mut <TestDatabaseInstance<DB> as TransactionManager>::Tx) -> TxFut
         + Send
         + Sync
         + 'static,
-    TestContext<DB>: TransactionStarter<DB>
-        + DBTransactionManager<
-            <TestContext<DB> as TransactionStarter<DB>>::Transaction,
-            <TestContext<DB> as TransactionStarter<DB>>::Connection,
-            Error = DB::Error,
-            Tx = <TestContext<DB> as TransactionStarter<DB>>::Transaction,
-        >,
+    TestDatabaseInstance<DB>: TransactionManager,
+    DB::Error: From<<TestDatabaseInstance<DB> as TransactionManager>::Error>,
 {
     /// Execute the entire operation chain
     #[allow(dead_code)]
@@ -83,37 +78,25 @@ where
         // Execute the setup function
         self.db.setup(self.setup_fn).await?;
 
-        // Create the context
-        let mut ctx = TestContext::new(self.db);
-
-        // Begin the transaction using explicit types
-        let mut tx = <TestContext<DB> as DBTransactionManager<
-            <TestContext<DB> as TransactionStarter<DB>>::Transaction,
-            <TestContext<DB> as TransactionStarter<DB>>::Connection,
-        >>::begin_transaction(&mut ctx)
-        .await?;
+        // Begin the transaction on its own connection
+        let mut tx = self.db.begin_transaction().await?;
 
         // Execute the transaction function
         match (self.transaction_fn)(&mut tx).await {
             Ok(_) => {
                 // Commit the transaction
-                <TestContext<DB> as DBTransactionManager<
-                    <TestContext<DB> as TransactionStarter<DB>>::Transaction,
-                    <TestContext<DB> as TransactionStarter<DB>>::Connection,
-                >>::commit_transaction(&mut tx)
-                .await?;
+                <TestDatabaseInstance<DB> as TransactionManager>::commit_transaction(&mut tx)
+                    .await?;
             }
             Err(e) => {
                 // Rollback the transaction on error
-                let _ = <TestContext<DB> as DBTransactionManager<
-                    <TestContext<DB> as TransactionStarter<DB>>::Transaction,
-                    <TestContext<DB> as TransactionStarter<DB>>::Connection,
-                >>::rollback_transaction(&mut tx)
-                .await;
+                let _ =
+                    <TestDatabaseInstance<DB> as TransactionManager>::rollback_transaction(&mut tx)
+                        .await;
                 return Err(e);
             }
         }
 
-        Ok(ctx)
+        Ok(TestContext::new(self.db))
     }
 }
@@ -1,18 +1,37 @@
-mod context;
 mod handlers;
+mod harness;
 mod testdb;
 mod tracing;
 pub mod utils;
 
 // Re-exported types and traits
-pub use context::*;
 pub use handlers::*;
+pub use harness::*;
 pub use testdb::*;
 pub use tracing::*;
 pub use utils::*;
 
+// The tracing glob also exports a `Value` trait, name the SQL value explicitly so it wins
+pub use testdb::Value;
+
 // The boxed_async macro is already exported with #[macro_export]
 
+/// Attribute macros for writing database tests
+///
+/// The macros live in their own module so that `use testkit_core::*` doesn't shadow the
+/// built-in `#[test]` attribute. Alias the module to write `#[testkit::test]`:
+///
+/// ```rust,ignore
+/// use testkit_core::macros as testkit;
+///
+/// #[testkit::test(backend = "postgres")]
+/// async fn creates_a_database(ctx: testkit_core::TestContext) {
+///     assert!(!ctx.db.name().as_str().is_empty());
+/// }
+/// ```
+#[cfg(feature = "macros")]
+pub use testkit_macros as macros;
+
 use std::{fmt::Debug, pin::Pin};
 
 /// A test context that contains a database instance
@@ -40,6 +59,39 @@ where
     pub fn new(db: TestDatabaseInstance<DB>) -> Self {
         Self { db }
     }
+
+    /// Returns the statements executed on the test database so far, see
+    /// [`TestDatabaseInstance::queries`]
+    pub fn queries(&self) -> Vec<Statement> {
+        self.db.queries()
+    }
+
+    /// Poll a query until a predicate over its rows holds, see
+    /// [`TestDatabaseInstance::wait_until`]
+    pub async fn wait_until<P>(
+        &self,
+        sql: &str,
+        predicate: P,
+        timeout: std::time::Duration,
+        interval: std::time::Duration,
+    ) -> Result<Vec<Row>, DB::Error>
+    where
+        DB::Connection: TestConnection,
+        P: Fn(&[Row]) -> bool,
+    {
+        self.db.wait_until(sql, predicate, timeout, interval).await
+    }
+
+    /// Returns the rows a function changes, see [`TestDatabaseInstance::capture_changes`]
+    pub async fn capture_changes<F, Fut, E>(&self, operation: F) -> Result<Changes, DB::Error>
+    where
+        DB::Connection: TestConnection,
+        F: FnOnce(DB::Connection) -> Fut,
+        Fut: std::future::Future<Output = Result<(), E>>,
+        E: std::fmt::Display,
+    {
+        self.db.capture_changes(operation).await
+    }
 }
 
 /// Testing utilities for working with database handlers in a mock environment
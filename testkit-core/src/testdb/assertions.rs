@@ -0,0 +1,313 @@
+use std::fmt::Write as _;
+
+use super::{Row, Value, snapshot_diff};
+
+/// Returns true if a value read from the database equals an expected value
+///
+/// Tolerates the differences between backends and column types: integers match equal
+/// floats and decimals read as text, MySQL booleans read as 0 and 1 match `true` and
+/// `false`, and text read as bytes matches the same string.
+pub fn value_matches(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Int(a), Value::Float(b)) | (Value::Float(b), Value::Int(a)) => *a as f64 == *b,
+        (Value::Text(s), Value::Int(i)) => s.parse::<f64>().is_ok_and(|x| x == *i as f64),
+        (Value::Text(s), Value::Float(x)) => s.parse::<f64>().is_ok_and(|parsed| parsed == *x),
+        (Value::Int(i), Value::Bool(b)) => *i == i64::from(*b),
+        (Value::Bytes(bytes), Value::Text(s)) => bytes.as_slice() == s.as_bytes(),
+        _ => actual == expected,
+    }
+}
+
+/// Compare the rows of a query with the expected values, column by column
+///
+/// Returns a table of the expected and actual rows with the differing rows marked by
+/// `-` and `+` when they don't match.
+pub fn compare_rows(actual: &[Row], expected: &[Vec<Value>]) -> Result<(), String> {
+    let matches = actual.len() == expected.len()
+        && actual.iter().zip(expected).all(|(row, values)| {
+            row.values().len() == values.len()
+                && row
+                    .values()
+                    .iter()
+                    .zip(values)
+                    .all(|(a, e)| value_matches(a, e))
+        });
+    if matches {
+        return Ok(());
+    }
+
+    let columns: Vec<String> = actual
+        .first()
+        .map(|row| row.columns().to_vec())
+        .unwrap_or_default();
+    let actual: Vec<Vec<Value>> = actual
+        .iter()
+        .zip(expected.iter().map(Some).chain(std::iter::repeat(None)))
+        .map(|(row, expected)| {
+            // Cells matching the expected value are shown like it, so only real
+            // differences show up in the diff
+            row.values()
+                .iter()
+                .enumerate()
+                .map(|(i, value)| match expected.and_then(|e| e.get(i)) {
+                    Some(e) if value_matches(value, e) => e.clone(),
+                    _ => value.clone(),
+                })
+                .collect()
+        })
+        .collect();
+
+    let widths = column_widths(&columns, expected.iter().chain(&actual));
+    let header = render_line(&columns, &widths);
+    let expected_table: String = expected.iter().map(|r| render_row(r, &widths)).collect();
+    let actual_table: String = actual.iter().map(|r| render_row(r, &widths)).collect();
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "expected {} rows, got {} (- expected, + actual):",
+        expected.len(),
+        actual.len()
+    );
+    if !columns.is_empty() {
+        let _ = writeln!(out, "  {}", header);
+    }
+    out.push_str(&snapshot_diff(&expected_table, &actual_table));
+    Err(out)
+}
+
+/// Render rows as a table with a header, for failure messages
+pub fn render_rows(rows: &[Row]) -> String {
+    let Some(first) = rows.first() else {
+        return "(no rows)\n".to_string();
+    };
+    let values: Vec<Vec<Value>> = rows.iter().map(|row| row.values().to_vec()).collect();
+    let widths = column_widths(first.columns(), &values);
+
+    let mut out = format!("{}\n", render_line(first.columns(), &widths));
+    for row in &values {
+        out.push_str(&render_row(row, &widths));
+    }
+    out
+}
+
+/// How a value is written in a table, with text quoted to tell it apart from numbers
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Text(s) => format!("{:?}", s),
+        Value::Bytes(bytes) => match std::str::from_utf8(bytes) {
+            Ok(s) => format!("{:?}", s),
+            Err(_) => value.to_string(),
+        },
+        _ => value.to_string(),
+    }
+}
+
+fn column_widths<'a>(
+    columns: &[String],
+    rows: impl IntoIterator<Item = &'a Vec<Value>>,
+) -> Vec<usize> {
+    let mut widths: Vec<usize> = columns.iter().map(|c| c.chars().count()).collect();
+    for row in rows {
+        for (i, value) in row.iter().enumerate() {
+            let width = cell(value).chars().count();
+            match widths.get_mut(i) {
+                Some(w) => *w = (*w).max(width),
+                None => widths.push(width),
+            }
+        }
+    }
+    widths
+}
+
+fn render_line(cells: &[String], widths: &[usize]) -> String {
+    let cells: Vec<String> = cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, width)| format!("{:width$}", cell, width = width))
+        .collect();
+    format!("| {} |", cells.join(" | "))
+}
+
+fn render_row(values: &[Value], widths: &[usize]) -> String {
+    let cells: Vec<String> = values.iter().map(cell).collect();
+    format!("{}\n", render_line(&cells, widths))
+}
+
+/// Run a query in an assertion, failing with the query when it fails
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __assert_query {
+    ($conn:expr, $sql:expr) => {{
+        use $crate::TestConnection as _;
+        let sql: &str = $sql;
+        match $conn.query(sql, &[]).await {
+            ::std::result::Result::Ok(rows) => (sql, rows),
+            ::std::result::Result::Err(e) => ::std::panic!("query failed: {}\nquery: {}", e, sql),
+        }
+    }};
+}
+
+/// Compare the rows of a query with a list of tuples
+///
+/// Takes a connection, the query and the expected rows, as tuples or single values for
+/// one column. Values are compared with [`value_matches`], so `1` matches an INT, BIGINT
+/// or NUMERIC column on every backend. Must be called in an async function; a mismatch
+/// prints a table diff of the rows.
+///
+/// ```rust,ignore
+/// assert_rows!(conn, "SELECT id, name FROM users ORDER BY id", [(1, "Alice"), (2, "Bob")]);
+/// assert_rows!(conn, "SELECT name FROM users ORDER BY id", ["Alice", "Bob"]);
+/// ```
+#[macro_export]
+macro_rules! assert_rows {
+    (@compare $conn:expr, $sql:expr, $expected:expr) => {{
+        let (sql, rows) = $crate::__assert_query!($conn, $sql);
+        let expected: ::std::vec::Vec<::std::vec::Vec<$crate::Value>> = $expected;
+        if let ::std::result::Result::Err(diff) = $crate::compare_rows(&rows, &expected) {
+            ::std::panic!("query: {}\n{}", sql, diff);
+        }
+    }};
+    ($conn:expr, $sql:expr, [$(($($value:expr),* $(,)?)),* $(,)?]) => {
+        $crate::assert_rows!(@compare $conn, $sql, ::std::vec![
+            $(::std::vec![$($crate::Value::from($value)),*]),*
+        ])
+    };
+    ($conn:expr, $sql:expr, [$($value:expr),* $(,)?]) => {
+        $crate::assert_rows!(@compare $conn, $sql, ::std::vec![
+            $(::std::vec![$crate::Value::from($value)]),*
+        ])
+    };
+}
+
+/// Compare the single value a query returns, such as a `COUNT(*)`
+///
+/// ```rust,ignore
+/// assert_scalar!(conn, "SELECT COUNT(*) FROM users", 2);
+/// ```
+#[macro_export]
+macro_rules! assert_scalar {
+    ($conn:expr, $sql:expr, $expected:expr $(,)?) => {{
+        let (sql, rows) = $crate::__assert_query!($conn, $sql);
+        let expected = $crate::Value::from($expected);
+        match rows.as_slice() {
+            [row] if row.values().len() == 1 => {
+                let actual = &row.values()[0];
+                if !$crate::value_matches(actual, &expected) {
+                    ::std::panic!("expected {:?}, got {:?}\nquery: {}", expected, actual, sql);
+                }
+            }
+            _ => ::std::panic!(
+                "expected a single value, got:\n{}query: {}",
+                $crate::render_rows(&rows),
+                sql
+            ),
+        }
+    }};
+}
+
+/// Fail if a query returns no rows
+///
+/// ```rust,ignore
+/// assert_exists!(conn, "SELECT 1 FROM users WHERE email = 'alice@example.com'");
+/// ```
+#[macro_export]
+macro_rules! assert_exists {
+    ($conn:expr, $sql:expr $(,)?) => {{
+        let (sql, rows) = $crate::__assert_query!($conn, $sql);
+        if rows.is_empty() {
+            ::std::panic!("expected rows, the query returned none\nquery: {}", sql);
+        }
+    }};
+}
+
+/// Fail if a query returns rows, printing them
+///
+/// ```rust,ignore
+/// assert_empty!(conn, "SELECT * FROM orders WHERE total < 0");
+/// ```
+#[macro_export]
+macro_rules! assert_empty {
+    ($conn:expr, $sql:expr $(,)?) => {{
+        let (sql, rows) = $crate::__assert_query!($conn, $sql);
+        if !rows.is_empty() {
+            ::std::panic!(
+                "expected no rows, got {}:\n{}query: {}",
+                rows.len(),
+                $crate::render_rows(&rows),
+                sql
+            );
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn rows(values: Vec<Vec<Value>>) -> Vec<Row> {
+        let columns: Arc<[String]> = vec!["id".to_string(), "name".to_string()].into();
+        values
+            .into_iter()
+            .map(|values| Row::new(columns.clone(), values))
+            .collect()
+    }
+
+    #[test]
+    fn test_value_matches() {
+        assert!(value_matches(&Value::Int(1), &Value::Int(1)));
+        assert!(value_matches(&Value::Int(2), &Value::Float(2.0)));
+        assert!(value_matches(
+            &Value::Text("1.50".into()),
+            &Value::Float(1.5)
+        ));
+        assert!(value_matches(&Value::Int(1), &Value::Bool(true)));
+        assert!(value_matches(
+            &Value::Bytes(b"a".to_vec()),
+            &Value::Text("a".into())
+        ));
+        assert!(value_matches(&Value::Null, &Value::Null));
+        assert!(!value_matches(
+            &Value::Text("1".into()),
+            &Value::Text("1.0".into())
+        ));
+        assert!(!value_matches(&Value::Int(1), &Value::Text("1".into())));
+        assert!(!value_matches(&Value::Null, &Value::Int(0)));
+    }
+
+    #[test]
+    fn test_compare_rows() {
+        let actual = rows(vec![
+            vec![Value::Int(1), Value::Bytes(b"Alice".to_vec())],
+            vec![Value::Int(2), Value::Text("Bob".into())],
+        ]);
+        let expected = vec![
+            vec![Value::from(1), Value::from("Alice")],
+            vec![Value::from(2), Value::from("Bob")],
+        ];
+        assert!(compare_rows(&actual, &expected).is_ok());
+
+        let expected = vec![
+            vec![Value::from(1), Value::from("Alice")],
+            vec![Value::from(2), Value::from("Robert")],
+        ];
+        assert_eq!(
+            compare_rows(&actual, &expected).unwrap_err(),
+            "expected 2 rows, got 2 (- expected, + actual):\n\
+             \x20 | id | name     |\n\
+             \x20 | 1  | \"Alice\"  |\n\
+             - | 2  | \"Robert\" |\n\
+             + | 2  | \"Bob\"    |\n"
+        );
+        assert!(compare_rows(&actual[..1], &expected).is_err());
+    }
+
+    #[test]
+    fn test_render_rows() {
+        let actual = rows(vec![vec![Value::Int(10), Value::Null]]);
+        assert_eq!(render_rows(&actual), "| id | name |\n| 10 | NULL |\n");
+        assert_eq!(render_rows(&[]), "(no rows)\n");
+    }
+}
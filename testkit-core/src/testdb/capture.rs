@@ -0,0 +1,443 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Write as _};
+use std::future::Future;
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use super::Value;
+
+/// Number of executions after which statements of the same shape count as an N+1 pattern
+pub const N_PLUS_ONE_THRESHOLD: usize = 3;
+
+/// The statement logs of the test databases, by connection string
+static LOGS: LazyLock<Mutex<HashMap<String, Arc<StatementLog>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// A statement executed through a test connection
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    /// The SQL text as it was sent
+    pub sql: String,
+    /// The bound parameters
+    pub params: Vec<Value>,
+    /// How long the server took to answer
+    pub duration: Duration,
+    /// The rows returned by a query or affected by a statement, unknown for batches
+    pub rows: Option<u64>,
+    /// The error the statement failed with
+    pub error: Option<String>,
+}
+
+impl Statement {
+    /// Returns the SQL text with literals and placeholders replaced by `?`
+    ///
+    /// Statements that differ only in their values have the same shape, so a query run
+    /// once per row of another query shows up as many statements of one shape.
+    pub fn shape(&self) -> String {
+        statement_shape(&self.sql)
+    }
+}
+
+impl Display for Statement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.sql.split_whitespace().collect::<Vec<_>>().join(" ")
+        )?;
+        if !self.params.is_empty() {
+            write!(f, " {:?}", self.params)?;
+        }
+        write!(f, " ({:.1?}", self.duration)?;
+        match (&self.rows, &self.error) {
+            (_, Some(error)) => write!(f, ", failed: {})", error),
+            (Some(rows), None) => write!(f, ", {} rows)", rows),
+            (None, None) => write!(f, ")"),
+        }
+    }
+}
+
+/// Statements of one shape executed repeatedly, see [`n_plus_one`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepeatedStatement {
+    /// The shape shared by the statements
+    pub shape: String,
+    /// How often the shape was executed
+    pub count: usize,
+}
+
+/// The statements recorded for one database
+#[derive(Debug, Default)]
+pub(crate) struct StatementLog {
+    statements: Mutex<Vec<Statement>>,
+}
+
+impl StatementLog {
+    /// Start recording the statements of the connections to a database
+    pub(crate) fn register(connection_string: &str) -> Arc<Self> {
+        let log = Arc::new(Self::default());
+        LOGS.lock()
+            .insert(connection_string.to_string(), log.clone());
+        log
+    }
+
+    /// Stop recording the statements of the connections to a database
+    pub(crate) fn unregister(connection_string: &str) {
+        LOGS.lock().remove(connection_string);
+    }
+
+    pub(crate) fn statements(&self) -> Vec<Statement> {
+        self.statements.lock().clone()
+    }
+
+    pub(crate) fn clear(&self) {
+        self.statements.lock().clear();
+    }
+}
+
+tokio::task_local! {
+    /// Set while testkit reads a database for itself
+    static UNRECORDED: ();
+}
+
+/// Run a future without recording the statements it sends, for testkit's own queries
+pub(crate) async fn unrecorded<F: Future>(future: F) -> F::Output {
+    UNRECORDED.scope((), future).await
+}
+
+/// Run a statement and record it in the log of the database it is sent to
+///
+/// Backends wrap the statements of their connections with it; `rows` counts the rows of
+/// a successful result. Statements to databases that don't belong to a
+/// [`TestDatabaseInstance`](crate::TestDatabaseInstance) are not recorded, and neither
+/// are the reads of [`DataSnapshot`](crate::DataSnapshot).
+pub async fn record<T, E, F>(
+    connection_string: &str,
+    sql: &str,
+    params: &[Value],
+    rows: impl FnOnce(&T) -> Option<u64>,
+    statement: F,
+) -> Result<T, E>
+where
+    E: Display,
+    F: Future<Output = Result<T, E>>,
+{
+    let recorded = UNRECORDED.try_with(|_| ()).is_err();
+    let log = LOGS.lock().get(connection_string).cloned();
+    let Some(log) = log.filter(|_| recorded) else {
+        return statement.await;
+    };
+
+    let started = Instant::now();
+    let result = statement.await;
+    let duration = started.elapsed();
+
+    let (rows, error) = match &result {
+        Ok(value) => (rows(value), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    log.statements.lock().push(Statement {
+        sql: sql.to_string(),
+        params: params.to_vec(),
+        duration,
+        rows,
+        error,
+    });
+
+    result
+}
+
+/// Returns the shapes executed at least `threshold` times, most frequent first
+pub fn n_plus_one(statements: &[Statement], threshold: usize) -> Vec<RepeatedStatement> {
+    let mut counts: Vec<RepeatedStatement> = Vec::new();
+    for statement in statements {
+        let shape = statement.shape();
+        match counts.iter_mut().find(|r| r.shape == shape) {
+            Some(repeated) => repeated.count += 1,
+            None => counts.push(RepeatedStatement { shape, count: 1 }),
+        }
+    }
+
+    counts.retain(|r| r.count >= threshold);
+    counts.sort_by_key(|r| std::cmp::Reverse(r.count));
+    counts
+}
+
+/// Returns a numbered list of statements for failure messages
+pub fn describe_statements(statements: &[Statement]) -> String {
+    let mut out = String::new();
+    for (i, statement) in statements.iter().enumerate() {
+        let _ = writeln!(out, "  {}. {}", i + 1, statement);
+    }
+    out
+}
+
+/// Replace the literals and placeholders of a statement with `?`
+///
+/// Also collapses whitespace and lists of values, so `IN (1, 2)` and `IN (3, 4, 5)` have
+/// the same shape.
+pub fn statement_shape(sql: &str) -> String {
+    let mut shape = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    let mut previous = ' ';
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                // A string literal, '' escapes a quote
+                while let Some(c) = chars.next() {
+                    if c == '\'' {
+                        if chars.peek() == Some(&'\'') {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                shape.push('?');
+            }
+            '"' | '`' => {
+                // A quoted identifier is kept
+                shape.push(c);
+                for ident in chars.by_ref() {
+                    shape.push(ident);
+                    if ident == c {
+                        break;
+                    }
+                }
+            }
+            '$' if chars.peek().is_some_and(char::is_ascii_digit) => {
+                while chars.peek().is_some_and(char::is_ascii_digit) {
+                    chars.next();
+                }
+                shape.push('?');
+            }
+            c if c.is_ascii_digit() && !(previous.is_alphanumeric() || previous == '_') => {
+                while chars
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_digit() || *c == '.')
+                {
+                    chars.next();
+                }
+                shape.push('?');
+            }
+            c if c.is_whitespace() => {
+                if !shape.ends_with(' ') && !shape.is_empty() {
+                    shape.push(' ');
+                }
+            }
+            c => shape.push(c),
+        }
+        previous = c;
+    }
+
+    // Lists of values differ only in their length
+    let mut shape = shape.trim_end().to_string();
+    while shape.contains("?, ?") || shape.contains("?,?") {
+        shape = shape.replace("?, ?", "?").replace("?,?", "?");
+    }
+    while shape.contains("(?), (?)") {
+        shape = shape.replace("(?), (?)", "(?)");
+    }
+    shape
+}
+
+/// Count the statements executed in a block and compare the count
+///
+/// Takes the `TestContext`, a comparison such as `<= 3` or `== 1` and the block, whose
+/// value is returned. The statements of every connection to the test database count,
+/// and a failure lists them.
+///
+/// ```rust,ignore
+/// let users = assert_query_count!(ctx, <= 3, {
+///     list_users_with_orders(&conn).await?
+/// });
+/// ```
+#[macro_export]
+macro_rules! assert_query_count {
+    ($ctx:expr, $op:tt $expected:expr, $body:block) => {{
+        let before = $ctx.db.queries().len();
+        let value = $body;
+        let statements = $ctx.db.queries().split_off(before);
+        let expected: usize = $expected;
+        if !(statements.len() $op expected) {
+            ::std::panic!(
+                "expected {} {} statements, executed {}:\n{}",
+                ::std::stringify!($op),
+                expected,
+                statements.len(),
+                $crate::describe_statements(&statements)
+            );
+        }
+        value
+    }};
+}
+
+/// Fail if a block executes statements of the same shape repeatedly
+///
+/// Flags the N+1 pattern of running one query per row of another query. Statements
+/// count as repeated after [`N_PLUS_ONE_THRESHOLD`] executions, or `threshold = n`.
+///
+/// ```rust,ignore
+/// assert_no_n_plus_one!(ctx, {
+///     list_users_with_orders(&conn).await?
+/// });
+/// ```
+#[macro_export]
+macro_rules! assert_no_n_plus_one {
+    ($ctx:expr, $body:block) => {
+        $crate::assert_no_n_plus_one!($ctx, threshold = $crate::N_PLUS_ONE_THRESHOLD, $body)
+    };
+    ($ctx:expr, threshold = $threshold:expr, $body:block) => {{
+        let before = $ctx.db.queries().len();
+        let value = $body;
+        let statements = $ctx.db.queries().split_off(before);
+        let repeated = $crate::n_plus_one(&statements, $threshold);
+        if !repeated.is_empty() {
+            let shapes: ::std::vec::Vec<::std::string::String> = repeated
+                .iter()
+                .map(|r| ::std::format!("  {}x {}", r.count, r.shape))
+                .collect();
+            ::std::panic!(
+                "statements repeated {} or more times:\n{}\nexecuted:\n{}",
+                $threshold,
+                shapes.join("\n"),
+                $crate::describe_statements(&statements)
+            );
+        }
+        value
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::AssertUnwindSafe;
+
+    use super::*;
+
+    fn statement(sql: &str) -> Statement {
+        Statement {
+            sql: sql.to_string(),
+            params: Vec::new(),
+            duration: Duration::ZERO,
+            rows: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_statement_shape() {
+        assert_eq!(
+            statement_shape("SELECT * FROM orders\n  WHERE user_id = 42 AND note = 'it''s'"),
+            "SELECT * FROM orders WHERE user_id = ? AND note = ?"
+        );
+        assert_eq!(
+            statement_shape("SELECT * FROM t2 WHERE id IN ($1, $2, $3)"),
+            statement_shape("SELECT * FROM t2 WHERE id IN (?)")
+        );
+        assert_eq!(
+            statement_shape("INSERT INTO \"t 1\" VALUES (1, 'a'), (2, 'b')"),
+            "INSERT INTO \"t 1\" VALUES (?)"
+        );
+        assert_eq!(statement_shape("SELECT 1.5"), "SELECT ?");
+    }
+
+    /// Stands in for a `TestContext`, the macros only call `ctx.db.queries()`
+    struct Context {
+        db: Log,
+    }
+
+    struct Log(Mutex<Vec<Statement>>);
+
+    impl Log {
+        fn queries(&self) -> Vec<Statement> {
+            self.0.lock().clone()
+        }
+
+        fn run(&self, sql: &str) {
+            self.0.lock().push(statement(sql));
+        }
+    }
+
+    fn panic_message(result: std::thread::Result<()>) -> String {
+        let payload = result.expect_err("the assertion should fail");
+        payload
+            .downcast_ref::<String>()
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_assert_query_count() {
+        let ctx = Context {
+            db: Log(Mutex::new(vec![statement("SELECT 1")])),
+        };
+
+        let value = crate::assert_query_count!(ctx, <= 2, {
+            ctx.db.run("SELECT 2");
+            ctx.db.run("SELECT 3");
+            42
+        });
+        assert_eq!(value, 42);
+
+        let message = panic_message(std::panic::catch_unwind(AssertUnwindSafe(|| {
+            crate::assert_query_count!(ctx, == 0, {
+                ctx.db.run("SELECT * FROM users");
+            })
+        })));
+        assert!(message.starts_with("expected == 0 statements, executed 1:\n"));
+        assert!(
+            message.contains("1. SELECT * FROM users (0.0ns)"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_assert_no_n_plus_one() {
+        let ctx = Context {
+            db: Log(Mutex::new(Vec::new())),
+        };
+
+        crate::assert_no_n_plus_one!(ctx, {
+            ctx.db.run("SELECT * FROM orders WHERE user_id = 1");
+            ctx.db.run("SELECT * FROM orders WHERE user_id = 2");
+        });
+
+        let message = panic_message(std::panic::catch_unwind(AssertUnwindSafe(|| {
+            crate::assert_no_n_plus_one!(ctx, threshold = 2, {
+                ctx.db.run("SELECT * FROM orders WHERE user_id = 3");
+                ctx.db.run("SELECT * FROM orders WHERE user_id = 4");
+            })
+        })));
+        assert!(
+            message.contains("2x SELECT * FROM orders WHERE user_id = ?"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_n_plus_one() {
+        let mut statements = vec![statement("SELECT * FROM users")];
+        for id in 1..=4 {
+            statements.push(statement(&format!(
+                "SELECT * FROM orders WHERE user_id = {}",
+                id
+            )));
+        }
+        statements.push(statement("SELECT * FROM users"));
+
+        assert_eq!(
+            n_plus_one(&statements, 3),
+            vec![RepeatedStatement {
+                shape: "SELECT * FROM orders WHERE user_id = ?".to_string(),
+                count: 4
+            }]
+        );
+        assert_eq!(n_plus_one(&statements, 2).len(), 2);
+        assert!(n_plus_one(&statements, 5).is_empty());
+    }
+}
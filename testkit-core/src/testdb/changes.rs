@@ -0,0 +1,382 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+
+use super::capture::unrecorded;
+use super::migrate::MIGRATION_TABLES;
+use super::{Introspect, Row, TestConnection, Value};
+
+/// Error raised while capturing the changes to a database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureError(String);
+
+impl CaptureError {
+    /// Create a new error with the given message
+    pub fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+impl Display for CaptureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to capture changes: {}", self.0)
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+fn capture_error(e: impl Display) -> CaptureError {
+    CaptureError::new(e.to_string())
+}
+
+/// The rows of one table, ordered by primary key
+#[derive(Debug, Clone, PartialEq)]
+struct TableRows {
+    table: String,
+    /// Positions of the primary key columns, empty for tables without one
+    key: Vec<usize>,
+    rows: Vec<Row>,
+}
+
+impl TableRows {
+    fn key_of<'a>(&self, row: &'a Row) -> Key<'a> {
+        Key(self.key.iter().map(|i| &row.values()[*i]).collect())
+    }
+}
+
+/// Values identifying a row, hashable to match the rows of two snapshots
+#[derive(PartialEq)]
+struct Key<'a>(Vec<&'a Value>);
+
+impl<'a> Key<'a> {
+    fn of_values(row: &'a Row) -> Self {
+        Key(row.values().iter().collect())
+    }
+}
+
+impl Eq for Key<'_> {}
+
+impl Hash for Key<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for value in &self.0 {
+            std::mem::discriminant(*value).hash(state);
+            match value {
+                Value::Null => {}
+                Value::Bool(b) => b.hash(state),
+                Value::Int(i) => i.hash(state),
+                // 0.0 and -0.0 are equal, so they have to hash the same
+                Value::Float(x) if *x == 0.0 => 0u64.hash(state),
+                Value::Float(x) => x.to_bits().hash(state),
+                Value::Text(s) => s.hash(state),
+                Value::Bytes(bytes) => bytes.hash(state),
+            }
+        }
+    }
+}
+
+/// Count the rows of each key
+fn key_counts<'a>(rows: &'a [Row], key: impl Fn(&'a Row) -> Key<'a>) -> HashMap<Key<'a>, usize> {
+    let mut counts = HashMap::new();
+    for row in rows {
+        *counts.entry(key(row)).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// The rows of every table of a database at one point in time
+///
+/// Compare two snapshots with [`changes`](Self::changes). Usually taken by
+/// [`TestDatabaseInstance::capture_changes`](crate::TestDatabaseInstance::capture_changes).
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataSnapshot {
+    tables: Vec<TableRows>,
+}
+
+impl DataSnapshot {
+    /// Read all rows of the tables except the migration tables and the excluded ones
+    ///
+    /// The queries are not recorded in the statement log of the database.
+    pub async fn read<C: TestConnection>(conn: &C, exclude: &[&str]) -> Result<Self, CaptureError> {
+        unrecorded(Self::read_tables(conn, exclude)).await
+    }
+
+    async fn read_tables<C: TestConnection>(
+        conn: &C,
+        exclude: &[&str],
+    ) -> Result<Self, CaptureError> {
+        let dialect = conn.dialect();
+        let mut tables = Vec::new();
+
+        for table in conn.tables().await.map_err(capture_error)? {
+            if MIGRATION_TABLES.contains(&table.as_str()) || exclude.contains(&table.as_str()) {
+                continue;
+            }
+
+            let key_columns = conn
+                .indexes(&table)
+                .await
+                .map_err(capture_error)?
+                .into_iter()
+                .find(|index| index.primary)
+                .map(|index| index.columns)
+                .unwrap_or_default();
+            let order_by = if key_columns.is_empty() {
+                "1".to_string()
+            } else {
+                let quoted: Vec<String> = key_columns
+                    .iter()
+                    .map(|c| dialect.quote_identifier(c))
+                    .collect();
+                quoted.join(", ")
+            };
+
+            let sql = format!(
+                "SELECT * FROM {} ORDER BY {}",
+                dialect.quote_identifier(&table),
+                order_by
+            );
+            let rows = conn
+                .query(&sql, &[])
+                .await
+                .map_err(|e| CaptureError::new(format!("reading {}: {}", table, e)))?;
+
+            // SELECT * returns the columns in the order of the table
+            let columns = conn.columns(&table).await.map_err(capture_error)?;
+            let key = key_columns
+                .iter()
+                .filter_map(|k| columns.iter().position(|c| &c.name == k))
+                .collect();
+            tables.push(TableRows { table, key, rows });
+        }
+
+        Ok(Self { tables })
+    }
+
+    /// Returns the rows inserted, updated and deleted between this snapshot and a later one
+    ///
+    /// Covers the tables of the later snapshot; tables without a primary key only have
+    /// inserted and deleted rows.
+    pub fn changes(&self, after: &DataSnapshot) -> Changes {
+        let mut tables = Vec::new();
+
+        for after in &after.tables {
+            let before = self.tables.iter().find(|t| t.table == after.table);
+            let before_rows = before.map(|t| t.rows.as_slice()).unwrap_or_default();
+            let keyed = Some(after).filter(|t| !t.key.is_empty());
+
+            let mut changes = TableChanges {
+                table: after.table.clone(),
+                inserted: Vec::new(),
+                updated: Vec::new(),
+                deleted: Vec::new(),
+            };
+            match keyed {
+                Some(keyed) => {
+                    let before_keys: HashMap<Key, &Row> = before_rows
+                        .iter()
+                        .map(|row| (keyed.key_of(row), row))
+                        .collect();
+                    for row in &after.rows {
+                        match before_keys.get(&keyed.key_of(row)) {
+                            Some(old) if old.values() != row.values() => {
+                                changes.updated.push(RowUpdate {
+                                    before: (*old).clone(),
+                                    after: row.clone(),
+                                })
+                            }
+                            Some(_) => {}
+                            None => changes.inserted.push(row.clone()),
+                        }
+                    }
+
+                    let after_keys: HashSet<Key> =
+                        after.rows.iter().map(|row| keyed.key_of(row)).collect();
+                    changes.deleted = before_rows
+                        .iter()
+                        .filter(|old| !after_keys.contains(&keyed.key_of(old)))
+                        .cloned()
+                        .collect();
+                }
+                None => {
+                    // Without a key, rows only appear and disappear. Identical rows are
+                    // matched up one for one.
+                    let mut before_counts = key_counts(before_rows, Key::of_values);
+                    for row in &after.rows {
+                        match before_counts.get_mut(&Key::of_values(row)) {
+                            Some(count) if *count > 0 => *count -= 1,
+                            _ => changes.inserted.push(row.clone()),
+                        }
+                    }
+
+                    let mut after_counts = key_counts(&after.rows, Key::of_values);
+                    for old in before_rows {
+                        match after_counts.get_mut(&Key::of_values(old)) {
+                            Some(count) if *count > 0 => *count -= 1,
+                            _ => changes.deleted.push(old.clone()),
+                        }
+                    }
+                }
+            }
+
+            if !changes.is_empty() {
+                tables.push(changes);
+            }
+        }
+
+        Changes { tables }
+    }
+}
+
+/// A row changed by an UPDATE
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowUpdate {
+    /// The row before the change
+    pub before: Row,
+    /// The row after the change
+    pub after: Row,
+}
+
+impl RowUpdate {
+    /// Returns the columns whose value changed
+    pub fn changed_columns(&self) -> Vec<&str> {
+        self.after
+            .columns()
+            .iter()
+            .zip(self.after.values())
+            .filter(|(column, value)| {
+                let index = self.before.columns().iter().position(|c| c == *column);
+                index.map(|i| &self.before.values()[i]) != Some(*value)
+            })
+            .map(|(column, _)| column.as_str())
+            .collect()
+    }
+}
+
+/// The rows of one table changed between two snapshots
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableChanges {
+    /// The table
+    pub table: String,
+    /// The new rows
+    pub inserted: Vec<Row>,
+    /// The rows whose values changed, matched by primary key
+    pub updated: Vec<RowUpdate>,
+    /// The removed rows
+    pub deleted: Vec<Row>,
+}
+
+impl TableChanges {
+    /// Returns true if no row changed
+    pub fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.updated.is_empty() && self.deleted.is_empty()
+    }
+}
+
+/// The rows changed in a database, per table
+///
+/// ```rust,ignore
+/// let changes = ctx.capture_changes(|conn| async move {
+///     place_order(&conn, 42).await
+/// }).await?;
+///
+/// assert_eq!(changes.changed_tables(), vec!["orders", "stock"]);
+/// assert_eq!(changes.table("orders").unwrap().inserted.len(), 1);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Changes {
+    /// The tables with changed rows, in the order of their names
+    pub tables: Vec<TableChanges>,
+}
+
+impl Changes {
+    /// Returns true if no row of any table changed
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Returns the changes to a table, `None` if none of its rows changed
+    pub fn table(&self, table: &str) -> Option<&TableChanges> {
+        self.tables.iter().find(|t| t.table == table)
+    }
+
+    /// Returns the names of the tables with changed rows
+    pub fn changed_tables(&self) -> Vec<&str> {
+        self.tables.iter().map(|t| t.table.as_str()).collect()
+    }
+}
+
+impl Display for Changes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.tables.is_empty() {
+            return writeln!(f, "no changes");
+        }
+        for table in &self.tables {
+            writeln!(
+                f,
+                "{}: {} inserted, {} updated, {} deleted",
+                table.table,
+                table.inserted.len(),
+                table.updated.len(),
+                table.deleted.len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn rows(values: &[(i64, &str)]) -> Vec<Row> {
+        let columns: Arc<[String]> = vec!["id".to_string(), "name".to_string()].into();
+        values
+            .iter()
+            .map(|(id, name)| Row::new(columns.clone(), vec![Value::Int(*id), (*name).into()]))
+            .collect()
+    }
+
+    fn snapshot(key: Vec<usize>, values: &[(i64, &str)]) -> DataSnapshot {
+        DataSnapshot {
+            tables: vec![TableRows {
+                table: "users".to_string(),
+                key,
+                rows: rows(values),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_changes_by_key() {
+        let before = snapshot(vec![0], &[(1, "Alice"), (2, "Bob"), (3, "Carol")]);
+        let after = snapshot(vec![0], &[(1, "Alice"), (2, "Robert"), (4, "Dan")]);
+
+        let changes = before.changes(&after);
+        assert_eq!(changes.changed_tables(), vec!["users"]);
+        let users = changes.table("users").unwrap();
+        assert_eq!(users.inserted, rows(&[(4, "Dan")]));
+        assert_eq!(users.deleted, rows(&[(3, "Carol")]));
+        assert_eq!(users.updated.len(), 1);
+        assert_eq!(users.updated[0].before, rows(&[(2, "Bob")])[0]);
+        assert_eq!(users.updated[0].changed_columns(), vec!["name"]);
+        assert_eq!(
+            changes.to_string(),
+            "users: 1 inserted, 1 updated, 1 deleted\n"
+        );
+
+        assert!(after.changes(&after).is_empty());
+    }
+
+    #[test]
+    fn test_changes_without_key() {
+        let before = snapshot(vec![], &[(1, "Alice"), (1, "Alice"), (2, "Bob")]);
+        let after = snapshot(vec![], &[(1, "Alice"), (2, "Robert"), (2, "Bob")]);
+
+        let users = before.changes(&after).tables.remove(0);
+        assert_eq!(users.inserted, rows(&[(2, "Robert")]));
+        assert_eq!(users.deleted, rows(&[(1, "Alice")]));
+        assert!(users.updated.is_empty());
+    }
+}
@@ -0,0 +1,222 @@
+use async_trait::async_trait;
+use std::fmt::Display;
+
+use super::script::{self, ScriptError, SqlScript};
+use super::{Row, Value};
+
+/// The SQL dialect spoken by a database server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SqlDialect {
+    /// PostgreSQL
+    Postgres,
+    /// MySQL and MariaDB
+    MySql,
+}
+
+impl SqlDialect {
+    /// Returns the human readable name of the dialect
+    pub fn name(&self) -> &'static str {
+        match self {
+            SqlDialect::Postgres => "PostgreSQL",
+            SqlDialect::MySql => "MySQL",
+        }
+    }
+
+    /// Returns the URL schemes used by servers of this dialect
+    pub fn schemes(&self) -> &'static [&'static str] {
+        match self {
+            SqlDialect::Postgres => &["postgres", "postgresql"],
+            SqlDialect::MySql => &["mysql", "mariadb"],
+        }
+    }
+
+    /// Returns the dialect of the server a connection string points to, if it is known
+    pub fn from_url(url: &str) -> Option<Self> {
+        [SqlDialect::Postgres, SqlDialect::MySql]
+            .into_iter()
+            .find(|dialect| dialect.matches_url(url))
+    }
+
+    /// Returns true if the connection string points to a server of this dialect
+    pub fn matches_url(&self, url: &str) -> bool {
+        url.split_once("://")
+            .is_some_and(|(scheme, _)| self.schemes().contains(&scheme))
+    }
+
+    /// Returns the port servers of this dialect listen on by default
+    pub fn default_port(&self) -> u16 {
+        match self {
+            SqlDialect::Postgres => 5432,
+            SqlDialect::MySql => 3306,
+        }
+    }
+
+    /// Returns the placeholder for the query parameter at the given 1-based position
+    ///
+    /// PostgreSQL numbers its placeholders (`$1`, `$2`) while MySQL uses `?` for all of them.
+    pub fn placeholder(&self, position: usize) -> String {
+        match self {
+            SqlDialect::Postgres => format!("${}", position),
+            SqlDialect::MySql => "?".to_string(),
+        }
+    }
+
+    /// Quote a possibly schema-qualified identifier, such as `users` or `public.users`
+    pub fn quote_identifier(&self, name: &str) -> String {
+        let quote = match self {
+            SqlDialect::Postgres => '"',
+            SqlDialect::MySql => '`',
+        };
+        name.split('.')
+            .map(|part| {
+                let escaped = part.replace(quote, &format!("{0}{0}", quote));
+                format!("{0}{1}{0}", quote, escaped)
+            })
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// Render a value as a SQL literal
+    ///
+    /// Text is left untyped, so the server converts it to the type of the column it is
+    /// compared with or stored in, which makes dates, JSON and enums work as text.
+    pub fn literal(&self, value: &Value) -> String {
+        match value {
+            Value::Null => "NULL".to_string(),
+            Value::Bool(b) => if *b { "TRUE" } else { "FALSE" }.to_string(),
+            Value::Int(i) => i.to_string(),
+            Value::Float(x) if x.is_finite() => x.to_string(),
+            Value::Float(x) => format!("'{}'", x),
+            Value::Text(s) => match self {
+                SqlDialect::Postgres => format!("'{}'", s.replace('\'', "''")),
+                SqlDialect::MySql => {
+                    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "''"))
+                }
+            },
+            Value::Bytes(bytes) => {
+                let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+                match self {
+                    SqlDialect::Postgres => format!("'\\x{}'", hex),
+                    SqlDialect::MySql => format!("X'{}'", hex),
+                }
+            }
+        }
+    }
+
+    /// Returns the environment variable holding the connection string for this dialect
+    ///
+    /// Used when tests run against several servers at once, see
+    /// [`DatabaseConfig::for_dialect`](crate::DatabaseConfig::for_dialect).
+    pub fn url_env(&self) -> &'static str {
+        match self {
+            SqlDialect::Postgres => "POSTGRES_DATABASE_URL",
+            SqlDialect::MySql => "MYSQL_DATABASE_URL",
+        }
+    }
+}
+
+impl Display for SqlDialect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A backend-agnostic view of a test database connection
+///
+/// Every connection type of the backend crates implements this trait, so helpers and
+/// test bodies written against it compile for all of them. Parameters and results use
+/// the neutral [`Value`] and [`Row`] types; placeholders follow the dialect of the
+/// server, see [`SqlDialect::placeholder`].
+#[async_trait]
+pub trait TestConnection: Send + Sync {
+    /// The error returned by the connection
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Returns the SQL dialect of the server this connection talks to
+    fn dialect(&self) -> SqlDialect;
+
+    /// Execute a single statement and return the number of affected rows
+    async fn execute(&self, sql: &str, params: &[Value]) -> Result<u64, Self::Error>;
+
+    /// Run a query and return all rows
+    async fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>, Self::Error>;
+
+    /// Execute one or more SQL statements separated by semicolons, without parameters
+    async fn batch_execute(&self, sql: &str) -> Result<(), Self::Error>;
+
+    /// Execute a SQL script, given as text or as the path of a `.sql` file
+    ///
+    /// Unlike [`batch_execute`](Self::batch_execute), the script is split into statements
+    /// with the rules of the [`dialect`](Self::dialect), so Postgres `$$` bodies and
+    /// `COPY ... FROM stdin` blocks or MySQL `DELIMITER` sections work as they do in `psql`
+    /// and the `mysql` client. The statements run in order, and the error of a failing one
+    /// names its line.
+    async fn execute_script<S>(&self, script: S) -> Result<(), ScriptError>
+    where
+        S: Into<SqlScript> + Send,
+    {
+        script::execute_script(self, &script.into()).await
+    }
+
+    /// Insert a row and return it as stored, with defaults and generated ids filled in
+    ///
+    /// `key` names the column identifying the row. PostgreSQL returns the row with
+    /// `RETURNING *`. MySQL reads it back by the value of `key`, or by `LAST_INSERT_ID()`
+    /// when the database generates the key, which only works if both statements run in
+    /// the same session. Connections that spread queries over a pool override this.
+    ///
+    /// Returns `None` if the row can't be found after inserting it.
+    async fn insert_returning(
+        &self,
+        table: &str,
+        key: &str,
+        values: &[(String, Value)],
+    ) -> Result<Option<Row>, Self::Error> {
+        let dialect = self.dialect();
+        let insert = insert_sql(dialect, table, values);
+
+        match dialect {
+            SqlDialect::Postgres => {
+                let rows = self.query(&format!("{} RETURNING *", insert), &[]).await?;
+                Ok(rows.into_iter().next())
+            }
+            SqlDialect::MySql => {
+                self.execute(&insert, &[]).await?;
+                let id = match values.iter().find(|(column, _)| column == key) {
+                    Some((_, value)) => dialect.literal(value),
+                    None => "LAST_INSERT_ID()".to_string(),
+                };
+                let select = format!(
+                    "SELECT * FROM {} WHERE {} = {}",
+                    dialect.quote_identifier(table),
+                    dialect.quote_identifier(key),
+                    id
+                );
+                Ok(self.query(&select, &[]).await?.into_iter().next())
+            }
+        }
+    }
+}
+
+/// Build an INSERT statement for a single row with the values inlined as literals
+fn insert_sql(dialect: SqlDialect, table: &str, values: &[(String, Value)]) -> String {
+    let table = dialect.quote_identifier(table);
+    if values.is_empty() {
+        return match dialect {
+            SqlDialect::Postgres => format!("INSERT INTO {} DEFAULT VALUES", table),
+            SqlDialect::MySql => format!("INSERT INTO {} () VALUES ()", table),
+        };
+    }
+
+    let columns: Vec<String> = values
+        .iter()
+        .map(|(column, _)| dialect.quote_identifier(column))
+        .collect();
+    let literals: Vec<String> = values.iter().map(|(_, v)| dialect.literal(v)).collect();
+    format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        table,
+        columns.join(", "),
+        literals.join(", ")
+    )
+}
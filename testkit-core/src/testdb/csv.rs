@@ -0,0 +1,164 @@
+use std::path::Path;
+
+use tokio::io::AsyncBufReadExt;
+
+/// Options for bulk loading CSV files with the `load_csv` methods of the backends
+///
+/// The defaults read a comma separated file with a header line, `"` quotes, and
+/// unquoted empty fields stored as NULL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// Whether the first line holds the column names
+    pub header: bool,
+    /// The columns the fields are stored in, in file order
+    ///
+    /// Defaults to the names in the header, or to all columns of the table when the file
+    /// has no header.
+    pub columns: Option<Vec<String>>,
+    /// The character separating fields
+    pub delimiter: char,
+    /// The character quoting fields
+    pub quote: char,
+    /// The unquoted field value stored as NULL
+    pub null: String,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            header: true,
+            columns: None,
+            delimiter: ',',
+            quote: '"',
+            null: String::new(),
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Read a file without a header line
+    pub fn without_header(mut self) -> Self {
+        self.header = false;
+        self
+    }
+
+    /// Store the fields in the given columns instead of the ones named by the header
+    pub fn with_columns<I, S>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.columns = Some(columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Separate fields with the given character
+    pub fn with_delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Store fields with the given unquoted value as NULL, such as `NULL` or `\N`
+    pub fn with_null(mut self, null: impl Into<String>) -> Self {
+        self.null = null.into();
+        self
+    }
+
+    /// Returns the columns the fields of a file are stored in
+    ///
+    /// Explicit [`columns`](Self::columns) win over the header. `None` means the fields
+    /// map to all columns of the table in order.
+    pub async fn resolve_columns(&self, path: &Path) -> std::io::Result<Option<Vec<String>>> {
+        if self.columns.is_some() || !self.header {
+            return Ok(self.columns.clone());
+        }
+
+        let mut line = String::new();
+        tokio::io::BufReader::new(tokio::fs::File::open(path).await?)
+            .read_line(&mut line)
+            .await?;
+        let line = line.strip_prefix('\u{feff}').unwrap_or(&line);
+        Ok(Some(split_line(
+            line.trim_end_matches(['\r', '\n']),
+            self.delimiter,
+            self.quote,
+        )))
+    }
+}
+
+/// Split a single CSV line into its fields
+fn split_line(line: &str, delimiter: char, quote: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            if c == quote {
+                // A doubled quote inside a quoted field escapes it
+                if chars.peek() == Some(&quote) {
+                    field.push(quote);
+                    chars.next();
+                } else {
+                    quoted = false;
+                }
+            } else {
+                field.push(c);
+            }
+        } else if c == quote {
+            quoted = true;
+        } else if c == delimiter {
+            fields.push(std::mem::take(&mut field).trim().to_string());
+        } else {
+            field.push(c);
+        }
+    }
+    fields.push(field.trim().to_string());
+
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_line() {
+        assert_eq!(
+            split_line("id,name, email ", ',', '"'),
+            ["id", "name", "email"]
+        );
+        assert_eq!(
+            split_line("\"a;b\";\"say \"\"hi\"\"\";", ';', '"'),
+            ["a;b", "say \"hi\"", ""]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_columns() {
+        let path = std::env::temp_dir().join(format!("testkit_{}.csv", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "\u{feff}id,\"full name\"\r\n1,Alice\r\n").unwrap();
+
+        let options = CsvOptions::default();
+        assert_eq!(
+            options.resolve_columns(&path).await.unwrap(),
+            Some(vec!["id".to_string(), "full name".to_string()])
+        );
+        let options = CsvOptions::default().with_columns(["a", "b"]);
+        assert_eq!(
+            options.resolve_columns(&path).await.unwrap(),
+            Some(vec!["a".to_string(), "b".to_string()])
+        );
+        assert_eq!(
+            CsvOptions::default()
+                .without_header()
+                .resolve_columns(&path)
+                .await
+                .unwrap(),
+            None
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
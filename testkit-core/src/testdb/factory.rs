@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::LazyLock;
+
+use parking_lot::Mutex;
+
+use super::{Row, TestConnection, TestDatabaseConnection, Value, ValueError};
+
+/// The next sequence number per database and table
+static SEQUENCES: LazyLock<Mutex<HashMap<(String, &'static str), u64>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The future returned by [`Model::associate`]
+pub type AssociateFuture<'a> = Pin<Box<dyn Future<Output = Result<(), FactoryError>> + Send + 'a>>;
+
+/// A struct stored as a row of a table, created by a [`Factory`]
+///
+/// Usually derived with `#[derive(Factory)]` from `testkit_core::macros`, which maps the
+/// fields to the columns of the same name:
+///
+/// ```rust,ignore
+/// use testkit_core::macros as testkit;
+///
+/// #[derive(Debug, Clone, testkit::Factory)]
+/// #[factory(table = "users")]
+/// struct User {
+///     #[factory(generated)]
+///     id: i32,
+///     #[factory(sequence = "user{n}@example.com")]
+///     email: String,
+///     #[factory(default = "Alice")]
+///     name: String,
+///     admin: bool,
+/// }
+///
+/// #[derive(Debug, Clone, testkit::Factory)]
+/// #[factory(table = "orders")]
+/// struct Order {
+///     #[factory(generated)]
+///     id: i32,
+///     #[factory(association = User)]
+///     user_id: i32,
+///     total: i32,
+/// }
+/// ```
+///
+/// Field attributes:
+///
+/// - `primary_key` - the column identifying a row, defaults to the field named `id`
+/// - `generated` - filled in by the database and left out of the INSERT
+/// - `sequence` or `sequence = "format"` - the sequence number of the instance, or a
+///   string formatted with it as `{n}`
+/// - `default = expr` - the default value, converted with `Into`; other fields use
+///   `Default::default()`
+/// - `association = Type` - the primary key of a row created with `Factory::<Type>`
+///   when the field still holds its default value
+/// - `column = "name"` - the column the field is stored in
+/// - `skip` - not stored in the table
+pub trait Model: Sized + Send + Sync + 'static {
+    /// The table rows are stored in
+    const TABLE: &'static str;
+
+    /// The column identifying a row
+    const PRIMARY_KEY: &'static str;
+
+    /// Build an instance with default values
+    ///
+    /// `n` numbers the instances built for a database, starting at 1.
+    fn build(n: u64) -> Self;
+
+    /// Returns the columns and values to insert, without generated columns
+    fn values(&self) -> Vec<(String, Value)>;
+
+    /// Read an instance from a stored row
+    fn from_row(row: &Row) -> Result<Self, ValueError>;
+
+    /// Returns the value of the primary key
+    fn primary_key(&self) -> Value;
+
+    /// Create the rows this instance references and store their keys
+    fn associate<'a, C>(&'a mut self, conn: &'a C) -> AssociateFuture<'a>
+    where
+        C: TestConnection + TestDatabaseConnection,
+    {
+        let _ = conn;
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Errors raised while creating rows with a [`Factory`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FactoryError {
+    /// Inserting a row failed
+    Insert { table: String, message: String },
+    /// The inserted row couldn't be read back into its type
+    Decode { table: String, message: String },
+}
+
+impl Display for FactoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FactoryError::Insert { table, message } => {
+                write!(f, "failed to insert a row into {}: {}", table, message)
+            }
+            FactoryError::Decode { table, message } => {
+                write!(
+                    f,
+                    "failed to read the row inserted into {}: {}",
+                    table, message
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for FactoryError {}
+
+/// Customizations applied to the built instances
+type Customizer<M> = Box<dyn Fn(&mut M, u64) + Send + Sync>;
+
+/// Builds and inserts rows of a [`Model`]
+///
+/// Each instance gets the next sequence number of its table in the database it is created
+/// in, so unique fields stay unique across the factories of a test while separate test
+/// databases count on their own. Rows the instance references through associations are
+/// created first, and the returned instances hold the rows as stored, with generated ids
+/// and defaults filled in.
+///
+/// ```rust,ignore
+/// let admins = Factory::<User>::new()
+///     .with(|u| u.admin = true)
+///     .create_many(&conn, 10)
+///     .await?;
+/// ```
+pub struct Factory<M: Model> {
+    customizers: Vec<Customizer<M>>,
+}
+
+impl<M: Model> Default for Factory<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M: Model> Factory<M> {
+    /// Create a factory building instances with the defaults of the model
+    pub fn new() -> Self {
+        Self {
+            customizers: Vec::new(),
+        }
+    }
+
+    /// Change the built instances before they are inserted
+    pub fn with(mut self, customize: impl Fn(&mut M) + Send + Sync + 'static) -> Self {
+        self.customizers
+            .push(Box::new(move |model, _| customize(model)));
+        self
+    }
+
+    /// Change the built instances, given their sequence number
+    pub fn with_sequence(
+        mut self,
+        customize: impl Fn(&mut M, u64) + Send + Sync + 'static,
+    ) -> Self {
+        self.customizers.push(Box::new(customize));
+        self
+    }
+
+    /// Build an instance with the given sequence number without inserting it
+    pub fn build(&self, n: u64) -> M {
+        let mut model = M::build(n);
+        for customize in &self.customizers {
+            customize(&mut model, n);
+        }
+        model
+    }
+
+    /// Build an instance, create its associations and insert it
+    pub async fn create<C>(&self, conn: &C) -> Result<M, FactoryError>
+    where
+        C: TestConnection + TestDatabaseConnection,
+    {
+        let n = next_sequence(&conn.connection_string(), M::TABLE);
+        let mut model = self.build(n);
+        model.associate(conn).await?;
+
+        let row = conn
+            .insert_returning(M::TABLE, M::PRIMARY_KEY, &model.values())
+            .await
+            .map_err(|e| FactoryError::Insert {
+                table: M::TABLE.to_string(),
+                message: e.to_string(),
+            })?;
+        let decode_error = |message: String| FactoryError::Decode {
+            table: M::TABLE.to_string(),
+            message,
+        };
+        let row = row.ok_or_else(|| decode_error("the row wasn't found".to_string()))?;
+        M::from_row(&row).map_err(|e| decode_error(e.to_string()))
+    }
+
+    /// Create `count` instances, one after the other
+    pub async fn create_many<C>(&self, conn: &C, count: usize) -> Result<Vec<M>, FactoryError>
+    where
+        C: TestConnection + TestDatabaseConnection,
+    {
+        let mut models = Vec::with_capacity(count);
+        for _ in 0..count {
+            models.push(self.create(conn).await?);
+        }
+        Ok(models)
+    }
+}
+
+/// Returns the next sequence number of a table in the given database, starting at 1
+fn next_sequence(database: &str, table: &'static str) -> u64 {
+    let mut sequences = SEQUENCES.lock();
+    let n = sequences.entry((database.to_string(), table)).or_insert(0);
+    *n += 1;
+    *n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_sequence() {
+        let database = format!("postgres://localhost/testkit_{}", uuid::Uuid::new_v4());
+        let other = format!("postgres://localhost/testkit_{}", uuid::Uuid::new_v4());
+
+        assert_eq!(next_sequence(&database, "users"), 1);
+        assert_eq!(next_sequence(&database, "users"), 2);
+        assert_eq!(next_sequence(&database, "orders"), 1);
+        assert_eq!(next_sequence(&other, "users"), 1);
+        assert_eq!(next_sequence(&database, "users"), 3);
+    }
+}
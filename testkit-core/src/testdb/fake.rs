@@ -0,0 +1,214 @@
+use uuid::Uuid;
+
+/// Environment variable that fixes the seed of the fake data generators
+pub const SEED_ENV: &str = "TESTKIT_SEED";
+
+const FIRST_NAMES: &[&str] = &[
+    "Alice", "Amir", "Ana", "Ben", "Carla", "Chen", "Daniel", "Dmitri", "Elena", "Emma", "Farah",
+    "Felix", "Grace", "Hannah", "Hiro", "Isaac", "Ines", "James", "Julia", "Kai", "Karen", "Liam",
+    "Lucia", "Maya", "Mohammed", "Nina", "Noah", "Olga", "Omar", "Priya", "Quinn", "Rosa", "Sam",
+    "Sofia", "Tariq", "Tom", "Uma", "Victor", "Wei", "Yuki", "Zara",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "Adams", "Alvarez", "Brown", "Chen", "Cohen", "Diaz", "Evans", "Fischer", "Garcia", "Gupta",
+    "Hansen", "Ito", "Jones", "Kim", "Kowalski", "Lee", "Lopez", "Martin", "Meyer", "Miller",
+    "Nguyen", "Novak", "Okafor", "Patel", "Petrov", "Rossi", "Santos", "Schmidt", "Silva", "Smith",
+    "Suzuki", "Taylor", "Thompson", "Walker", "Wang", "Williams", "Yilmaz", "Young",
+];
+
+const DOMAINS: &[&str] = &["example.com", "example.org", "example.net"];
+
+/// Seconds from 1970-01-01 to 2000-01-01 and 2030-01-01, the range of generated timestamps
+const TIMESTAMP_RANGE: (i64, i64) = (946_684_800, 1_893_456_000);
+
+/// A deterministic generator of fake data
+///
+/// The same seed produces the same values on every run and platform, so data generated
+/// for a failed test can be reproduced byte for byte. Test databases hand out generators
+/// with [`TestDatabaseInstance::fake`](crate::TestDatabaseInstance::fake), seeded from
+/// `TESTKIT_SEED` or the database name; the seed is printed when a test fails.
+///
+/// ```rust,ignore
+/// let mut fake = ctx.db.fake();
+/// let name = fake.name();
+/// let email = fake.email();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Fake {
+    seed: u64,
+    state: u64,
+}
+
+impl Fake {
+    /// Create a generator with the given seed
+    pub fn new(seed: u64) -> Self {
+        Self { seed, state: seed }
+    }
+
+    /// Returns the seed the generator started from
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the next random number
+    pub fn u64(&mut self) -> u64 {
+        // SplitMix64, which keeps the output independent of any library version
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `min..=max`
+    pub fn int(&mut self, min: i64, max: i64) -> i64 {
+        assert!(min <= max, "empty range {}..={}", min, max);
+        let span = (max as i128 - min as i128 + 1) as u128;
+        (min as i128 + (self.u64() as u128 % span) as i128) as i64
+    }
+
+    /// Returns a number in `0.0..1.0`
+    pub fn float(&mut self) -> f64 {
+        (self.u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns true with the given probability
+    pub fn bool(&mut self, probability: f64) -> bool {
+        self.float() < probability
+    }
+
+    /// Returns one of the given items
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        assert!(!items.is_empty(), "can't pick from an empty slice");
+        &items[self.int(0, items.len() as i64 - 1) as usize]
+    }
+
+    /// Returns a first name
+    pub fn first_name(&mut self) -> String {
+        self.pick(FIRST_NAMES).to_string()
+    }
+
+    /// Returns a last name
+    pub fn last_name(&mut self) -> String {
+        self.pick(LAST_NAMES).to_string()
+    }
+
+    /// Returns a first and last name
+    pub fn name(&mut self) -> String {
+        format!("{} {}", self.first_name(), self.last_name())
+    }
+
+    /// Returns an email address at a reserved example domain
+    pub fn email(&mut self) -> String {
+        let first = self.first_name().to_lowercase();
+        let last = self.last_name().to_lowercase();
+        let number = self.int(1, 9999);
+        format!("{}.{}{}@{}", first, last, number, self.pick(DOMAINS))
+    }
+
+    /// Returns a random (version 4) UUID
+    pub fn uuid(&mut self) -> Uuid {
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&self.u64().to_be_bytes());
+        bytes[8..].copy_from_slice(&self.u64().to_be_bytes());
+        uuid::Builder::from_random_bytes(bytes).into_uuid()
+    }
+
+    /// Returns a timestamp between 2000 and 2030 as `YYYY-MM-DD HH:MM:SS`
+    pub fn timestamp(&mut self) -> String {
+        let seconds = self.int(TIMESTAMP_RANGE.0, TIMESTAMP_RANGE.1 - 1);
+        format_timestamp(seconds)
+    }
+
+    /// Returns a date between 2000 and 2030 as `YYYY-MM-DD`
+    pub fn date(&mut self) -> String {
+        let mut timestamp = self.timestamp();
+        timestamp.truncate(10);
+        timestamp
+    }
+}
+
+/// Returns the seed from `TESTKIT_SEED`, if it is set to a number
+pub fn seed_from_env() -> Option<u64> {
+    let value = std::env::var(SEED_ENV).ok()?;
+    match value.trim().parse() {
+        Ok(seed) => Some(seed),
+        Err(_) => {
+            tracing::warn!("Ignoring {}={}, expected a number", SEED_ENV, value);
+            None
+        }
+    }
+}
+
+/// Format seconds since the Unix epoch as `YYYY-MM-DD HH:MM:SS` in UTC
+fn format_timestamp(seconds: i64) -> String {
+    let days = seconds.div_euclid(86_400);
+    let time = seconds.rem_euclid(86_400);
+
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_values() {
+        let mut a = Fake::new(42);
+        let mut b = Fake::new(42);
+        let values = |fake: &mut Fake| {
+            (
+                fake.name(),
+                fake.email(),
+                fake.uuid(),
+                fake.timestamp(),
+                fake.int(-5, 5),
+            )
+        };
+        assert_eq!(values(&mut a), values(&mut b));
+        assert_ne!(values(&mut Fake::new(42)), values(&mut Fake::new(43)));
+
+        // Pinned, so a change of the algorithm doesn't go unnoticed
+        assert_eq!(Fake::new(0).u64(), 0xe220_a839_7b1d_cdaf);
+    }
+
+    #[test]
+    fn test_ranges() {
+        let mut fake = Fake::new(7);
+        for _ in 0..1000 {
+            assert!((1..=6).contains(&fake.int(1, 6)));
+            assert!((0.0..1.0).contains(&fake.float()));
+            let date = fake.date();
+            assert!(date.as_str() >= "2000-01-01" && date.as_str() < "2030-01-01");
+        }
+        assert_eq!(fake.int(i64::MIN, i64::MIN), i64::MIN);
+        assert_eq!(fake.uuid().get_version_num(), 4);
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00:00");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14 22:13:20");
+    }
+}
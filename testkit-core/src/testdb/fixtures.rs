@@ -0,0 +1,551 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use super::{Introspect, SqlDialect, TestConnection, Value};
+
+/// Number of rows inserted with a single statement
+const BATCH_SIZE: usize = 100;
+
+/// Rows to insert into one table
+///
+/// Fixtures are usually read from YAML or JSON files with [`Fixtures::from_files`]. A file
+/// either holds a list of rows for the table named like the file:
+///
+/// ```yaml
+/// # users.yaml
+/// - { id: 1, email: alice@example.com }
+/// - { id: 2, email: bob@example.com }
+/// ```
+///
+/// or a map from table names to their rows:
+///
+/// ```yaml
+/// users:
+///   - { id: 1, email: alice@example.com }
+/// orders:
+///   - { id: 1, user_id: 1, total: 9.99 }
+/// ```
+///
+/// Nested lists and maps are stored as JSON text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fixture {
+    /// The table the rows are inserted into
+    pub table: String,
+    /// The rows as column names and values, in the order of the file
+    pub rows: Vec<Vec<(String, Value)>>,
+    /// Where the rows came from, used in error messages
+    pub source: String,
+}
+
+impl Fixture {
+    /// Create a fixture without rows for the given table
+    pub fn new(table: impl Into<String>) -> Self {
+        let table = table.into();
+        Self {
+            source: table.clone(),
+            table,
+            rows: Vec::new(),
+        }
+    }
+
+    /// Add a row
+    pub fn row<I, K, V>(mut self, values: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        self.rows.push(
+            values
+                .into_iter()
+                .map(|(column, value)| (column.into(), value.into()))
+                .collect(),
+        );
+        self
+    }
+
+    /// Read the fixtures of a `.yaml`, `.yml` or `.json` file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Vec<Fixture>, FixtureError> {
+        let path = path.as_ref();
+        let source = path.display().to_string();
+        let text = std::fs::read_to_string(path).map_err(|e| FixtureError::Io {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+        let parse_error = |message: String| FixtureError::Parse {
+            source: source.clone(),
+            message,
+        };
+
+        let document: serde_json::Value = match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml" | "yml") => {
+                serde_yaml::from_str(&text).map_err(|e| parse_error(e.to_string()))?
+            }
+            Some("json") => serde_json::from_str(&text).map_err(|e| parse_error(e.to_string()))?,
+            _ => return Err(FixtureError::UnsupportedFormat(path.to_path_buf())),
+        };
+
+        let stem = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| FixtureError::UnsupportedFormat(path.to_path_buf()))?;
+
+        let tables = match document {
+            serde_json::Value::Null => Vec::new(),
+            serde_json::Value::Array(rows) => vec![(stem.to_string(), rows)],
+            serde_json::Value::Object(tables) => tables
+                .into_iter()
+                .map(|(table, rows)| match rows {
+                    serde_json::Value::Array(rows) => Ok((table, rows)),
+                    _ => Err(parse_error(format!("the rows of {} must be a list", table))),
+                })
+                .collect::<Result<_, _>>()?,
+            _ => {
+                return Err(parse_error(
+                    "expected a list of rows or a map from tables to rows".to_string(),
+                ));
+            }
+        };
+
+        tables
+            .into_iter()
+            .map(|(table, rows)| {
+                let rows = rows
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, row)| match row {
+                        serde_json::Value::Object(columns) => Ok(columns
+                            .into_iter()
+                            .map(|(column, value)| (column, json_to_value(value)))
+                            .collect()),
+                        _ => Err(parse_error(format!(
+                            "row {} of {} is not a map",
+                            i + 1,
+                            table
+                        ))),
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                Ok(Fixture {
+                    table,
+                    rows,
+                    source: source.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Errors raised while reading or loading fixtures
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FixtureError {
+    /// A fixture file couldn't be read
+    Io { path: PathBuf, message: String },
+    /// A fixture file isn't YAML or JSON
+    UnsupportedFormat(PathBuf),
+    /// A fixture file doesn't contain rows
+    Parse { source: String, message: String },
+    /// Inserting rows failed
+    Insert {
+        source: String,
+        table: String,
+        message: String,
+    },
+    /// Inspecting the schema or resetting a sequence failed
+    Database(String),
+}
+
+impl Display for FixtureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FixtureError::Io { path, message } => {
+                write!(f, "failed to read {}: {}", path.display(), message)
+            }
+            FixtureError::UnsupportedFormat(path) => write!(
+                f,
+                "unsupported fixture file {}, expected .yaml, .yml or .json",
+                path.display()
+            ),
+            FixtureError::Parse { source, message } => {
+                write!(f, "invalid fixture file {}: {}", source, message)
+            }
+            FixtureError::Insert {
+                source,
+                table,
+                message,
+            } => write!(
+                f,
+                "failed to insert the rows of {} from {}: {}",
+                table, source, message
+            ),
+            FixtureError::Database(message) => write!(f, "failed to load fixtures: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for FixtureError {}
+
+/// A set of fixtures that can be loaded into a database
+///
+/// Tables are filled parents first, following the foreign keys of the database, so the
+/// files can be listed in any order. Afterwards the sequences of the filled tables are
+/// moved past the inserted ids (`AUTO_INCREMENT` on MySQL), so rows created by the test
+/// don't collide with the fixtures.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Fixtures {
+    fixtures: Vec<Fixture>,
+}
+
+impl Fixtures {
+    /// Create a set from fixtures built in code
+    pub fn new(fixtures: Vec<Fixture>) -> Self {
+        Self { fixtures }
+    }
+
+    /// Read the fixtures of several files, see [`Fixture::from_file`]
+    pub fn from_files<I, P>(paths: I) -> Result<Self, FixtureError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut fixtures = Vec::new();
+        for path in paths {
+            fixtures.extend(Fixture::from_file(path)?);
+        }
+        Ok(Self { fixtures })
+    }
+
+    /// Returns the fixtures in the order they were given
+    pub fn fixtures(&self) -> &[Fixture] {
+        &self.fixtures
+    }
+
+    /// Insert all rows and reset the sequences of the filled tables
+    pub async fn load<C: TestConnection>(&self, conn: &C) -> Result<(), FixtureError> {
+        let dialect = conn.dialect();
+        let foreign_keys = foreign_keys(conn).await?;
+
+        for fixture in insertion_order(&self.fixtures, &foreign_keys) {
+            for sql in insert_statements(dialect, fixture) {
+                conn.batch_execute(&sql)
+                    .await
+                    .map_err(|e| FixtureError::Insert {
+                        source: fixture.source.clone(),
+                        table: fixture.table.clone(),
+                        message: e.to_string(),
+                    })?;
+            }
+        }
+
+        let mut tables: Vec<&str> = Vec::new();
+        for fixture in &self.fixtures {
+            if !tables.contains(&fixture.table.as_str()) {
+                tables.push(&fixture.table);
+            }
+        }
+        for table in tables {
+            reset_sequences(conn, table).await?;
+        }
+
+        Ok(())
+    }
+}
+
+fn json_to_value(value: serde_json::Value) -> Value {
+    match value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(b) => Value::Bool(b),
+        serde_json::Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => Value::Int(i),
+            (None, Some(x)) if !n.is_u64() => Value::Float(x),
+            _ => Value::Text(n.to_string()),
+        },
+        serde_json::Value::String(s) => Value::Text(s),
+        nested => Value::Text(nested.to_string()),
+    }
+}
+
+/// The name used to match a fixture table against the tables of the database
+fn table_key(table: &str) -> String {
+    table.rsplit('.').next().unwrap_or(table).to_lowercase()
+}
+
+/// Returns the (child, parent) table pairs of the foreign keys in the database
+async fn foreign_keys<C: TestConnection>(conn: &C) -> Result<Vec<(String, String)>, FixtureError> {
+    let foreign_keys = Introspect::foreign_keys(conn)
+        .await
+        .map_err(database_error)?;
+    Ok(foreign_keys
+        .iter()
+        .map(|key| (table_key(&key.table), table_key(&key.referenced_table)))
+        .collect())
+}
+
+/// Order fixtures so that referenced tables are filled before the tables referencing them
+///
+/// Fixtures keep the order they were given in where the foreign keys allow it. Tables
+/// that reference each other are filled in the given order.
+fn insertion_order<'a>(
+    fixtures: &'a [Fixture],
+    foreign_keys: &[(String, String)],
+) -> Vec<&'a Fixture> {
+    let mut tables: Vec<String> = Vec::new();
+    for fixture in fixtures {
+        let key = table_key(&fixture.table);
+        if !tables.contains(&key) {
+            tables.push(key);
+        }
+    }
+
+    let mut parents: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (child, parent) in foreign_keys {
+        if child != parent && tables.contains(child) && tables.contains(parent) {
+            parents.entry(child).or_default().push(parent);
+        }
+    }
+
+    let mut ordered: Vec<&str> = Vec::new();
+    while ordered.len() < tables.len() {
+        let remaining = tables.iter().filter(|t| !ordered.contains(&t.as_str()));
+        let next = remaining
+            .clone()
+            .find(|t| {
+                parents
+                    .get(t.as_str())
+                    .is_none_or(|ps| ps.iter().all(|p| ordered.contains(p)))
+            })
+            .or_else(|| remaining.clone().next());
+        match next {
+            Some(table) => ordered.push(table),
+            None => break,
+        }
+    }
+
+    ordered
+        .into_iter()
+        .flat_map(|table| {
+            fixtures
+                .iter()
+                .filter(move |fixture| table_key(&fixture.table) == table)
+        })
+        .collect()
+}
+
+/// Build the INSERT statements for a fixture
+///
+/// Consecutive rows with the same columns share a statement, up to [`BATCH_SIZE`] rows.
+/// Values are inlined as literals, so the server converts them to the column types.
+fn insert_statements(dialect: SqlDialect, fixture: &Fixture) -> Vec<String> {
+    let table = dialect.quote_identifier(&fixture.table);
+    let mut statements = Vec::new();
+
+    let mut start = 0;
+    while start < fixture.rows.len() {
+        let columns: Vec<&str> = fixture.rows[start]
+            .iter()
+            .map(|(c, _)| c.as_str())
+            .collect();
+        let same_columns = fixture.rows[start..]
+            .iter()
+            .take(BATCH_SIZE)
+            .take_while(|row| {
+                row.iter()
+                    .map(|(c, _)| c.as_str())
+                    .eq(columns.iter().copied())
+            })
+            .count();
+        let batch = &fixture.rows[start..start + same_columns];
+        start += same_columns;
+
+        if columns.is_empty() {
+            let values = match dialect {
+                SqlDialect::Postgres => "DEFAULT VALUES",
+                SqlDialect::MySql => "() VALUES ()",
+            };
+            statements.extend(
+                batch
+                    .iter()
+                    .map(|_| format!("INSERT INTO {} {}", table, values)),
+            );
+            continue;
+        }
+
+        let columns = columns
+            .iter()
+            .map(|c| dialect.quote_identifier(c))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let rows = batch
+            .iter()
+            .map(|row| {
+                let values: Vec<String> = row.iter().map(|(_, v)| dialect.literal(v)).collect();
+                format!("({})", values.join(", "))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        statements.push(format!(
+            "INSERT INTO {} ({}) VALUES {}",
+            table, columns, rows
+        ));
+    }
+
+    statements
+}
+
+/// Move the sequences of a table past the largest value in their columns
+async fn reset_sequences<C: TestConnection>(conn: &C, table: &str) -> Result<(), FixtureError> {
+    let dialect = conn.dialect();
+    let quoted = dialect.quote_identifier(table);
+
+    match dialect {
+        SqlDialect::Postgres => {
+            let name = dialect.literal(&Value::Text(quoted.clone()));
+            let sql = format!(
+                "SELECT a.attname::text FROM pg_attribute a
+                 WHERE a.attrelid = {0}::regclass AND a.attnum > 0 AND NOT a.attisdropped
+                   AND pg_get_serial_sequence({0}, a.attname) IS NOT NULL",
+                name
+            );
+            let rows = conn.query(&sql, &[]).await.map_err(database_error)?;
+
+            for row in rows {
+                let column: String = row.try_get(0).map_err(database_error)?;
+                let sql = format!(
+                    "SELECT setval(pg_get_serial_sequence({}, {}), COALESCE(MAX({}), 0) + 1, false) FROM {}",
+                    name,
+                    dialect.literal(&Value::Text(column.clone())),
+                    dialect.quote_identifier(&column),
+                    quoted
+                );
+                conn.batch_execute(&sql).await.map_err(database_error)?;
+            }
+        }
+        SqlDialect::MySql => {
+            // InnoDB raises a counter set below the largest value to the value after it
+            let sql = format!("ALTER TABLE {} AUTO_INCREMENT = 1", quoted);
+            conn.batch_execute(&sql).await.map_err(database_error)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn database_error(e: impl Display) -> FixtureError {
+    FixtureError::Database(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_file(name: &str, contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("testkit_fixtures_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_fixture_from_file() {
+        let path = write_file(
+            "users.yaml",
+            "- { id: 1, email: alice@example.com, admin: true, tags: [a, b] }\n- id: 2\n  email: null\n",
+        );
+        let fixtures = Fixture::from_file(&path).unwrap();
+        assert_eq!(fixtures.len(), 1);
+        assert_eq!(fixtures[0].table, "users");
+        assert_eq!(
+            fixtures[0].rows[0],
+            [
+                ("id".to_string(), Value::Int(1)),
+                ("email".to_string(), Value::Text("alice@example.com".into())),
+                ("admin".to_string(), Value::Bool(true)),
+                ("tags".to_string(), Value::Text("[\"a\",\"b\"]".into())),
+            ]
+        );
+        assert_eq!(fixtures[0].rows[1][1], ("email".to_string(), Value::Null));
+
+        let path = write_file(
+            "data.json",
+            r#"{"users": [{"id": 1}], "orders": [{"id": 1, "user_id": 1, "total": 9.5}]}"#,
+        );
+        let fixtures = Fixture::from_file(&path).unwrap();
+        assert_eq!(
+            fixtures
+                .iter()
+                .map(|f| f.table.as_str())
+                .collect::<Vec<_>>(),
+            ["users", "orders"]
+        );
+
+        let path = write_file("users.csv", "id\n1\n");
+        assert!(matches!(
+            Fixture::from_file(&path),
+            Err(FixtureError::UnsupportedFormat(_))
+        ));
+        let path = write_file("users.yaml", "- 1\n");
+        assert!(matches!(
+            Fixture::from_file(&path),
+            Err(FixtureError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn test_insertion_order() {
+        let fixtures = vec![
+            Fixture::new("order_items"),
+            Fixture::new("public.orders"),
+            Fixture::new("users"),
+            Fixture::new("tags"),
+        ];
+        let foreign_keys = vec![
+            ("order_items".to_string(), "orders".to_string()),
+            ("orders".to_string(), "users".to_string()),
+            ("users".to_string(), "users".to_string()),
+            ("orders".to_string(), "coupons".to_string()),
+        ];
+
+        let order: Vec<&str> = insertion_order(&fixtures, &foreign_keys)
+            .iter()
+            .map(|f| f.table.as_str())
+            .collect();
+        assert_eq!(order, ["users", "public.orders", "order_items", "tags"]);
+
+        // Tables referencing each other keep the given order
+        let cycle = vec![
+            ("a".to_string(), "b".to_string()),
+            ("b".to_string(), "a".to_string()),
+        ];
+        let fixtures = vec![Fixture::new("b"), Fixture::new("a")];
+        let order: Vec<&str> = insertion_order(&fixtures, &cycle)
+            .iter()
+            .map(|f| f.table.as_str())
+            .collect();
+        assert_eq!(order, ["b", "a"]);
+    }
+
+    #[test]
+    fn test_insert_statements() {
+        let fixture = Fixture::new("users")
+            .row([("id", Value::Int(1)), ("name", Value::from("O'Neil"))])
+            .row([("id", Value::Int(2)), ("name", Value::Null)])
+            .row([("id", 3)]);
+
+        assert_eq!(
+            insert_statements(SqlDialect::Postgres, &fixture),
+            [
+                "INSERT INTO \"users\" (\"id\", \"name\") VALUES (1, 'O''Neil'), (2, NULL)",
+                "INSERT INTO \"users\" (\"id\") VALUES (3)",
+            ]
+        );
+        assert_eq!(
+            insert_statements(SqlDialect::MySql, &Fixture::new("t").row([("v", "a\\b")])),
+            ["INSERT INTO `t` (`v`) VALUES ('a\\\\b')"]
+        );
+
+        let many = (0..250).fold(Fixture::new("t"), |f, i| f.row([("id", i)]));
+        assert_eq!(insert_statements(SqlDialect::Postgres, &many).len(), 3);
+    }
+}
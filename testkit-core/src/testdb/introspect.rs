@@ -0,0 +1,423 @@
+use async_trait::async_trait;
+
+use super::{Row, SchemaError, SqlDialect, TestConnection};
+
+/// Separates the names aggregated into one text column by the Postgres queries
+const SEPARATOR: char = '\u{1f}';
+
+/// A column of a table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    /// The table the column belongs to
+    pub table: String,
+    /// The name of the column
+    pub name: String,
+    /// The type as the server writes it, such as `character varying(50)` or `varchar(50)`
+    pub data_type: String,
+    /// Whether the column accepts NULL
+    pub nullable: bool,
+    /// The default expression
+    pub default: Option<String>,
+    /// How the server fills the column, `always` or `by default` for Postgres identity
+    /// columns and the `EXTRA` attributes on MySQL, such as `auto_increment`
+    pub generated: Option<String>,
+}
+
+/// A foreign key constraint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKey {
+    /// The name of the constraint
+    pub name: String,
+    /// The referencing table
+    pub table: String,
+    /// The referencing columns
+    pub columns: Vec<String>,
+    /// The referenced table
+    pub referenced_table: String,
+    /// The referenced columns, in the order of `columns`
+    pub referenced_columns: Vec<String>,
+    /// The action on update, such as `NO ACTION` or `CASCADE`
+    pub on_update: String,
+    /// The action on delete
+    pub on_delete: String,
+}
+
+/// An index of a table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Index {
+    /// The table the index belongs to
+    pub table: String,
+    /// The name of the index
+    pub name: String,
+    /// The indexed columns, or the expressions of expression indexes
+    pub columns: Vec<String>,
+    /// Whether the index enforces unique values
+    pub unique: bool,
+    /// Whether the index backs the primary key
+    pub primary: bool,
+    /// The access method, such as `btree` or `hash`
+    pub method: String,
+}
+
+/// A sequence generating column values
+///
+/// MySQL has no sequences; the AUTO_INCREMENT counter of a table is reported as a
+/// sequence named after the table instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sequence {
+    /// The name of the sequence
+    pub name: String,
+    /// The table of the column the sequence belongs to
+    pub table: Option<String>,
+    /// The column the sequence belongs to
+    pub column: Option<String>,
+}
+
+/// Inspect the tables of the database a connection points to
+///
+/// Implemented for every [`TestConnection`], so the same calls work on all connection
+/// types. Postgres is inspected in the current schema and MySQL in the current database.
+///
+/// ```rust,ignore
+/// use testkit_core::Introspect;
+///
+/// for table in conn.tables().await? {
+///     let columns = conn.columns(&table).await?;
+/// }
+/// ```
+#[async_trait]
+pub trait Introspect: TestConnection + Sized {
+    /// Returns the names of the base tables, sorted
+    async fn tables(&self) -> Result<Vec<String>, SchemaError> {
+        let sql = match self.dialect() {
+            SqlDialect::Postgres => {
+                "SELECT c.relname::text
+                 FROM pg_class c JOIN pg_namespace n ON n.oid = c.relnamespace
+                 WHERE n.nspname = current_schema() AND c.relkind IN ('r', 'p')
+                 ORDER BY 1"
+            }
+            SqlDialect::MySql => {
+                "SELECT TABLE_NAME FROM information_schema.TABLES
+                 WHERE TABLE_SCHEMA = DATABASE() AND TABLE_TYPE = 'BASE TABLE'
+                 ORDER BY TABLE_NAME"
+            }
+        };
+
+        query(self, sql)
+            .await?
+            .iter()
+            .map(|row| text(row, 0))
+            .collect()
+    }
+
+    /// Returns the columns of a table in their order in the table
+    async fn columns(&self, table: &str) -> Result<Vec<Column>, SchemaError> {
+        columns(self, Some(table)).await
+    }
+
+    /// Returns the foreign keys between tables, sorted by table and name
+    ///
+    /// On Postgres this includes the tables of other schemas than the current one,
+    /// whose names are qualified with their schema.
+    async fn foreign_keys(&self) -> Result<Vec<ForeignKey>, SchemaError> {
+        match self.dialect() {
+            SqlDialect::Postgres => postgres_foreign_keys(self).await,
+            SqlDialect::MySql => mysql_foreign_keys(self).await,
+        }
+    }
+
+    /// Returns the indexes of a table, sorted by name
+    async fn indexes(&self, table: &str) -> Result<Vec<Index>, SchemaError> {
+        indexes(self, Some(table)).await
+    }
+
+    /// Returns the sequences, sorted by name
+    async fn sequences(&self) -> Result<Vec<Sequence>, SchemaError> {
+        let sql = match self.dialect() {
+            SqlDialect::Postgres => {
+                "SELECT s.relname::text, t.relname::text, a.attname::text
+                 FROM pg_class s
+                 JOIN pg_namespace n ON n.oid = s.relnamespace
+                 LEFT JOIN pg_depend d
+                   ON d.classid = 'pg_class'::regclass AND d.objid = s.oid
+                  AND d.refclassid = 'pg_class'::regclass AND d.deptype IN ('a', 'i')
+                 LEFT JOIN pg_class t ON t.oid = d.refobjid
+                 LEFT JOIN pg_attribute a ON a.attrelid = d.refobjid AND a.attnum = d.refobjsubid
+                 WHERE n.nspname = current_schema() AND s.relkind = 'S'
+                 ORDER BY 1"
+            }
+            SqlDialect::MySql => {
+                "SELECT TABLE_NAME, TABLE_NAME, COLUMN_NAME
+                 FROM information_schema.COLUMNS
+                 WHERE TABLE_SCHEMA = DATABASE() AND EXTRA LIKE '%auto_increment%'
+                 ORDER BY TABLE_NAME"
+            }
+        };
+
+        query(self, sql)
+            .await?
+            .iter()
+            .map(|row| {
+                Ok(Sequence {
+                    name: text(row, 0)?,
+                    table: optional_text(row, 1)?,
+                    column: optional_text(row, 2)?,
+                })
+            })
+            .collect()
+    }
+}
+
+impl<C: TestConnection> Introspect for C {}
+
+pub(crate) fn text(row: &Row, column: usize) -> Result<String, SchemaError> {
+    row.try_get(column)
+        .map_err(|e| SchemaError::new(e.to_string()))
+}
+
+pub(crate) fn optional_text(row: &Row, column: usize) -> Result<Option<String>, SchemaError> {
+    row.try_get(column)
+        .map_err(|e| SchemaError::new(e.to_string()))
+}
+
+fn names(row: &Row, column: usize, separator: char) -> Result<Vec<String>, SchemaError> {
+    Ok(optional_text(row, column)?
+        .map(|names| names.split(separator).map(str::to_string).collect())
+        .unwrap_or_default())
+}
+
+pub(crate) async fn query<C: TestConnection>(conn: &C, sql: &str) -> Result<Vec<Row>, SchemaError> {
+    conn.query(sql, &[])
+        .await
+        .map_err(|e| SchemaError::new(e.to_string()))
+}
+
+/// Returns the columns of one table, or of all tables ordered by table
+pub(crate) async fn columns<C: TestConnection>(
+    conn: &C,
+    table: Option<&str>,
+) -> Result<Vec<Column>, SchemaError> {
+    let dialect = conn.dialect();
+    let filter = |column: &str| {
+        table
+            .map(|table| format!(" AND {} = {}", column, dialect.literal(&table.into())))
+            .unwrap_or_default()
+    };
+
+    let sql = match dialect {
+        SqlDialect::Postgres => format!(
+            "SELECT c.relname::text, a.attname::text, format_type(a.atttypid, a.atttypmod),
+                    a.attnotnull, pg_get_expr(d.adbin, d.adrelid),
+                    CASE a.attidentity WHEN 'a' THEN 'always' WHEN 'd' THEN 'by default' END
+             FROM pg_attribute a
+             JOIN pg_class c ON c.oid = a.attrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum
+             WHERE n.nspname = current_schema() AND c.relkind IN ('r', 'p')
+               AND a.attnum > 0 AND NOT a.attisdropped{}
+             ORDER BY c.relname, a.attnum",
+            filter("c.relname")
+        ),
+        SqlDialect::MySql => format!(
+            "SELECT TABLE_NAME, COLUMN_NAME, COLUMN_TYPE, IS_NULLABLE = 'NO', COLUMN_DEFAULT, EXTRA
+             FROM information_schema.COLUMNS
+             WHERE TABLE_SCHEMA = DATABASE(){}
+             ORDER BY TABLE_NAME, ORDINAL_POSITION",
+            filter("TABLE_NAME")
+        ),
+    };
+
+    query(conn, &sql)
+        .await?
+        .iter()
+        .map(|row| {
+            let not_null: bool = row
+                .try_get(3)
+                .map_err(|e| SchemaError::new(e.to_string()))?;
+            Ok(Column {
+                table: text(row, 0)?,
+                name: text(row, 1)?,
+                data_type: text(row, 2)?,
+                nullable: !not_null,
+                default: optional_text(row, 4)?,
+                generated: optional_text(row, 5)?.filter(|generated| !generated.is_empty()),
+            })
+        })
+        .collect()
+}
+
+/// Returns the indexes of one table, or of all tables ordered by table
+pub(crate) async fn indexes<C: TestConnection>(
+    conn: &C,
+    table: Option<&str>,
+) -> Result<Vec<Index>, SchemaError> {
+    let dialect = conn.dialect();
+    let filter = |column: &str| {
+        table
+            .map(|table| format!(" AND {} = {}", column, dialect.literal(&table.into())))
+            .unwrap_or_default()
+    };
+
+    match dialect {
+        SqlDialect::Postgres => {
+            // pg_get_indexdef with a key position returns the column or the expression
+            let sql = format!(
+                "SELECT t.relname::text, i.relname::text, x.indisunique, x.indisprimary,
+                        m.amname::text,
+                        (SELECT string_agg(pg_get_indexdef(x.indexrelid, k, true), chr(31) ORDER BY k)
+                         FROM generate_series(1, x.indnkeyatts) k)
+                 FROM pg_index x
+                 JOIN pg_class i ON i.oid = x.indexrelid
+                 JOIN pg_class t ON t.oid = x.indrelid
+                 JOIN pg_am m ON m.oid = i.relam
+                 JOIN pg_namespace n ON n.oid = t.relnamespace
+                 WHERE n.nspname = current_schema(){}
+                 ORDER BY 1, 2",
+                filter("t.relname")
+            );
+
+            query(conn, &sql)
+                .await?
+                .iter()
+                .map(|row| {
+                    let flag = |column| {
+                        row.try_get::<bool>(column)
+                            .map_err(|e| SchemaError::new(e.to_string()))
+                    };
+                    Ok(Index {
+                        table: text(row, 0)?,
+                        name: text(row, 1)?,
+                        unique: flag(2)?,
+                        primary: flag(3)?,
+                        method: text(row, 4)?,
+                        columns: names(row, 5, SEPARATOR)?,
+                    })
+                })
+                .collect()
+        }
+        SqlDialect::MySql => {
+            // One row per column of an index, grouped into one index
+            let sql = format!(
+                "SELECT TABLE_NAME, INDEX_NAME, NON_UNIQUE, INDEX_TYPE, COLUMN_NAME
+                 FROM information_schema.STATISTICS
+                 WHERE TABLE_SCHEMA = DATABASE(){}
+                 ORDER BY TABLE_NAME, INDEX_NAME, SEQ_IN_INDEX",
+                filter("TABLE_NAME")
+            );
+
+            let mut indexes: Vec<Index> = Vec::new();
+            for row in &query(conn, &sql).await? {
+                let (table, name) = (text(row, 0)?, text(row, 1)?);
+                let column = optional_text(row, 4)?.unwrap_or_else(|| "expression".to_string());
+                match indexes.last_mut() {
+                    Some(index) if index.table == table && index.name == name => {
+                        index.columns.push(column)
+                    }
+                    _ => {
+                        let non_unique: i64 = row
+                            .try_get(2)
+                            .map_err(|e| SchemaError::new(e.to_string()))?;
+                        indexes.push(Index {
+                            primary: name == "PRIMARY",
+                            unique: non_unique == 0,
+                            method: text(row, 3)?.to_lowercase(),
+                            columns: vec![column],
+                            table,
+                            name,
+                        });
+                    }
+                }
+            }
+
+            Ok(indexes)
+        }
+    }
+}
+
+async fn postgres_foreign_keys<C: TestConnection>(
+    conn: &C,
+) -> Result<Vec<ForeignKey>, SchemaError> {
+    let sql = "SELECT k.conname::text,
+                      CASE WHEN cn.nspname = current_schema() THEN c.relname::text
+                           ELSE cn.nspname || '.' || c.relname END,
+                      (SELECT string_agg(a.attname::text, chr(31) ORDER BY u.i)
+                       FROM unnest(k.conkey) WITH ORDINALITY u(num, i)
+                       JOIN pg_attribute a ON a.attrelid = k.conrelid AND a.attnum = u.num),
+                      CASE WHEN pn.nspname = current_schema() THEN p.relname::text
+                           ELSE pn.nspname || '.' || p.relname END,
+                      (SELECT string_agg(a.attname::text, chr(31) ORDER BY u.i)
+                       FROM unnest(k.confkey) WITH ORDINALITY u(num, i)
+                       JOIN pg_attribute a ON a.attrelid = k.confrelid AND a.attnum = u.num),
+                      k.confupdtype::text, k.confdeltype::text
+               FROM pg_constraint k
+               JOIN pg_class c ON c.oid = k.conrelid
+               JOIN pg_namespace cn ON cn.oid = c.relnamespace
+               JOIN pg_class p ON p.oid = k.confrelid
+               JOIN pg_namespace pn ON pn.oid = p.relnamespace
+               WHERE k.contype = 'f'
+               ORDER BY 2, 1";
+
+    let action = |code: String| {
+        match code.as_str() {
+            "r" => "RESTRICT",
+            "c" => "CASCADE",
+            "n" => "SET NULL",
+            "d" => "SET DEFAULT",
+            _ => "NO ACTION",
+        }
+        .to_string()
+    };
+
+    query(conn, sql)
+        .await?
+        .iter()
+        .map(|row| {
+            Ok(ForeignKey {
+                name: text(row, 0)?,
+                table: text(row, 1)?,
+                columns: names(row, 2, SEPARATOR)?,
+                referenced_table: text(row, 3)?,
+                referenced_columns: names(row, 4, SEPARATOR)?,
+                on_update: action(text(row, 5)?),
+                on_delete: action(text(row, 6)?),
+            })
+        })
+        .collect()
+}
+
+async fn mysql_foreign_keys<C: TestConnection>(conn: &C) -> Result<Vec<ForeignKey>, SchemaError> {
+    // One row per column of a foreign key, grouped into one foreign key
+    let sql = "SELECT k.CONSTRAINT_NAME, k.TABLE_NAME, k.COLUMN_NAME,
+                      k.REFERENCED_TABLE_NAME, k.REFERENCED_COLUMN_NAME,
+                      r.UPDATE_RULE, r.DELETE_RULE
+               FROM information_schema.KEY_COLUMN_USAGE k
+               JOIN information_schema.REFERENTIAL_CONSTRAINTS r
+                 ON r.CONSTRAINT_SCHEMA = k.CONSTRAINT_SCHEMA
+                AND r.TABLE_NAME = k.TABLE_NAME
+                AND r.CONSTRAINT_NAME = k.CONSTRAINT_NAME
+               WHERE k.TABLE_SCHEMA = DATABASE() AND k.REFERENCED_TABLE_NAME IS NOT NULL
+               ORDER BY k.TABLE_NAME, k.CONSTRAINT_NAME, k.ORDINAL_POSITION";
+
+    let mut foreign_keys: Vec<ForeignKey> = Vec::new();
+    for row in &query(conn, sql).await? {
+        let (name, table) = (text(row, 0)?, text(row, 1)?);
+        let (column, referenced_column) = (text(row, 2)?, text(row, 4)?);
+        match foreign_keys.last_mut() {
+            Some(key) if key.table == table && key.name == name => {
+                key.columns.push(column);
+                key.referenced_columns.push(referenced_column);
+            }
+            _ => foreign_keys.push(ForeignKey {
+                name,
+                table,
+                columns: vec![column],
+                referenced_table: text(row, 3)?,
+                referenced_columns: vec![referenced_column],
+                on_update: text(row, 5)?,
+                on_delete: text(row, 6)?,
+            }),
+        }
+    }
+
+    Ok(foreign_keys)
+}
@@ -0,0 +1,567 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use super::{
+    DatabaseBackend, DatabasePool, DatabaseTransaction, ScriptError, SqlDialect, TestConnection,
+    TransactionalConnection, Value,
+};
+
+/// Table recording the migrations applied to a database
+pub const MIGRATIONS_TABLE: &str = "_testkit_migrations";
+
+/// Tables of migration tools, left out of snapshots and resets
+pub(crate) const MIGRATION_TABLES: &[&str] = &[MIGRATIONS_TABLE, "_sqlx_migrations"];
+
+/// A versioned SQL migration
+///
+/// Migrations are usually discovered from a directory with [`Migrator::from_dir`], where
+/// two naming schemes are understood:
+///
+/// - `V1__create_users.sql` for forward-only migrations
+/// - `0001_create_users.up.sql` with an optional `0001_create_users.down.sql` to revert it,
+///   or `0001_create_users.sql` without a down migration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migration {
+    /// The version, migrations are applied in ascending order
+    pub version: i64,
+    /// Human readable description taken from the file name
+    pub description: String,
+    /// The file the migration was read from, or its name for in-memory migrations
+    pub source: String,
+    /// The SQL applying the migration
+    pub sql: String,
+    /// The SQL reverting the migration, if there is one
+    pub down_sql: Option<String>,
+    checksum: String,
+}
+
+impl Migration {
+    /// Create a migration from SQL in memory
+    pub fn new(version: i64, description: impl Into<String>, sql: impl Into<String>) -> Self {
+        let description = description.into();
+        let sql = sql.into();
+
+        Self {
+            version,
+            source: format!("V{}__{}", version, description.replace(' ', "_")),
+            checksum: checksum(&sql),
+            description,
+            sql,
+            down_sql: None,
+        }
+    }
+
+    /// Set the SQL that reverts this migration
+    pub fn with_down(mut self, down_sql: impl Into<String>) -> Self {
+        self.down_sql = Some(down_sql.into());
+        self
+    }
+
+    /// Hex encoded SHA-256 of the SQL applying the migration
+    pub fn checksum(&self) -> &str {
+        &self.checksum
+    }
+}
+
+/// A migration recorded as applied in the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedMigration {
+    /// The version of the migration
+    pub version: i64,
+    /// The description of the migration
+    pub description: String,
+    /// The checksum of the migration when it was applied
+    pub checksum: String,
+}
+
+/// Errors reading or applying migrations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MigrationError {
+    /// A migration file or directory could not be read
+    Io { path: PathBuf, message: String },
+    /// A `.sql` file doesn't follow one of the naming schemes
+    InvalidName(PathBuf),
+    /// Two migrations share a version
+    DuplicateVersion {
+        version: i64,
+        first: String,
+        second: String,
+    },
+    /// A down migration has no matching up migration
+    MissingUp { version: i64, source: String },
+    /// An applied migration was changed after it was applied
+    ChecksumMismatch { version: i64, source: String },
+    /// A migration can't be reverted because it has no down migration
+    MissingDown { version: i64, source: String },
+    /// A statement of a migration failed
+    Statement {
+        source: String,
+        line: usize,
+        statement: String,
+        message: String,
+    },
+    /// Reading or writing the migrations table failed
+    Database(String),
+}
+
+impl Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationError::Io { path, message } => {
+                write!(f, "failed to read {}: {}", path.display(), message)
+            }
+            MigrationError::InvalidName(path) => write!(
+                f,
+                "invalid migration file name {}, expected V<version>__<name>.sql or <version>_<name>.up.sql",
+                path.display()
+            ),
+            MigrationError::DuplicateVersion {
+                version,
+                first,
+                second,
+            } => write!(
+                f,
+                "migration version {} is used by both {} and {}",
+                version, first, second
+            ),
+            MigrationError::MissingUp { version, source } => write!(
+                f,
+                "down migration {} has no up migration for version {}",
+                source, version
+            ),
+            MigrationError::ChecksumMismatch { version, source } => write!(
+                f,
+                "migration {} ({}) was modified after it was applied",
+                version, source
+            ),
+            MigrationError::MissingDown { version, source } => write!(
+                f,
+                "migration {} ({}) has no down migration",
+                version, source
+            ),
+            MigrationError::Statement {
+                source,
+                line,
+                statement,
+                message,
+            } => write!(
+                f,
+                "migration {} failed at line {}: {}\n  statement: {}",
+                source, line, message, statement
+            ),
+            MigrationError::Database(message) => {
+                write!(f, "failed to update {}: {}", MIGRATIONS_TABLE, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+/// An ordered set of migrations that can be applied to a database
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Migrator {
+    migrations: Vec<Migration>,
+}
+
+impl Migrator {
+    /// Create a migrator from migrations in any order
+    pub fn new(mut migrations: Vec<Migration>) -> Result<Self, MigrationError> {
+        migrations.sort_by_key(|m| m.version);
+
+        if let Some(pair) = migrations.windows(2).find(|w| w[0].version == w[1].version) {
+            return Err(MigrationError::DuplicateVersion {
+                version: pair[0].version,
+                first: pair[0].source.clone(),
+                second: pair[1].source.clone(),
+            });
+        }
+
+        Ok(Self { migrations })
+    }
+
+    /// Discover the migrations in a directory
+    ///
+    /// Files that don't end in `.sql` are ignored.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, MigrationError> {
+        let dir = dir.as_ref();
+        let io_error = |path: &Path, e: std::io::Error| MigrationError::Io {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        };
+
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(|e| io_error(dir, e))? {
+            let path = entry.map_err(|e| io_error(dir, e))?.path();
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "sql") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut migrations: Vec<Migration> = Vec::new();
+        let mut downs = Vec::new();
+        for path in paths {
+            let (version, description, kind) = parse_file_name(&path)?;
+            let sql = std::fs::read_to_string(&path).map_err(|e| io_error(&path, e))?;
+
+            match kind {
+                FileKind::Up => {
+                    let mut migration = Migration::new(version, description, sql);
+                    migration.source = path.display().to_string();
+                    migrations.push(migration);
+                }
+                FileKind::Down => downs.push((version, path, sql)),
+            }
+        }
+
+        for (version, path, sql) in downs {
+            // Duplicate up migrations are rejected by `new`, the first one is fine here
+            let migration = migrations
+                .iter_mut()
+                .find(|m| m.version == version)
+                .ok_or_else(|| MigrationError::MissingUp {
+                    version,
+                    source: path.display().to_string(),
+                })?;
+            migration.down_sql = Some(sql);
+        }
+
+        Self::new(migrations)
+    }
+
+    /// The migrations in the order they are applied
+    pub fn migrations(&self) -> &[Migration] {
+        &self.migrations
+    }
+
+    /// The migrations recorded as applied in the database
+    pub async fn applied<C: TestConnection>(
+        &self,
+        conn: &C,
+    ) -> Result<Vec<AppliedMigration>, MigrationError> {
+        ensure_table(conn).await?;
+
+        let sql = format!(
+            "SELECT version, description, checksum FROM {} ORDER BY version",
+            MIGRATIONS_TABLE
+        );
+        let rows = conn.query(&sql, &[]).await.map_err(database_error)?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(AppliedMigration {
+                    version: row.try_get("version").map_err(database_error)?,
+                    description: row.try_get("description").map_err(database_error)?,
+                    checksum: row.try_get("checksum").map_err(database_error)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Apply the migrations that haven't been applied yet
+    ///
+    /// The statements of a migration run one by one in the same session, so a failure can
+    /// be traced to the file and line of the statement. On PostgreSQL every migration is
+    /// applied and recorded in one transaction, so a failed migration leaves nothing behind.
+    /// MySQL commits DDL statements implicitly, so there it can stay partly applied.
+    /// Returns the versions that were applied.
+    pub async fn run<C: TransactionalConnection>(
+        &self,
+        conn: &C,
+    ) -> Result<Vec<i64>, MigrationError> {
+        let applied: HashMap<i64, String> = self
+            .applied(conn)
+            .await?
+            .into_iter()
+            .map(|m| (m.version, m.checksum))
+            .collect();
+
+        let mut versions = Vec::new();
+        for migration in &self.migrations {
+            if let Some(checksum) = applied.get(&migration.version) {
+                if checksum != &migration.checksum {
+                    return Err(MigrationError::ChecksumMismatch {
+                        version: migration.version,
+                        source: migration.source.clone(),
+                    });
+                }
+                continue;
+            }
+
+            tracing::debug!("Applying migration {}", migration.source);
+            let dialect = conn.dialect();
+            let record = format!(
+                "INSERT INTO {} (version, description, checksum) VALUES ({}, {}, {})",
+                MIGRATIONS_TABLE,
+                dialect.placeholder(1),
+                dialect.placeholder(2),
+                dialect.placeholder(3)
+            );
+            let params: [Value; 3] = [
+                migration.version.into(),
+                migration.description.as_str().into(),
+                migration.checksum.as_str().into(),
+            ];
+            apply(conn, &migration.source, &migration.sql, &record, &params).await?;
+
+            versions.push(migration.version);
+        }
+
+        Ok(versions)
+    }
+
+    /// Apply the migrations on a connection from the pool of a database
+    ///
+    /// Used by the backends to migrate every database they create, see [`WithMigrations`].
+    pub async fn run_on_pool<P>(&self, pool: &P) -> Result<Vec<i64>, MigrationError>
+    where
+        P: DatabasePool,
+        P::Connection: TransactionalConnection,
+    {
+        let conn = pool.acquire().await.map_err(database_error)?;
+        let result = self.run(&conn).await;
+        pool.release(conn).await.map_err(database_error)?;
+        result
+    }
+
+    /// Revert the applied migrations newer than `target`, newest first
+    ///
+    /// Every migration is reverted in a transaction like in [`run`](Self::run). Returns the
+    /// versions that were reverted.
+    pub async fn undo<C: TransactionalConnection>(
+        &self,
+        conn: &C,
+        target: i64,
+    ) -> Result<Vec<i64>, MigrationError> {
+        let applied = self.applied(conn).await?;
+
+        let mut versions = Vec::new();
+        for record in applied.iter().rev().filter(|m| m.version > target) {
+            let Some(migration) = self.migrations.iter().find(|m| m.version == record.version)
+            else {
+                return Err(MigrationError::MissingDown {
+                    version: record.version,
+                    source: record.description.clone(),
+                });
+            };
+            let down_sql =
+                migration
+                    .down_sql
+                    .as_deref()
+                    .ok_or_else(|| MigrationError::MissingDown {
+                        version: migration.version,
+                        source: migration.source.clone(),
+                    })?;
+
+            tracing::debug!("Reverting migration {}", migration.source);
+            let record = format!(
+                "DELETE FROM {} WHERE version = {}",
+                MIGRATIONS_TABLE,
+                conn.dialect().placeholder(1)
+            );
+            let params = [migration.version.into()];
+            apply(conn, &migration.source, down_sql, &record, &params).await?;
+
+            versions.push(migration.version);
+        }
+
+        Ok(versions)
+    }
+}
+
+/// Backends that run a set of migrations on every database they create
+///
+/// Used for migrations that a backend runs itself, such as the embedded
+/// `sqlx::migrate::Migrator` of the sqlx backends.
+pub trait WithMigrations<M>: DatabaseBackend {
+    /// Run the migrations on every database this backend creates
+    fn with_migrations(self, migrations: M) -> Self;
+}
+
+/// Whether a migration file applies or reverts its migration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileKind {
+    Up,
+    Down,
+}
+
+/// Parse the version, description and kind out of a migration file name
+fn parse_file_name(path: &Path) -> Result<(i64, String, FileKind), MigrationError> {
+    let invalid = || MigrationError::InvalidName(path.to_path_buf());
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_suffix(".sql"))
+        .ok_or_else(invalid)?;
+
+    let (version, description, kind) = if let Some(rest) = name.strip_prefix('V') {
+        let (version, description) = rest.split_once("__").ok_or_else(invalid)?;
+        (version, description, FileKind::Up)
+    } else {
+        let (name, kind) = if let Some(name) = name.strip_suffix(".up") {
+            (name, FileKind::Up)
+        } else if let Some(name) = name.strip_suffix(".down") {
+            (name, FileKind::Down)
+        } else {
+            (name, FileKind::Up)
+        };
+        let (version, description) = name.split_once('_').ok_or_else(invalid)?;
+        (version, description, kind)
+    };
+
+    if version.is_empty() || !version.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let version = version.parse().map_err(|_| invalid())?;
+
+    Ok((version, description.replace('_', " "), kind))
+}
+
+/// Run the SQL of a migration and update its record in the migrations table
+///
+/// Both happen in one transaction on PostgreSQL. MySQL would commit the transaction at
+/// the first DDL statement, so there they run on the connection directly.
+async fn apply<C: TransactionalConnection>(
+    conn: &C,
+    source: &str,
+    sql: &str,
+    record: &str,
+    params: &[Value],
+) -> Result<(), MigrationError> {
+    if conn.dialect() == SqlDialect::MySql {
+        return apply_on(conn, source, sql, record, params).await;
+    }
+
+    let mut tx = conn.begin_transaction().await.map_err(database_error)?;
+    match apply_on(&tx, source, sql, record, params).await {
+        Ok(()) => tx.commit().await.map_err(database_error),
+        Err(e) => {
+            let _ = tx.rollback().await;
+            Err(e)
+        }
+    }
+}
+
+async fn apply_on<C: TestConnection>(
+    conn: &C,
+    source: &str,
+    sql: &str,
+    record: &str,
+    params: &[Value],
+) -> Result<(), MigrationError> {
+    // The connection splits the script and keeps all statements in one session
+    conn.execute_script(sql).await.map_err(|e| match e {
+        ScriptError::Statement {
+            line,
+            statement,
+            message,
+            ..
+        } => MigrationError::Statement {
+            source: source.to_string(),
+            line,
+            statement,
+            message,
+        },
+        ScriptError::Io { path, message } => MigrationError::Io { path, message },
+    })?;
+
+    conn.execute(record, params).await.map_err(database_error)?;
+    Ok(())
+}
+
+/// Create the migrations table if it doesn't exist yet
+async fn ensure_table<C: TestConnection>(conn: &C) -> Result<(), MigrationError> {
+    let sql = format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            version BIGINT PRIMARY KEY,
+            description VARCHAR(255) NOT NULL,
+            checksum VARCHAR(64) NOT NULL,
+            applied_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        MIGRATIONS_TABLE
+    );
+
+    conn.batch_execute(&sql).await.map_err(database_error)
+}
+
+fn database_error(e: impl Display) -> MigrationError {
+    MigrationError::Database(e.to_string())
+}
+
+/// Hex encoded SHA-256 of the given SQL
+fn checksum(sql: &str) -> String {
+    Sha256::digest(sql.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_file_name() {
+        assert_eq!(
+            parse_file_name(Path::new("migrations/V1__create_users.sql")).unwrap(),
+            (1, "create users".to_string(), FileKind::Up)
+        );
+        assert_eq!(
+            parse_file_name(Path::new("0002_add_email.up.sql")).unwrap(),
+            (2, "add email".to_string(), FileKind::Up)
+        );
+        assert_eq!(
+            parse_file_name(Path::new("0002_add_email.down.sql")).unwrap(),
+            (2, "add email".to_string(), FileKind::Down)
+        );
+        assert_eq!(
+            parse_file_name(Path::new("20240101120000_seed.sql")).unwrap(),
+            (20240101120000, "seed".to_string(), FileKind::Up)
+        );
+
+        for invalid in ["init.sql", "V1_init.sql", "Vx__init.sql", "_init.sql"] {
+            assert_eq!(
+                parse_file_name(Path::new(invalid)),
+                Err(MigrationError::InvalidName(PathBuf::from(invalid)))
+            );
+        }
+    }
+
+    #[test]
+    fn test_migrator_orders_and_rejects_duplicates() {
+        let migrator = Migrator::new(vec![
+            Migration::new(2, "second", "SELECT 2"),
+            Migration::new(1, "first", "SELECT 1"),
+        ])
+        .unwrap();
+        let versions: Vec<i64> = migrator.migrations().iter().map(|m| m.version).collect();
+        assert_eq!(versions, [1, 2]);
+
+        let err = Migrator::new(vec![
+            Migration::new(1, "first", "SELECT 1"),
+            Migration::new(1, "again", "SELECT 1"),
+        ])
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            MigrationError::DuplicateVersion { version: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn test_checksum() {
+        let migration = Migration::new(1, "first", "SELECT 1");
+        assert_eq!(migration.checksum().len(), 64);
+        assert_eq!(
+            migration.checksum(),
+            Migration::new(1, "other", "SELECT 1").checksum()
+        );
+        assert_ne!(
+            migration.checksum(),
+            Migration::new(1, "first", "SELECT 2").checksum()
+        );
+    }
+}
@@ -1,6 +1,50 @@
 // mod pooling;
+mod assertions;
+mod capture;
+mod changes;
+mod connection;
+mod csv;
+mod factory;
+mod fake;
+mod fixtures;
+mod introspect;
+mod migrate;
+mod plan;
+mod schema;
+mod script;
+mod snapshot;
 mod test_database;
 pub mod transaction;
+mod value;
+mod wait;
+
+pub use assertions::{compare_rows, render_rows, value_matches};
+pub use capture::{
+    N_PLUS_ONE_THRESHOLD, RepeatedStatement, Statement, describe_statements, n_plus_one, record,
+    statement_shape,
+};
+pub use changes::{CaptureError, Changes, DataSnapshot, RowUpdate, TableChanges};
+pub use connection::{SqlDialect, TestConnection};
+pub use csv::CsvOptions;
+pub use factory::{AssociateFuture, Factory, FactoryError, Model};
+pub use fake::{Fake, SEED_ENV};
+pub use fixtures::{Fixture, FixtureError, Fixtures};
+pub use introspect::{Column, ForeignKey, Index, Introspect, Sequence};
+pub use migrate::{
+    AppliedMigration, MIGRATIONS_TABLE, Migration, MigrationError, Migrator, WithMigrations,
+};
+pub use plan::{Explain, PlanError, PlanNode, QueryPlan};
+pub use schema::{Schema, SchemaError, SchemaObject, SchemaObjectKind, SchemaSnapshot};
+pub use script::{ScriptError, SqlScript};
+pub use snapshot::{
+    PendingSnapshot, SNAPSHOT_DIR, TableSnapshot, UPDATE_SNAPSHOTS_ENV, assert_snapshot,
+    assert_snapshot_with, snapshot_diff, snapshot_name,
+};
+pub use transaction::{
+    DatabaseTransaction, IsolationLevel, TransactionManager, TransactionalConnection, TxOptions,
+};
+pub use value::{ColumnIndex, FromValue, Row, Value, ValueError};
+pub use wait::{WaitError, wait_until};
 
 pub use test_database::{
     DatabaseBackend, DatabaseConfig, DatabaseName, DatabasePool, TestDatabaseConnection,
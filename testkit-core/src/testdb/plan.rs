@@ -0,0 +1,307 @@
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use serde_json::Value as Json;
+
+use super::{SqlDialect, TestConnection};
+
+/// Error raised while explaining a query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanError(String);
+
+impl PlanError {
+    /// Create a new error with the given message
+    pub fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+impl Display for PlanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to explain the query: {}", self.0)
+    }
+}
+
+impl std::error::Error for PlanError {}
+
+fn plan_error(e: impl Display) -> PlanError {
+    PlanError::new(e.to_string())
+}
+
+/// A step of a query plan, such as a scan of a table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanNode {
+    /// How deep the step is nested in the plan, 0 for the top
+    pub depth: usize,
+    /// The operation, the node type on Postgres such as `Index Scan` and the access type
+    /// on MySQL such as `ref`
+    pub operation: String,
+    /// The table the step reads
+    pub table: Option<String>,
+    /// The index the step reads
+    pub index: Option<String>,
+    /// Whether the step reads every row of the table
+    pub full_scan: bool,
+}
+
+impl Display for PlanNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:indent$}{}",
+            "",
+            self.operation,
+            indent = self.depth * 2
+        )?;
+        if let Some(index) = &self.index {
+            write!(f, " using {}", index)?;
+        }
+        if let Some(table) = &self.table {
+            write!(f, " on {}", table)?;
+        }
+        Ok(())
+    }
+}
+
+/// The plan the server chose for a query, read with `EXPLAIN`
+///
+/// ```rust,ignore
+/// use testkit_core::Explain;
+///
+/// let plan = conn.explain("SELECT * FROM users WHERE email = 'a@example.com'").await?;
+/// assert!(plan.uses_index("users_email_idx"), "{}", plan);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryPlan {
+    /// The steps of the plan, each followed by the steps nested in it
+    pub nodes: Vec<PlanNode>,
+    /// The plan as the server returned it
+    pub json: Json,
+}
+
+impl QueryPlan {
+    /// Read a plan from the JSON output of `EXPLAIN`
+    pub fn from_json(dialect: SqlDialect, json: &str) -> Result<Self, PlanError> {
+        let json: Json = serde_json::from_str(json).map_err(plan_error)?;
+        let mut nodes = Vec::new();
+        match dialect {
+            SqlDialect::Postgres => {
+                // A list with one object holding the top node under "Plan"
+                for plan in json.as_array().into_iter().flatten() {
+                    if let Some(node) = plan.get("Plan") {
+                        postgres_nodes(node, 0, &mut nodes);
+                    }
+                }
+            }
+            SqlDialect::MySql => mysql_nodes(&json, 0, &mut nodes),
+        }
+
+        Ok(Self { nodes, json })
+    }
+
+    /// Returns true if a step of the plan reads the given index
+    pub fn uses_index(&self, index: &str) -> bool {
+        self.nodes
+            .iter()
+            .any(|node| node.index.as_deref() == Some(index))
+    }
+
+    /// Returns the tables the plan reads every row of
+    pub fn full_scans(&self) -> Vec<&str> {
+        self.nodes
+            .iter()
+            .filter(|node| node.full_scan)
+            .filter_map(|node| node.table.as_deref())
+            .collect()
+    }
+}
+
+impl Display for QueryPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for node in &self.nodes {
+            writeln!(f, "{}", node)?;
+        }
+        Ok(())
+    }
+}
+
+fn postgres_nodes(node: &Json, depth: usize, nodes: &mut Vec<PlanNode>) {
+    let field = |name| node.get(name).and_then(Json::as_str).map(str::to_string);
+    let operation = field("Node Type").unwrap_or_default();
+    nodes.push(PlanNode {
+        depth,
+        full_scan: operation == "Seq Scan",
+        operation,
+        table: field("Relation Name"),
+        index: field("Index Name"),
+    });
+
+    for child in node
+        .get("Plans")
+        .and_then(Json::as_array)
+        .into_iter()
+        .flatten()
+    {
+        postgres_nodes(child, depth + 1, nodes);
+    }
+}
+
+/// Collect the table accesses of a MySQL plan, which nests them in query blocks, nested
+/// loops and subqueries
+fn mysql_nodes(json: &Json, depth: usize, nodes: &mut Vec<PlanNode>) {
+    match json {
+        Json::Object(object) => {
+            let depth = match object.get("table_name").and_then(Json::as_str) {
+                Some(table) => {
+                    let field = |name| object.get(name).and_then(Json::as_str);
+                    let access = field("access_type").unwrap_or("unknown");
+                    nodes.push(PlanNode {
+                        depth,
+                        operation: access.to_string(),
+                        table: Some(table.to_string()),
+                        index: field("key").map(str::to_string),
+                        full_scan: access == "ALL",
+                    });
+                    depth + 1
+                }
+                None => depth,
+            };
+            for value in object.values() {
+                mysql_nodes(value, depth, nodes);
+            }
+        }
+        Json::Array(values) => {
+            for value in values {
+                mysql_nodes(value, depth, nodes);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Read the plans of queries with `EXPLAIN`
+///
+/// Implemented for every [`TestConnection`]. The query is planned but not executed, so
+/// it runs `EXPLAIN (FORMAT JSON)` on Postgres and `EXPLAIN FORMAT=JSON` on MySQL.
+#[async_trait]
+pub trait Explain: TestConnection + Sized {
+    /// Returns the plan the server chooses for a query
+    async fn explain(&self, sql: &str) -> Result<QueryPlan, PlanError> {
+        let dialect = self.dialect();
+        let explain = match dialect {
+            SqlDialect::Postgres => format!("EXPLAIN (FORMAT JSON) {}", sql),
+            SqlDialect::MySql => format!("EXPLAIN FORMAT=JSON {}", sql),
+        };
+
+        let rows = self.query(&explain, &[]).await.map_err(plan_error)?;
+        let json: String = rows
+            .first()
+            .ok_or_else(|| PlanError::new("EXPLAIN returned no rows"))?
+            .try_get(0)
+            .map_err(plan_error)?;
+        QueryPlan::from_json(dialect, &json)
+    }
+}
+
+impl<C: TestConnection> Explain for C {}
+
+/// Check the plan of a query
+///
+/// Takes a connection, the query and any of:
+///
+/// - `uses_index = "name"` - a step of the plan reads the index
+/// - `no_seq_scan` - no step reads every row of a table
+///
+/// Must be called in an async function. A failure prints the plan.
+///
+/// ```rust,ignore
+/// assert_plan!(conn, "SELECT * FROM users WHERE email = 'a@example.com'", uses_index = "users_email_idx");
+/// ```
+#[macro_export]
+macro_rules! assert_plan {
+    ($conn:expr, $sql:expr $(, $($check:tt)+)?) => {{
+        let sql: &str = $sql;
+        let plan = {
+            use $crate::Explain as _;
+            match $conn.explain(sql).await {
+                ::std::result::Result::Ok(plan) => plan,
+                ::std::result::Result::Err(e) => ::std::panic!("{}\nquery: {}", e, sql),
+            }
+        };
+        $($crate::__assert_plan_checks!(plan, sql, $($check)+);)?
+        plan
+    }};
+}
+
+/// Fail if the plan of a query reads every row of a table, see [`assert_plan!`]
+#[macro_export]
+macro_rules! assert_no_seq_scan {
+    ($conn:expr, $sql:expr $(,)?) => {
+        $crate::assert_plan!($conn, $sql, no_seq_scan)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __assert_plan_checks {
+    ($plan:ident, $sql:ident, uses_index = $index:expr $(, $($rest:tt)*)?) => {
+        let index: &str = $index;
+        if !$plan.uses_index(index) {
+            ::std::panic!(
+                "expected the plan to use index {}\nquery: {}\nplan:\n{}",
+                index, $sql, $plan
+            );
+        }
+        $($crate::__assert_plan_checks!($plan, $sql, $($rest)*);)?
+    };
+    ($plan:ident, $sql:ident, no_seq_scan $(, $($rest:tt)*)?) => {
+        let scans = $plan.full_scans();
+        if !scans.is_empty() {
+            ::std::panic!(
+                "expected no sequential scan, the plan scans {}\nquery: {}\nplan:\n{}",
+                scans.join(", "), $sql, $plan
+            );
+        }
+        $($crate::__assert_plan_checks!($plan, $sql, $($rest)*);)?
+    };
+    ($plan:ident, $sql:ident $(,)?) => {};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_postgres_plan() {
+        let json = r#"[{"Plan": {"Node Type": "Nested Loop", "Plans": [
+            {"Node Type": "Seq Scan", "Relation Name": "orders", "Alias": "orders"},
+            {"Node Type": "Index Scan", "Relation Name": "users", "Index Name": "users_pkey"}
+        ]}}]"#;
+        let plan = QueryPlan::from_json(SqlDialect::Postgres, json).unwrap();
+
+        assert!(plan.uses_index("users_pkey"));
+        assert!(!plan.uses_index("orders_pkey"));
+        assert_eq!(plan.full_scans(), vec!["orders"]);
+        assert_eq!(
+            plan.to_string(),
+            "Nested Loop\n  Seq Scan on orders\n  Index Scan using users_pkey on users\n"
+        );
+    }
+
+    #[test]
+    fn test_mysql_plan() {
+        let json = r#"{"query_block": {"select_id": 1, "nested_loop": [
+            {"table": {"table_name": "orders", "access_type": "ALL"}},
+            {"table": {"table_name": "users", "access_type": "eq_ref", "key": "PRIMARY"}}
+        ]}}"#;
+        let plan = QueryPlan::from_json(SqlDialect::MySql, json).unwrap();
+
+        assert!(plan.uses_index("PRIMARY"));
+        assert_eq!(plan.full_scans(), vec!["orders"]);
+        assert_eq!(
+            plan.to_string(),
+            "ALL on orders\neq_ref using PRIMARY on users\n"
+        );
+        assert!(QueryPlan::from_json(SqlDialect::MySql, "not json").is_err());
+    }
+}
@@ -0,0 +1,748 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Write as _};
+
+use super::introspect::{self, optional_text, query, text};
+use super::migrate::MIGRATION_TABLES;
+use super::{Introspect, SqlDialect, TestConnection};
+
+/// Error raised while reading the schema of a database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaError(String);
+
+impl SchemaError {
+    /// Create a new error with the given message
+    pub fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to read the schema: {}", self.0)
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+fn schema_error(e: impl Display) -> SchemaError {
+    SchemaError::new(e.to_string())
+}
+
+/// The kind of a [`SchemaObject`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SchemaObjectKind {
+    /// A base table
+    Table,
+    /// A column of a table
+    Column,
+    /// An index, including the ones backing primary keys and unique constraints
+    Index,
+    /// A primary key, unique, foreign key, check or exclusion constraint
+    Constraint,
+    /// A trigger on a table
+    Trigger,
+}
+
+impl SchemaObjectKind {
+    const ALL: [SchemaObjectKind; 5] = [
+        SchemaObjectKind::Table,
+        SchemaObjectKind::Column,
+        SchemaObjectKind::Index,
+        SchemaObjectKind::Constraint,
+        SchemaObjectKind::Trigger,
+    ];
+
+    /// Returns the name of the kind as written in snapshots
+    pub fn name(&self) -> &'static str {
+        match self {
+            SchemaObjectKind::Table => "table",
+            SchemaObjectKind::Column => "column",
+            SchemaObjectKind::Index => "index",
+            SchemaObjectKind::Constraint => "constraint",
+            SchemaObjectKind::Trigger => "trigger",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+/// A table or an object belonging to a table, with its normalized attributes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaObject {
+    /// What the object is
+    pub kind: SchemaObjectKind,
+    /// The table the object belongs to, or the name of the table itself
+    pub table: String,
+    /// The name of the object
+    pub name: String,
+    /// Attributes such as `type` or `nullable`, in a fixed order per kind
+    pub attributes: Vec<(String, String)>,
+}
+
+impl SchemaObject {
+    fn new(kind: SchemaObjectKind, table: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            kind,
+            table: table.into(),
+            name: name.into(),
+            attributes: Vec::new(),
+        }
+    }
+
+    fn with(mut self, key: &str, value: impl AsRef<str>) -> Self {
+        // Whitespace is collapsed so multi-line definitions stay on one line
+        let value = value
+            .as_ref()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.attributes.push((key.to_string(), value));
+        self
+    }
+
+    fn with_opt(self, key: &str, value: Option<impl AsRef<str>>) -> Self {
+        match value {
+            Some(value) => self.with(key, value),
+            None => self,
+        }
+    }
+
+    /// Returns the name used in change descriptions, such as `users.email`
+    fn path(&self) -> String {
+        match self.kind {
+            SchemaObjectKind::Table => self.table.clone(),
+            _ => format!("{}.{}", self.table, self.name),
+        }
+    }
+
+    fn key(&self) -> (SchemaObjectKind, &str, &str) {
+        (self.kind, &self.table, &self.name)
+    }
+}
+
+/// A normalized description of the tables of a database
+///
+/// Rendered with one object per line and one attribute per indented line, so it can be
+/// committed and reviewed:
+///
+/// ```text
+/// table users
+///   column id
+///     type: integer
+///     nullable: false
+///   constraint users_pkey
+///     type: primary key
+///     definition: PRIMARY KEY (id)
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schema {
+    /// The tables and their columns, indexes, constraints and triggers
+    pub objects: Vec<SchemaObject>,
+}
+
+impl Schema {
+    /// Read the schema of the database a connection points to
+    ///
+    /// Covers the base tables of the current schema on Postgres and of the current
+    /// database on MySQL.
+    pub async fn read<C: TestConnection>(conn: &C) -> Result<Self, SchemaError> {
+        let dialect = conn.dialect();
+        let mut objects = Vec::new();
+
+        for table in conn.tables().await? {
+            objects.push(SchemaObject::new(SchemaObjectKind::Table, &table, &table));
+        }
+
+        // Postgres names the identity kind, MySQL the EXTRA attributes
+        let generated = match dialect {
+            SqlDialect::Postgres => "identity",
+            SqlDialect::MySql => "extra",
+        };
+        let prefix = match dialect {
+            SqlDialect::Postgres => Some(format!("{}.", current_schema(conn).await?)),
+            SqlDialect::MySql => None,
+        };
+        for column in introspect::columns(conn, None).await? {
+            // Postgres defaults name sequences of the current schema with its prefix
+            let default = match &prefix {
+                Some(prefix) => column.default.map(|default| default.replace(prefix, "")),
+                None => column.default,
+            };
+            objects.push(
+                SchemaObject::new(SchemaObjectKind::Column, column.table, column.name)
+                    .with("type", column.data_type)
+                    .with("nullable", column.nullable.to_string())
+                    .with_opt("default", default)
+                    .with_opt(generated, column.generated),
+            );
+        }
+
+        objects.extend(match dialect {
+            SqlDialect::Postgres => read_postgres(conn).await?,
+            SqlDialect::MySql => read_mysql(conn).await?,
+        });
+
+        Ok(Self { objects })
+    }
+
+    /// Leave out the given tables and everything belonging to them
+    pub fn without_tables(mut self, tables: &[String]) -> Self {
+        self.objects
+            .retain(|object| !tables.contains(&object.table));
+        self
+    }
+
+    /// Render the schema, ordered by table and kind
+    pub fn render(&self) -> String {
+        let mut tables: Vec<&str> = self.objects.iter().map(|o| o.table.as_str()).collect();
+        tables.sort_unstable();
+        tables.dedup();
+
+        let mut out = String::new();
+        for table in tables {
+            let _ = writeln!(out, "table {}", table);
+            for kind in &SchemaObjectKind::ALL[1..] {
+                let objects = self
+                    .objects
+                    .iter()
+                    .filter(|o| o.table == table && o.kind == *kind);
+                for object in objects {
+                    let _ = writeln!(out, "  {} {}", kind.name(), object.name);
+                    for (key, value) in &object.attributes {
+                        let _ = writeln!(out, "    {}: {}", key, value);
+                    }
+                }
+            }
+        }
+
+        out
+    }
+
+    /// Parse a schema written by [`render`](Self::render)
+    pub fn parse(text: &str) -> Result<Self, SchemaError> {
+        let mut objects: Vec<SchemaObject> = Vec::new();
+        let mut table: Option<String> = None;
+
+        for (number, line) in text.lines().enumerate() {
+            let invalid = || SchemaError::new(format!("invalid line {}: {}", number + 1, line));
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix("table ") {
+                table = Some(name.to_string());
+                objects.push(SchemaObject::new(SchemaObjectKind::Table, name, name));
+            } else if let Some(attribute) = line.strip_prefix("    ") {
+                let (key, value) = attribute.split_once(": ").ok_or_else(invalid)?;
+                let object = objects
+                    .last_mut()
+                    .filter(|o| o.kind != SchemaObjectKind::Table)
+                    .ok_or_else(invalid)?;
+                object.attributes.push((key.to_string(), value.to_string()));
+            } else if let Some(object) = line.strip_prefix("  ") {
+                let (kind, name) = object.split_once(' ').ok_or_else(invalid)?;
+                let kind = SchemaObjectKind::from_name(kind).ok_or_else(invalid)?;
+                let table = table.as_ref().ok_or_else(invalid)?;
+                objects.push(SchemaObject::new(kind, table.as_str(), name));
+            } else {
+                return Err(invalid());
+            }
+        }
+
+        Ok(Self { objects })
+    }
+
+    /// Describe the changes from this schema to another one
+    ///
+    /// Objects are matched by kind, table and name, so the descriptions read like
+    /// `column users.email changed nullable from false to true` or `index
+    /// users.users_email_key removed`.
+    pub fn changes(&self, actual: &Schema) -> Vec<String> {
+        let expected_objects: HashMap<_, _> = self.objects.iter().map(|o| (o.key(), o)).collect();
+        let actual_objects: HashMap<_, _> = actual.objects.iter().map(|o| (o.key(), o)).collect();
+        let mut changes = Vec::new();
+
+        for expected in &self.objects {
+            let Some(actual) = actual_objects.get(&expected.key()) else {
+                changes.push(format!(
+                    "{} {} removed",
+                    expected.kind.name(),
+                    expected.path()
+                ));
+                continue;
+            };
+
+            let mut keys: Vec<&str> = expected
+                .attributes
+                .iter()
+                .map(|(k, _)| k.as_str())
+                .collect();
+            for (key, _) in &actual.attributes {
+                if !keys.contains(&key.as_str()) {
+                    keys.push(key);
+                }
+            }
+
+            let value = |object: &SchemaObject, key: &str| {
+                object
+                    .attributes
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| v.clone())
+            };
+            for key in keys {
+                let (old, new) = (value(expected, key), value(actual, key));
+                if old != new {
+                    changes.push(format!(
+                        "{} {} changed {} from {} to {}",
+                        expected.kind.name(),
+                        expected.path(),
+                        key,
+                        old.as_deref().unwrap_or("none"),
+                        new.as_deref().unwrap_or("none")
+                    ));
+                }
+            }
+        }
+
+        for actual in &actual.objects {
+            if !expected_objects.contains_key(&actual.key()) {
+                changes.push(format!("{} {} added", actual.kind.name(), actual.path()));
+            }
+        }
+
+        changes
+    }
+
+    /// Describe the changes between two rendered schemas, one per line
+    ///
+    /// Falls back to a line diff when either side can't be parsed.
+    pub fn describe_changes(expected: &str, actual: &str) -> String {
+        match (Schema::parse(expected), Schema::parse(actual)) {
+            (Ok(expected), Ok(actual)) => {
+                let mut out = String::new();
+                for change in expected.changes(&actual) {
+                    let _ = writeln!(out, "  {}", change);
+                }
+                out
+            }
+            _ => super::snapshot_diff(expected, actual),
+        }
+    }
+}
+
+/// A snapshot of the schema of a test database
+///
+/// Usually used through [`assert_schema_snapshot!`](crate::assert_schema_snapshot).
+/// Migration bookkeeping tables are always left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaSnapshot {
+    exclude: Vec<String>,
+    name: Option<String>,
+}
+
+impl SchemaSnapshot {
+    /// Snapshot all tables
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leave out the given tables
+    pub fn exclude<I, S>(mut self, tables: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.exclude.extend(tables.into_iter().map(Into::into));
+        self
+    }
+
+    /// Name the snapshot file instead of deriving the name from the test
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Returns the name of the snapshot file, without the `.snap` extension
+    pub fn snapshot_name(&self, function: &str) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("{}__schema", super::snapshot_name(function)),
+        }
+    }
+
+    /// Read the schema and render it
+    pub async fn render<C: TestConnection>(&self, conn: &C) -> Result<String, SchemaError> {
+        let mut exclude = self.exclude.clone();
+        exclude.extend(MIGRATION_TABLES.iter().map(|t| t.to_string()));
+
+        Ok(Schema::read(conn).await?.without_tables(&exclude).render())
+    }
+}
+
+/// Compare the schema of the test database with a snapshot under `tests/snapshots/`
+///
+/// Takes the `TestContext` and optional `exclude = [...]` and `name = "..."` arguments,
+/// see [`SchemaSnapshot`]. Must be called in an async function. When the schema differs,
+/// the test fails with the semantic changes, such as `column users.email changed nullable
+/// from false to true`, and the snapshot can be updated like the ones of
+/// [`assert_table_snapshot!`](crate::assert_table_snapshot).
+///
+/// ```rust,ignore
+/// #[testkit::test(migrations = "migrations")]
+/// async fn schema_matches(ctx: TestContext<PostgresBackend>) {
+///     testkit_core::assert_schema_snapshot!(ctx);
+/// }
+/// ```
+#[macro_export]
+macro_rules! assert_schema_snapshot {
+    ($ctx:expr $(, $option:ident = $value:expr)* $(,)?) => {{
+        let snapshot = $crate::SchemaSnapshot::new() $(.$option($value))*;
+        let db = &$ctx.db;
+        let conn = db
+            .acquire_connection()
+            .await
+            .unwrap_or_else(|e| panic!("failed to acquire a connection: {}", e));
+        let contents = snapshot.render(&conn).await.unwrap_or_else(|e| panic!("{}", e));
+        let _ = db.release_connection(conn).await;
+        let name = snapshot.snapshot_name($crate::__function_path!());
+        $crate::assert_snapshot_with(
+            env!("CARGO_MANIFEST_DIR"),
+            &name,
+            &contents,
+            $crate::Schema::describe_changes,
+        );
+    }};
+}
+
+async fn current_schema<C: TestConnection>(conn: &C) -> Result<String, SchemaError> {
+    text(&query(conn, "SELECT current_schema()::text").await?[0], 0)
+}
+
+/// Read the indexes, constraints and triggers of the current schema of a Postgres database
+async fn read_postgres<C: TestConnection>(conn: &C) -> Result<Vec<SchemaObject>, SchemaError> {
+    let schema = current_schema(conn).await?;
+    // Definitions name objects of the current schema with its prefix
+    let unqualify = |definition: String| definition.replace(&format!("{}.", schema), "");
+    let mut objects = Vec::new();
+
+    let indexes = query(
+        conn,
+        "SELECT t.relname::text, i.relname::text, x.indisunique, pg_get_indexdef(i.oid)
+         FROM pg_index x
+         JOIN pg_class i ON i.oid = x.indexrelid
+         JOIN pg_class t ON t.oid = x.indrelid
+         JOIN pg_namespace n ON n.oid = t.relnamespace
+         WHERE n.nspname = current_schema()
+         ORDER BY 1, 2",
+    )
+    .await?;
+    for row in &indexes {
+        let unique: bool = row.try_get(2).map_err(schema_error)?;
+        let definition = text(row, 3)?;
+        // Keep the part after USING, such as `btree (email)`
+        let definition = definition
+            .split_once(" USING ")
+            .map_or(definition.as_str(), |(_, rest)| rest);
+        objects.push(
+            SchemaObject::new(SchemaObjectKind::Index, text(row, 0)?, text(row, 1)?)
+                .with("unique", unique.to_string())
+                .with("definition", unqualify(definition.to_string())),
+        );
+    }
+
+    let constraints = query(
+        conn,
+        "SELECT t.relname::text, k.conname::text, k.contype::text, pg_get_constraintdef(k.oid)
+         FROM pg_constraint k
+         JOIN pg_class t ON t.oid = k.conrelid
+         JOIN pg_namespace n ON n.oid = t.relnamespace
+         WHERE n.nspname = current_schema() AND k.contype IN ('p', 'u', 'f', 'c', 'x')
+         ORDER BY 1, 2",
+    )
+    .await?;
+    for row in &constraints {
+        let kind = match text(row, 2)?.as_str() {
+            "p" => "primary key",
+            "u" => "unique",
+            "f" => "foreign key",
+            "c" => "check",
+            _ => "exclusion",
+        };
+        objects.push(
+            SchemaObject::new(SchemaObjectKind::Constraint, text(row, 0)?, text(row, 1)?)
+                .with("type", kind)
+                .with("definition", unqualify(text(row, 3)?)),
+        );
+    }
+
+    let triggers = query(
+        conn,
+        "SELECT c.relname::text, t.tgname::text, pg_get_triggerdef(t.oid)
+         FROM pg_trigger t
+         JOIN pg_class c ON c.oid = t.tgrelid
+         JOIN pg_namespace n ON n.oid = c.relnamespace
+         WHERE n.nspname = current_schema() AND NOT t.tgisinternal
+         ORDER BY 1, 2",
+    )
+    .await?;
+    for row in &triggers {
+        let name = text(row, 1)?;
+        let definition = text(row, 2)?;
+        // Drop the `CREATE TRIGGER name` prefix repeating the name
+        let definition = definition
+            .strip_prefix(&format!("CREATE TRIGGER {} ", name))
+            .unwrap_or(&definition)
+            .to_string();
+        objects.push(
+            SchemaObject::new(SchemaObjectKind::Trigger, text(row, 0)?, name)
+                .with("definition", unqualify(definition)),
+        );
+    }
+
+    Ok(objects)
+}
+
+/// Read the indexes, constraints and triggers of the current MySQL database
+async fn read_mysql<C: TestConnection>(conn: &C) -> Result<Vec<SchemaObject>, SchemaError> {
+    let mut objects = Vec::new();
+
+    for index in introspect::indexes(conn, None).await? {
+        objects.push(
+            SchemaObject::new(SchemaObjectKind::Index, index.table, index.name)
+                .with("unique", index.unique.to_string())
+                .with(
+                    "definition",
+                    format!("{} ({})", index.method, index.columns.join(", ")),
+                ),
+        );
+    }
+
+    objects.extend(mysql_constraints(conn).await?);
+
+    let triggers = query(
+        conn,
+        "SELECT EVENT_OBJECT_TABLE, TRIGGER_NAME, ACTION_TIMING, EVENT_MANIPULATION,
+                ACTION_ORIENTATION, ACTION_STATEMENT
+         FROM information_schema.TRIGGERS
+         WHERE TRIGGER_SCHEMA = DATABASE()
+         ORDER BY 1, 2",
+    )
+    .await?;
+    for row in &triggers {
+        let definition = format!(
+            "{} {} ON {} FOR EACH {} {}",
+            text(row, 2)?,
+            text(row, 3)?,
+            text(row, 0)?,
+            text(row, 4)?,
+            text(row, 5)?
+        );
+        objects.push(
+            SchemaObject::new(SchemaObjectKind::Trigger, text(row, 0)?, text(row, 1)?)
+                .with("definition", definition),
+        );
+    }
+
+    Ok(objects)
+}
+
+/// Read the constraints of the current MySQL database, written like Postgres defines them
+async fn mysql_constraints<C: TestConnection>(conn: &C) -> Result<Vec<SchemaObject>, SchemaError> {
+    let key_columns = query(
+        conn,
+        "SELECT tc.TABLE_NAME, tc.CONSTRAINT_NAME, tc.CONSTRAINT_TYPE, k.COLUMN_NAME,
+                k.REFERENCED_TABLE_NAME, k.REFERENCED_COLUMN_NAME
+         FROM information_schema.TABLE_CONSTRAINTS tc
+         JOIN information_schema.KEY_COLUMN_USAGE k
+           ON k.CONSTRAINT_SCHEMA = tc.CONSTRAINT_SCHEMA
+          AND k.TABLE_NAME = tc.TABLE_NAME
+          AND k.CONSTRAINT_NAME = tc.CONSTRAINT_NAME
+         WHERE tc.TABLE_SCHEMA = DATABASE()
+         ORDER BY tc.TABLE_NAME, tc.CONSTRAINT_NAME, k.ORDINAL_POSITION",
+    )
+    .await?;
+    let rules = query(
+        conn,
+        "SELECT TABLE_NAME, CONSTRAINT_NAME, UPDATE_RULE, DELETE_RULE
+         FROM information_schema.REFERENTIAL_CONSTRAINTS
+         WHERE CONSTRAINT_SCHEMA = DATABASE()",
+    )
+    .await?;
+    let checks = query(
+        conn,
+        "SELECT tc.TABLE_NAME, cc.CONSTRAINT_NAME, cc.CHECK_CLAUSE
+         FROM information_schema.CHECK_CONSTRAINTS cc
+         JOIN information_schema.TABLE_CONSTRAINTS tc
+           ON tc.CONSTRAINT_SCHEMA = cc.CONSTRAINT_SCHEMA
+          AND tc.CONSTRAINT_NAME = cc.CONSTRAINT_NAME
+         WHERE cc.CONSTRAINT_SCHEMA = DATABASE()
+         ORDER BY 1, 2",
+    )
+    .await?;
+
+    // (table, name, type, columns, referenced table, referenced columns)
+    type Key = (
+        String,
+        String,
+        String,
+        Vec<String>,
+        Option<String>,
+        Vec<String>,
+    );
+    let mut keys: Vec<Key> = Vec::new();
+    for row in &key_columns {
+        let (table, name) = (text(row, 0)?, text(row, 1)?);
+        let column = text(row, 3)?;
+        let referenced_column = optional_text(row, 5)?;
+        match keys.last_mut() {
+            Some(key) if key.0 == table && key.1 == name => {
+                key.3.push(column);
+                key.5.extend(referenced_column);
+            }
+            _ => keys.push((
+                table,
+                name,
+                text(row, 2)?,
+                vec![column],
+                optional_text(row, 4)?,
+                referenced_column.into_iter().collect(),
+            )),
+        }
+    }
+
+    let mut objects = Vec::new();
+    for (table, name, kind, columns, referenced_table, referenced_columns) in keys {
+        let columns = columns.join(", ");
+        let definition = match (kind.as_str(), referenced_table) {
+            ("FOREIGN KEY", Some(referenced_table)) => {
+                let mut definition = format!(
+                    "FOREIGN KEY ({}) REFERENCES {}({})",
+                    columns,
+                    referenced_table,
+                    referenced_columns.join(", ")
+                );
+                let rule = rules.iter().find(|row| {
+                    text(row, 0).is_ok_and(|t| t == table) && text(row, 1).is_ok_and(|n| n == name)
+                });
+                if let Some(rule) = rule {
+                    // RESTRICT and NO ACTION behave the same on MySQL and are the default
+                    for (event, column) in [("UPDATE", 2), ("DELETE", 3)] {
+                        let action = text(rule, column)?;
+                        if action != "RESTRICT" && action != "NO ACTION" {
+                            let _ = write!(definition, " ON {} {}", event, action);
+                        }
+                    }
+                }
+                definition
+            }
+            _ => format!("{} ({})", kind, columns),
+        };
+        objects.push(
+            SchemaObject::new(SchemaObjectKind::Constraint, table, name)
+                .with("type", kind.to_lowercase())
+                .with("definition", definition),
+        );
+    }
+
+    for row in &checks {
+        objects.push(
+            SchemaObject::new(SchemaObjectKind::Constraint, text(row, 0)?, text(row, 1)?)
+                .with("type", "check")
+                .with("definition", format!("CHECK ({})", text(row, 2)?)),
+        );
+    }
+    objects.sort_by(|a, b| (&a.table, &a.name).cmp(&(&b.table, &b.name)));
+
+    Ok(objects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn users() -> Schema {
+        Schema {
+            objects: vec![
+                SchemaObject::new(SchemaObjectKind::Table, "users", "users"),
+                SchemaObject::new(SchemaObjectKind::Constraint, "users", "users_pkey")
+                    .with("type", "primary key")
+                    .with("definition", "PRIMARY KEY (id)"),
+                SchemaObject::new(SchemaObjectKind::Column, "users", "id")
+                    .with("type", "integer")
+                    .with("nullable", "false"),
+                SchemaObject::new(SchemaObjectKind::Column, "users", "email")
+                    .with("type", "text")
+                    .with("nullable", "false")
+                    .with("default", "'none'::text"),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_render_and_parse() {
+        let rendered = users().render();
+        assert_eq!(
+            rendered,
+            "table users\n\
+             \x20 column id\n    type: integer\n    nullable: false\n\
+             \x20 column email\n    type: text\n    nullable: false\n    default: 'none'::text\n\
+             \x20 constraint users_pkey\n    type: primary key\n    definition: PRIMARY KEY (id)\n"
+        );
+
+        let parsed = Schema::parse(&rendered).unwrap();
+        assert_eq!(parsed.render(), rendered);
+        assert!(users().changes(&parsed).is_empty());
+        assert!(Schema::parse("  column id\n").is_err());
+    }
+
+    #[test]
+    fn test_changes() {
+        let mut actual = users();
+        actual
+            .objects
+            .retain(|o| o.kind != SchemaObjectKind::Constraint);
+        actual.objects[2].attributes[1].1 = "true".to_string();
+        actual.objects[2].attributes.pop();
+        actual.objects.push(
+            SchemaObject::new(SchemaObjectKind::Index, "users", "users_email_idx")
+                .with("unique", "false"),
+        );
+        actual
+            .objects
+            .push(SchemaObject::new(SchemaObjectKind::Table, "audit", "audit"));
+
+        assert_eq!(
+            users().changes(&actual),
+            [
+                "constraint users.users_pkey removed",
+                "column users.email changed nullable from false to true",
+                "column users.email changed default from 'none'::text to none",
+                "index users.users_email_idx added",
+                "table audit added",
+            ]
+        );
+        assert_eq!(
+            Schema::describe_changes(&users().render(), &actual.render())
+                .lines()
+                .count(),
+            5
+        );
+    }
+
+    #[test]
+    fn test_snapshot_name() {
+        assert_eq!(
+            SchemaSnapshot::new().snapshot_name("tests::schema::{{closure}}"),
+            "schema__schema"
+        );
+        assert_eq!(
+            SchemaSnapshot::new()
+                .name("app")
+                .snapshot_name("tests::schema"),
+            "app"
+        );
+    }
+}
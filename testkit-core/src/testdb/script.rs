@@ -0,0 +1,687 @@
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use super::{SqlDialect, TestConnection};
+
+/// A SQL script, given either as text or as the path of a `.sql` file
+///
+/// Strings are taken as SQL, paths are read when the script is executed:
+///
+/// ```rust,no_run
+/// # use testkit_core::TestConnection;
+/// # async fn example<C: TestConnection>(conn: &C) -> Result<(), testkit_core::ScriptError> {
+/// use std::path::Path;
+///
+/// conn.execute_script("CREATE TABLE users (id INT); INSERT INTO users VALUES (1);").await?;
+/// conn.execute_script(Path::new("tests/fixtures/schema.sql")).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SqlScript {
+    /// SQL text
+    Sql(String),
+    /// A file containing SQL
+    File(PathBuf),
+}
+
+impl SqlScript {
+    /// The name used for the script in error messages
+    pub fn source(&self) -> String {
+        match self {
+            SqlScript::Sql(_) => "script".to_string(),
+            SqlScript::File(path) => path.display().to_string(),
+        }
+    }
+
+    /// Returns the SQL of the script, reading it from disk if needed
+    pub fn read(&self) -> Result<Cow<'_, str>, ScriptError> {
+        match self {
+            SqlScript::Sql(sql) => Ok(Cow::Borrowed(sql)),
+            SqlScript::File(path) => {
+                std::fs::read_to_string(path)
+                    .map(Cow::Owned)
+                    .map_err(|e| ScriptError::Io {
+                        path: path.clone(),
+                        message: e.to_string(),
+                    })
+            }
+        }
+    }
+}
+
+impl From<&str> for SqlScript {
+    fn from(sql: &str) -> Self {
+        SqlScript::Sql(sql.to_string())
+    }
+}
+
+impl From<String> for SqlScript {
+    fn from(sql: String) -> Self {
+        SqlScript::Sql(sql)
+    }
+}
+
+impl From<&Path> for SqlScript {
+    fn from(path: &Path) -> Self {
+        SqlScript::File(path.to_path_buf())
+    }
+}
+
+impl From<PathBuf> for SqlScript {
+    fn from(path: PathBuf) -> Self {
+        SqlScript::File(path)
+    }
+}
+
+impl From<&PathBuf> for SqlScript {
+    fn from(path: &PathBuf) -> Self {
+        SqlScript::File(path.clone())
+    }
+}
+
+/// Errors raised while executing a SQL script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptError {
+    /// The script file couldn't be read
+    Io { path: PathBuf, message: String },
+    /// A statement of the script couldn't be parsed or failed to execute
+    Statement {
+        source: String,
+        line: usize,
+        statement: String,
+        message: String,
+    },
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScriptError::Io { path, message } => {
+                write!(f, "failed to read {}: {}", path.display(), message)
+            }
+            ScriptError::Statement {
+                source,
+                line,
+                statement,
+                message,
+            } => write!(
+                f,
+                "{} failed at line {}: {}\n  statement: {}",
+                source, line, message, statement
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// Split a script with the rules of the connection's dialect and run it statement by statement
+pub(crate) async fn execute_script<C: TestConnection + ?Sized>(
+    conn: &C,
+    script: &SqlScript,
+) -> Result<(), ScriptError> {
+    let source = script.source();
+    let sql = script.read()?;
+
+    let statements =
+        split_statements(&sql, conn.dialect()).map_err(|e| ScriptError::Statement {
+            source: source.clone(),
+            line: e.line,
+            statement: e.statement,
+            message: e.message,
+        })?;
+
+    for statement in statements {
+        conn.batch_execute(&statement.sql)
+            .await
+            .map_err(|e| ScriptError::Statement {
+                source: source.clone(),
+                line: statement.line,
+                statement: statement.sql.clone(),
+                message: e.to_string(),
+            })?;
+    }
+
+    Ok(())
+}
+
+/// A single statement of a SQL script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Statement {
+    /// The text of the statement without the terminating delimiter
+    pub(crate) sql: String,
+    /// The 1-based line the statement starts on
+    pub(crate) line: usize,
+}
+
+/// A script that can't be split into statements
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SyntaxError {
+    pub(crate) line: usize,
+    pub(crate) statement: String,
+    pub(crate) message: String,
+}
+
+/// Split a SQL script into its statements
+///
+/// Statements are separated by semicolons outside of string literals, quoted
+/// identifiers and comments. Comments inside a statement are kept, comments in
+/// front of it are not, and statements that only contain comments are dropped.
+///
+/// On top of that the splitter follows the dialect the way `psql` and the `mysql`
+/// client do:
+///
+/// - Postgres: dollar-quoted bodies (`$$ ... $$`, `$fn$ ... $fn$`), `E'...'` strings with
+///   backslash escapes, and `COPY ... FROM stdin` followed by rows in the text format up
+///   to a `\.` line. The rows are turned into an `INSERT`, since the statements are sent
+///   as plain queries.
+/// - MySQL: `DELIMITER` directives, `#` comments, backslash escapes in strings and
+///   executable `/*! ... */` comments.
+pub(crate) fn split_statements(
+    script: &str,
+    dialect: SqlDialect,
+) -> Result<Vec<Statement>, SyntaxError> {
+    let mut splitter = Splitter {
+        script,
+        dialect,
+        pos: 0,
+        line: 1,
+        delimiter: ";".to_string(),
+        statements: Vec::new(),
+        current: String::new(),
+        start_line: 1,
+        has_code: false,
+    };
+    splitter.run()?;
+    Ok(splitter.statements)
+}
+
+struct Splitter<'a> {
+    script: &'a str,
+    dialect: SqlDialect,
+    /// Byte offset of the next character to read
+    pos: usize,
+    /// The line of the next character to read
+    line: usize,
+    delimiter: String,
+    statements: Vec<Statement>,
+    current: String,
+    start_line: usize,
+    /// Whether the current statement contains anything besides whitespace and comments
+    has_code: bool,
+}
+
+impl Splitter<'_> {
+    fn run(&mut self) -> Result<(), SyntaxError> {
+        while let Some(c) = self.peek() {
+            let rest = &self.script[self.pos..];
+
+            if !self.has_code && self.dialect == SqlDialect::MySql && is_delimiter_directive(rest) {
+                self.read_delimiter()?;
+                continue;
+            }
+            if rest.starts_with(self.delimiter.as_str()) {
+                self.pos += self.delimiter.len();
+                self.finish_statement()?;
+                continue;
+            }
+
+            // Comments and whitespace in front of a statement are not part of it
+            if !self.has_code && !c.is_whitespace() && !self.starts_comment(rest) {
+                self.current.clear();
+                self.start_line = self.line;
+                self.has_code = true;
+            }
+
+            match c {
+                '\'' | '"' | '`' => self.quoted(c),
+                '$' if self.dialect == SqlDialect::Postgres && !self.in_identifier() => {
+                    match dollar_tag(rest) {
+                        Some(tag) => self.take_through(tag.len(), tag),
+                        None => self.push(c),
+                    }
+                }
+                '-' if rest.starts_with("--") => self.take_through(2, "\n"),
+                '#' if self.dialect == SqlDialect::MySql => self.take_through(1, "\n"),
+                '/' if rest.starts_with("/*") => self.take_through(2, "*/"),
+                _ => self.push(c),
+            }
+        }
+
+        self.finish_statement()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.script[self.pos..].chars().next()
+    }
+
+    fn push(&mut self, c: char) {
+        self.current.push(c);
+        self.pos += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+        }
+    }
+
+    /// Copy everything up to and including the next `end` after the first `skip` bytes
+    fn take_through(&mut self, skip: usize, end: &str) {
+        let body = self.pos + skip;
+        let stop = self.script[body..]
+            .find(end)
+            .map_or(self.script.len(), |i| body + i + end.len());
+        let text = &self.script[self.pos..stop];
+        self.line += text.matches('\n').count();
+        self.current.push_str(text);
+        self.pos = stop;
+    }
+
+    /// Copy a string literal or quoted identifier
+    fn quoted(&mut self, quote: char) {
+        let escapes = match self.dialect {
+            SqlDialect::MySql => quote != '`',
+            SqlDialect::Postgres => quote == '\'' && self.in_escape_string(),
+        };
+
+        self.push(quote);
+        while let Some(next) = self.peek() {
+            self.push(next);
+            if escapes && next == '\\' {
+                if let Some(escaped) = self.peek() {
+                    self.push(escaped);
+                }
+            } else if next == quote {
+                // A doubled quote inside the literal escapes it
+                if self.peek() == Some(quote) {
+                    self.push(quote);
+                } else {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Returns true if the statement so far ends with the `E` prefix of a Postgres escape string
+    fn in_escape_string(&self) -> bool {
+        let mut chars = self.current.chars().rev();
+        matches!(chars.next(), Some('E' | 'e')) && !chars.next().is_some_and(is_identifier_char)
+    }
+
+    /// Returns true if the statement so far ends inside an identifier, where `$` is a plain character
+    fn in_identifier(&self) -> bool {
+        self.current
+            .chars()
+            .next_back()
+            .is_some_and(is_identifier_char)
+    }
+
+    /// Returns true if the input opens a comment that doesn't count as code
+    fn starts_comment(&self, rest: &str) -> bool {
+        if rest.starts_with("--") {
+            return true;
+        }
+        match self.dialect {
+            SqlDialect::MySql => {
+                rest.starts_with('#') || (rest.starts_with("/*") && !rest.starts_with("/*!"))
+            }
+            SqlDialect::Postgres => rest.starts_with("/*"),
+        }
+    }
+
+    /// Read a MySQL `DELIMITER` directive, which applies to the statements after it
+    fn read_delimiter(&mut self) -> Result<(), SyntaxError> {
+        let rest = &self.script[self.pos..];
+        let end = rest.find('\n').unwrap_or(rest.len());
+        let directive = rest[..end].trim_end();
+        let delimiter = directive["DELIMITER".len()..].trim();
+
+        if delimiter.is_empty() {
+            return Err(SyntaxError {
+                line: self.line,
+                statement: directive.to_string(),
+                message: "DELIMITER requires a delimiter".to_string(),
+            });
+        }
+
+        self.delimiter = delimiter.to_string();
+        self.current.clear();
+        self.pos += end;
+        Ok(())
+    }
+
+    fn finish_statement(&mut self) -> Result<(), SyntaxError> {
+        let has_code = std::mem::take(&mut self.has_code);
+        let sql = self.current.trim().to_string();
+        self.current.clear();
+        if !has_code {
+            return Ok(());
+        }
+
+        if self.dialect == SqlDialect::Postgres
+            && let Some(target) = copy_from_stdin(&sql)
+        {
+            let line = self.start_line;
+            let error = |message: String| SyntaxError {
+                line,
+                statement: sql.clone(),
+                message,
+            };
+            let target = target.map_err(error)?;
+            let rows = self.copy_rows().map_err(error)?;
+            if !rows.is_empty() {
+                self.statements.push(Statement {
+                    sql: format!("INSERT INTO {} VALUES\n{}", target, rows.join(",\n")),
+                    line,
+                });
+            }
+            return Ok(());
+        }
+
+        self.statements.push(Statement {
+            sql,
+            line: self.start_line,
+        });
+        Ok(())
+    }
+
+    /// Read the data lines following `COPY ... FROM stdin` as `VALUES` rows
+    fn copy_rows(&mut self) -> Result<Vec<String>, String> {
+        // The data starts on the line after the statement
+        match self.script[self.pos..].find('\n') {
+            Some(i) => {
+                self.pos += i + 1;
+                self.line += 1;
+            }
+            None => self.pos = self.script.len(),
+        }
+
+        let mut rows = Vec::new();
+        while self.pos < self.script.len() {
+            let rest = &self.script[self.pos..];
+            let data = match rest.find('\n') {
+                Some(i) => {
+                    self.pos += i + 1;
+                    self.line += 1;
+                    &rest[..i]
+                }
+                None => {
+                    self.pos = self.script.len();
+                    rest
+                }
+            };
+            let data = data.strip_suffix('\r').unwrap_or(data);
+
+            if data == "\\." {
+                return Ok(rows);
+            }
+            let values: Vec<String> = data.split('\t').map(copy_value).collect();
+            rows.push(format!("({})", values.join(", ")));
+        }
+
+        Err("COPY data is not terminated by a line containing only \\.".to_string())
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Returns true if the input starts with a MySQL `DELIMITER` directive
+fn is_delimiter_directive(rest: &str) -> bool {
+    rest.get(.."DELIMITER".len())
+        .is_some_and(|word| word.eq_ignore_ascii_case("DELIMITER"))
+        && rest["DELIMITER".len()..].starts_with([' ', '\t'])
+}
+
+/// Returns the opening tag of a dollar-quoted string, such as `$$` or `$body$`
+fn dollar_tag(rest: &str) -> Option<&str> {
+    let body = &rest[1..];
+    let end = body.find(|c: char| !is_identifier_char(c))?;
+    if body.starts_with(|c: char| c.is_ascii_digit()) || !body[end..].starts_with('$') {
+        return None;
+    }
+    Some(&rest[..end + 2])
+}
+
+/// Returns the table and column list of a `COPY ... FROM stdin` statement
+///
+/// `None` means the statement is something else and runs as is. Only the default
+/// text format is understood, so an error is returned for any options.
+fn copy_from_stdin(sql: &str) -> Option<Result<&str, String>> {
+    let lower = sql.to_ascii_lowercase();
+    if !lower.starts_with("copy") || !lower[4..].starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let (from, _) = lower.match_indices("from").find(|(i, _)| {
+        lower[..*i].ends_with(|c: char| c.is_whitespace() || c == ')')
+            && lower[i + 4..].starts_with(char::is_whitespace)
+            && lower[i + 4..].trim_start().starts_with("stdin")
+    })?;
+
+    let options = lower[from + 4..].trim_start()["stdin".len()..].trim();
+    if !options.is_empty() {
+        return Some(Err(format!(
+            "only the text format without options is supported for COPY ... FROM stdin, got `{}`",
+            options
+        )));
+    }
+
+    Some(Ok(sql[4..from].trim()))
+}
+
+/// Turn a field of the COPY text format into a SQL literal
+fn copy_value(field: &str) -> String {
+    if field == "\\N" {
+        return "NULL".to_string();
+    }
+
+    let mut value = String::with_capacity(field.len() + 2);
+    value.push('\'');
+    let mut chars = field.chars().peekable();
+    while let Some(c) = chars.next() {
+        let c = if c == '\\' {
+            match chars.next() {
+                Some('b') => '\u{8}',
+                Some('f') => '\u{c}',
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('v') => '\u{b}',
+                Some(d @ '0'..='7') => {
+                    let mut code = d.to_digit(8).unwrap_or(0);
+                    for _ in 0..2 {
+                        match chars.peek().and_then(|c| c.to_digit(8)) {
+                            Some(digit) => {
+                                code = code * 8 + digit;
+                                chars.next();
+                            }
+                            None => break,
+                        }
+                    }
+                    char::from_u32(code).unwrap_or('\u{fffd}')
+                }
+                Some('x') if chars.peek().is_some_and(|c| c.is_ascii_hexdigit()) => {
+                    let mut code = 0;
+                    for _ in 0..2 {
+                        match chars.peek().and_then(|c| c.to_digit(16)) {
+                            Some(digit) => {
+                                code = code * 16 + digit;
+                                chars.next();
+                            }
+                            None => break,
+                        }
+                    }
+                    char::from_u32(code).unwrap_or('\u{fffd}')
+                }
+                Some(other) => other,
+                None => '\\',
+            }
+        } else {
+            c
+        };
+
+        if c == '\'' {
+            value.push('\'');
+        }
+        value.push(c);
+    }
+    value.push('\'');
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(script: &str, dialect: SqlDialect) -> Vec<Statement> {
+        split_statements(script, dialect).unwrap()
+    }
+
+    fn sql(statements: &[Statement]) -> Vec<&str> {
+        statements.iter().map(|s| s.sql.as_str()).collect()
+    }
+
+    #[test]
+    fn test_split_statements() {
+        let script = "CREATE TABLE users (id INT);\n\nINSERT INTO users VALUES (1);\nSELECT 1";
+        let statements = split(script, SqlDialect::Postgres);
+        assert_eq!(
+            sql(&statements),
+            [
+                "CREATE TABLE users (id INT)",
+                "INSERT INTO users VALUES (1)",
+                "SELECT 1"
+            ]
+        );
+        assert_eq!(
+            statements.iter().map(|s| s.line).collect::<Vec<_>>(),
+            [1, 3, 4]
+        );
+    }
+
+    #[test]
+    fn test_split_statements_quotes_and_comments() {
+        let script = "-- create; the table\nCREATE TABLE \"a;b\" (v TEXT DEFAULT 'x;''y');\n/* ; */ INSERT INTO t /* ; */ VALUES (1); -- trailing;\n";
+        let statements = split(script, SqlDialect::Postgres);
+        assert_eq!(
+            sql(&statements),
+            [
+                "CREATE TABLE \"a;b\" (v TEXT DEFAULT 'x;''y')",
+                "INSERT INTO t /* ; */ VALUES (1)"
+            ]
+        );
+        assert_eq!(statements[0].line, 2);
+        assert_eq!(statements[1].line, 3);
+    }
+
+    #[test]
+    fn test_split_statements_empty() {
+        assert!(split("  -- nothing here\n;;\n/* at all */", SqlDialect::Postgres).is_empty());
+    }
+
+    #[test]
+    fn test_split_statements_dollar_quotes() {
+        let script = "CREATE FUNCTION f() RETURNS INT AS $$\nBEGIN\n  RETURN 1;\nEND;\n$$ LANGUAGE plpgsql;\n\
+                      DO $body$ BEGIN PERFORM 'it''s $$;'; END $body$;\n\
+                      SELECT $1::int, E'a\\';b';";
+        let statements = split(script, SqlDialect::Postgres);
+        assert_eq!(
+            sql(&statements),
+            [
+                "CREATE FUNCTION f() RETURNS INT AS $$\nBEGIN\n  RETURN 1;\nEND;\n$$ LANGUAGE plpgsql",
+                "DO $body$ BEGIN PERFORM 'it''s $$;'; END $body$",
+                "SELECT $1::int, E'a\\';b'"
+            ]
+        );
+        assert_eq!(
+            statements.iter().map(|s| s.line).collect::<Vec<_>>(),
+            [1, 6, 7]
+        );
+    }
+
+    #[test]
+    fn test_split_statements_copy_from_stdin() {
+        let script = "CREATE TABLE t (id INT, name TEXT);\n\
+                      COPY public.t (id, name) FROM stdin;\n\
+                      1\tO'Brien\n\
+                      2\t\\N\n\
+                      3\ttab\\there\n\
+                      \\.\n\
+                      SELECT 1;";
+        let statements = split(script, SqlDialect::Postgres);
+        assert_eq!(
+            sql(&statements),
+            [
+                "CREATE TABLE t (id INT, name TEXT)",
+                "INSERT INTO public.t (id, name) VALUES\n('1', 'O''Brien'),\n('2', NULL),\n('3', 'tab\there')",
+                "SELECT 1"
+            ]
+        );
+        assert_eq!(
+            statements.iter().map(|s| s.line).collect::<Vec<_>>(),
+            [1, 2, 7]
+        );
+
+        // COPY to a client or from a file is sent to the server unchanged
+        let statements = split(
+            "COPY t TO stdout;\nCOPY t FROM '/tmp/t.txt';",
+            SqlDialect::Postgres,
+        );
+        assert_eq!(
+            sql(&statements),
+            ["COPY t TO stdout", "COPY t FROM '/tmp/t.txt'"]
+        );
+    }
+
+    #[test]
+    fn test_split_statements_copy_errors() {
+        let err = split_statements(
+            "SELECT 1;\nCOPY t FROM stdin;\n1\n2\n",
+            SqlDialect::Postgres,
+        )
+        .unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(err.message.contains("not terminated"));
+
+        let err = split_statements(
+            "COPY t FROM stdin WITH (FORMAT csv);\n1\n\\.\n",
+            SqlDialect::Postgres,
+        )
+        .unwrap_err();
+        assert!(err.message.contains("with (format csv)"));
+    }
+
+    #[test]
+    fn test_split_statements_mysql_delimiter() {
+        let script = "# create the procedure\nDROP PROCEDURE IF EXISTS p;\nDELIMITER //\n\
+                      CREATE PROCEDURE p()\nBEGIN\n  SELECT 'a\\'; b';\n  SELECT 2;\nEND //\n\
+                      DELIMITER ;\n/*!40101 SET NAMES utf8 */;\nCALL p();";
+        let statements = split(script, SqlDialect::MySql);
+        assert_eq!(
+            sql(&statements),
+            [
+                "DROP PROCEDURE IF EXISTS p",
+                "CREATE PROCEDURE p()\nBEGIN\n  SELECT 'a\\'; b';\n  SELECT 2;\nEND",
+                "/*!40101 SET NAMES utf8 */",
+                "CALL p()"
+            ]
+        );
+        assert_eq!(
+            statements.iter().map(|s| s.line).collect::<Vec<_>>(),
+            [2, 4, 10, 11]
+        );
+
+        let err = split_statements("DELIMITER \nSELECT 1;", SqlDialect::MySql).unwrap_err();
+        assert_eq!(err.line, 1);
+    }
+
+    #[test]
+    fn test_copy_value_escapes() {
+        assert_eq!(copy_value("a\\\\b"), "'a\\b'");
+        assert_eq!(copy_value("\\101\\x42"), "'AB'");
+        assert_eq!(copy_value("line\\nbreak"), "'line\nbreak'");
+        assert_eq!(copy_value(""), "''");
+    }
+}
@@ -0,0 +1,497 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use super::{Row, SqlDialect, TestConnection, Value};
+
+/// Environment variable choosing how snapshots that differ are written
+///
+/// - `new` (default) - write the actual contents next to the snapshot as `.snap.new`
+///   and fail, so they can be reviewed with `testkit snapshots review`
+/// - `always` - overwrite the snapshot and pass
+/// - `no` - write nothing and fail, for CI
+pub const UPDATE_SNAPSHOTS_ENV: &str = "TESTKIT_UPDATE_SNAPSHOTS";
+
+/// The directory snapshots are stored in, relative to the crate root
+pub const SNAPSHOT_DIR: &str = "tests/snapshots";
+
+/// The value written in place of redacted columns
+const REDACTED: &str = "[redacted]";
+
+/// How snapshots that differ from the actual contents are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UpdateMode {
+    New,
+    Always,
+    No,
+}
+
+impl UpdateMode {
+    fn from_env() -> Self {
+        match std::env::var(UPDATE_SNAPSHOTS_ENV)
+            .unwrap_or_default()
+            .to_lowercase()
+            .as_str()
+        {
+            "always" | "1" | "true" => UpdateMode::Always,
+            "no" | "0" | "false" => UpdateMode::No,
+            _ => UpdateMode::New,
+        }
+    }
+}
+
+/// The rows of a table, rendered for a snapshot
+///
+/// Usually used through [`assert_table_snapshot!`](crate::assert_table_snapshot). Every
+/// row is written as a list item with one `column: value` line per column, so a changed
+/// value shows up as a single changed line in a diff:
+///
+/// ```text
+/// # orders ordered by id
+/// - id: 1
+///   user_id: 1
+///   note: "gift wrap"
+///   created_at: [redacted]
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableSnapshot {
+    table: String,
+    order_by: Option<String>,
+    redact: Vec<String>,
+    name: Option<String>,
+}
+
+impl TableSnapshot {
+    /// Snapshot the rows of a table, ordered by the first column
+    pub fn new(table: impl Into<String>) -> Self {
+        Self {
+            table: table.into(),
+            order_by: None,
+            redact: Vec::new(),
+            name: None,
+        }
+    }
+
+    /// Order the rows by a SQL expression such as `id` or `user_id, created_at DESC`
+    pub fn order_by(mut self, order_by: impl Into<String>) -> Self {
+        self.order_by = Some(order_by.into());
+        self
+    }
+
+    /// Write the values of these columns as `[redacted]`, unless they are NULL
+    ///
+    /// Used for columns that differ on every run, such as timestamps and UUIDs.
+    pub fn redact<I, S>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.redact.extend(columns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Name the snapshot file instead of deriving the name from the test and the table
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Returns the name of the snapshot file, without the `.snap` extension
+    ///
+    /// `function` is the path of the test function, see [`snapshot_name`].
+    pub fn snapshot_name(&self, function: &str) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("{}__{}", snapshot_name(function), self.table),
+        }
+    }
+
+    /// Read the rows of the table and render them
+    pub async fn render<C>(&self, conn: &C) -> Result<String, C::Error>
+    where
+        C: TestConnection,
+    {
+        let dialect = conn.dialect();
+        let order_by = self.order_by.as_deref().unwrap_or("1");
+        let sql = format!(
+            "SELECT * FROM {} ORDER BY {}",
+            dialect.quote_identifier(&self.table),
+            order_by
+        );
+        let rows = conn.query(&sql, &[]).await?;
+
+        Ok(self.render_rows(dialect, &rows))
+    }
+
+    fn render_rows(&self, dialect: SqlDialect, rows: &[Row]) -> String {
+        let mut out = format!(
+            "# {} ordered by {}\n",
+            self.table,
+            self.order_by.as_deref().unwrap_or("the first column")
+        );
+        if rows.is_empty() {
+            out.push_str("[]\n");
+        }
+
+        for row in rows {
+            for (i, (column, value)) in row.columns().iter().zip(row.values()).enumerate() {
+                let prefix = if i == 0 { "- " } else { "  " };
+                let value = if self.redact.contains(column) && !value.is_null() {
+                    REDACTED.to_string()
+                } else {
+                    render_value(dialect, value)
+                };
+                let _ = writeln!(out, "{}{}: {}", prefix, column, value);
+            }
+        }
+
+        out
+    }
+}
+
+/// Render a value so that its type stays visible, with text quoted and escaped
+fn render_value(dialect: SqlDialect, value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Text(s) => format!("{:?}", s),
+        // Keep the decimal point that tells a whole float from an integer
+        Value::Float(x) => format!("{:?}", x),
+        _ => dialect.literal(value).to_lowercase(),
+    }
+}
+
+/// Derive a snapshot name from the path of a test function
+///
+/// The crate name and the `{{closure}}` segments of async bodies are dropped and the
+/// remaining segments joined with `__`, so `tests::orders::{{closure}}` becomes `orders`
+/// and every backend of a `#[testkit::matrix]` test gets its own snapshot.
+pub fn snapshot_name(function: &str) -> String {
+    function
+        .split("::")
+        .skip(1)
+        .filter(|segment| *segment != "{{closure}}")
+        .collect::<Vec<_>>()
+        .join("__")
+}
+
+/// Compare contents with the snapshot stored in `tests/snapshots/<name>.snap` of a crate
+///
+/// # Panics
+///
+/// Panics with a diff when the snapshot is missing or differs, after writing the actual
+/// contents as configured by `TESTKIT_UPDATE_SNAPSHOTS`.
+#[track_caller]
+pub fn assert_snapshot(manifest_dir: &str, name: &str, contents: &str) {
+    assert_snapshot_with(manifest_dir, name, contents, snapshot_diff);
+}
+
+/// Like [`assert_snapshot`], describing the differences with a custom function
+///
+/// `describe` gets the stored and the actual contents, or an empty string and the
+/// actual contents when the snapshot doesn't exist yet.
+#[track_caller]
+pub fn assert_snapshot_with(
+    manifest_dir: &str,
+    name: &str,
+    contents: &str,
+    describe: impl Fn(&str, &str) -> String,
+) {
+    let path = Path::new(manifest_dir)
+        .join(SNAPSHOT_DIR)
+        .join(format!("{}.snap", name));
+    let new_path = pending_path(&path);
+    let expected = std::fs::read_to_string(&path).ok();
+
+    if expected.as_deref() == Some(contents) {
+        let _ = std::fs::remove_file(&new_path);
+        return;
+    }
+
+    let mode = UpdateMode::from_env();
+    let write = |target: &Path| {
+        std::fs::create_dir_all(target.parent().expect("snapshot paths have a parent"))
+            .and_then(|_| std::fs::write(target, contents))
+            .unwrap_or_else(|e| panic!("failed to write snapshot {}: {}", target.display(), e));
+    };
+
+    match mode {
+        UpdateMode::Always => {
+            write(&path);
+            let _ = std::fs::remove_file(&new_path);
+            eprintln!("testkit: updated snapshot {}", path.display());
+            return;
+        }
+        UpdateMode::New => write(&new_path),
+        UpdateMode::No => {}
+    }
+
+    let hint = match mode {
+        UpdateMode::New => format!(
+            "the actual contents were written to {}, accept them with `testkit snapshots accept`",
+            new_path.display()
+        ),
+        _ => format!(
+            "set {}=always to overwrite the snapshot",
+            UPDATE_SNAPSHOTS_ENV
+        ),
+    };
+    match expected {
+        Some(expected) => panic!(
+            "snapshot {} differs:\n{}\n{}",
+            path.display(),
+            describe(&expected, contents),
+            hint
+        ),
+        None => panic!(
+            "snapshot {} doesn't exist:\n{}\n{}",
+            path.display(),
+            describe("", contents),
+            hint
+        ),
+    }
+}
+
+/// A snapshot whose actual contents are waiting for review in a `.snap.new` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingSnapshot {
+    /// The path of the snapshot, which may not exist yet
+    pub path: PathBuf,
+    /// The path of the `.snap.new` file
+    pub new_path: PathBuf,
+}
+
+impl PendingSnapshot {
+    /// Find the pending snapshots under a directory, skipping `target` and hidden directories
+    pub fn find(root: &Path) -> std::io::Result<Vec<Self>> {
+        let mut pending = Vec::new();
+        let mut dirs = vec![root.to_path_buf()];
+
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(&dir)? {
+                let path = entry?.path();
+                let file_name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+
+                if path.is_dir() {
+                    if file_name != "target" && !file_name.starts_with('.') {
+                        dirs.push(path);
+                    }
+                } else if let Some(snap) = file_name.strip_suffix(".new")
+                    && snap.ends_with(".snap")
+                {
+                    pending.push(Self {
+                        path: path.with_file_name(snap),
+                        new_path: path,
+                    });
+                }
+            }
+        }
+
+        pending.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(pending)
+    }
+
+    /// Returns the changes from the stored snapshot to the pending contents
+    pub fn diff(&self) -> std::io::Result<String> {
+        let old = std::fs::read_to_string(&self.path).unwrap_or_default();
+        let new = std::fs::read_to_string(&self.new_path)?;
+        Ok(snapshot_diff(&old, &new))
+    }
+
+    /// Replace the snapshot with the pending contents
+    pub fn accept(&self) -> std::io::Result<()> {
+        std::fs::rename(&self.new_path, &self.path)
+    }
+
+    /// Discard the pending contents
+    pub fn reject(&self) -> std::io::Result<()> {
+        std::fs::remove_file(&self.new_path)
+    }
+}
+
+/// Returns the path of the `.snap.new` file of a snapshot
+fn pending_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".new");
+    PathBuf::from(name)
+}
+
+/// Render a line diff, with removed lines prefixed by `-` and added lines by `+`
+pub fn snapshot_diff(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            let _ = writeln!(out, "  {}", old[i]);
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            let _ = writeln!(out, "- {}", old[i]);
+            i += 1;
+        } else {
+            let _ = writeln!(out, "+ {}", new[j]);
+            j += 1;
+        }
+    }
+
+    out
+}
+
+/// Compare the rows of a table with a snapshot under `tests/snapshots/`
+///
+/// Takes a connection implementing [`TestConnection`](crate::TestConnection), the table
+/// and optional `order_by = "..."`, `redact = [...]` and `name = "..."` arguments, see
+/// [`TableSnapshot`]. Must be called in an async function.
+///
+/// When the snapshot is missing or differs, the test fails with a diff and the actual
+/// rows are written to a `.snap.new` file. Review them with `testkit snapshots review`
+/// and accept them with `testkit snapshots accept`, or set `TESTKIT_UPDATE_SNAPSHOTS`,
+/// see [`UPDATE_SNAPSHOTS_ENV`](crate::UPDATE_SNAPSHOTS_ENV).
+///
+/// ```rust,ignore
+/// let conn = ctx.db.acquire_connection().await?;
+/// testkit_core::assert_table_snapshot!(conn, "orders", order_by = "id", redact = ["created_at"]);
+/// ```
+#[macro_export]
+macro_rules! assert_table_snapshot {
+    ($conn:expr, $table:expr $(, $option:ident = $value:expr)* $(,)?) => {{
+        let snapshot = $crate::TableSnapshot::new($table) $(.$option($value))*;
+        let contents = snapshot
+            .render(&$conn)
+            .await
+            .unwrap_or_else(|e| panic!("failed to read the rows of {}: {}", $table, e));
+        let name = snapshot.snapshot_name($crate::__function_path!());
+        $crate::assert_snapshot(env!("CARGO_MANIFEST_DIR"), &name, &contents);
+    }};
+}
+
+/// Returns the path of the function the macro is called in
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __function_path {
+    () => {{
+        fn f() {}
+        fn type_name_of<T>(_: T) -> &'static str {
+            ::std::any::type_name::<T>()
+        }
+        let name = type_name_of(f);
+        name.strip_suffix("::f").unwrap_or(name)
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_render_rows() {
+        let columns: Arc<[String]> = ["id", "note", "created_at", "data"]
+            .map(String::from)
+            .into();
+        let rows = [
+            Row::new(
+                columns.clone(),
+                vec![
+                    Value::Int(1),
+                    Value::from("say \"hi\"\n"),
+                    Value::from("2024-01-31 10:00:00"),
+                    Value::from(vec![0xca, 0xfe]),
+                ],
+            ),
+            Row::new(
+                columns.clone(),
+                vec![Value::Int(2), Value::Null, Value::Null, Value::Bool(true)],
+            ),
+            Row::new(
+                columns,
+                vec![Value::Int(3), Value::Null, Value::Null, Value::Float(1.0)],
+            ),
+        ];
+
+        let snapshot = TableSnapshot::new("orders")
+            .order_by("id")
+            .redact(["created_at"]);
+        assert_eq!(
+            snapshot.render_rows(SqlDialect::Postgres, &rows),
+            "# orders ordered by id\n\
+             - id: 1\n  note: \"say \\\"hi\\\"\\n\"\n  created_at: [redacted]\n  data: '\\xcafe'\n\
+             - id: 2\n  note: null\n  created_at: null\n  data: true\n\
+             - id: 3\n  note: null\n  created_at: null\n  data: 1.0\n"
+        );
+        assert_eq!(
+            TableSnapshot::new("orders").render_rows(SqlDialect::MySql, &[]),
+            "# orders ordered by the first column\n[]\n"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_name() {
+        assert_eq!(
+            snapshot_name("matrix_tests::test_orders::tokio_postgres::{{closure}}"),
+            "test_orders__tokio_postgres"
+        );
+        assert_eq!(
+            TableSnapshot::new("orders").snapshot_name("tests::test_orders::{{closure}}"),
+            "test_orders__orders"
+        );
+        assert_eq!(
+            TableSnapshot::new("orders")
+                .name("shop")
+                .snapshot_name("tests::test_orders"),
+            "shop"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_diff() {
+        assert_eq!(
+            snapshot_diff("a\nb\nc\n", "a\nc\nd\n"),
+            "  a\n- b\n  c\n+ d\n"
+        );
+        assert_eq!(snapshot_diff("", "a\n"), "+ a\n");
+    }
+
+    #[test]
+    fn test_pending_snapshots() {
+        let root = std::env::temp_dir().join(format!("testkit_{}", uuid::Uuid::new_v4()));
+        let dir = root.join(SNAPSHOT_DIR);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::create_dir_all(root.join("target/snapshots")).unwrap();
+        std::fs::write(dir.join("orders.snap"), "a\nb\n").unwrap();
+        std::fs::write(dir.join("orders.snap.new"), "a\nc\n").unwrap();
+        std::fs::write(dir.join("users.snap.new"), "x\n").unwrap();
+        std::fs::write(root.join("target/snapshots/old.snap.new"), "x\n").unwrap();
+
+        let pending = PendingSnapshot::find(&root).unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].path, dir.join("orders.snap"));
+        assert_eq!(pending[0].diff().unwrap(), "  a\n- b\n+ c\n");
+
+        pending[0].accept().unwrap();
+        pending[1].reject().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("orders.snap")).unwrap(),
+            "a\nc\n"
+        );
+        assert!(PendingSnapshot::find(&root).unwrap().is_empty());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
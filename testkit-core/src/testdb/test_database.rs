@@ -1,11 +1,23 @@
 use async_trait::async_trait;
 use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
 use std::fmt::{Debug, Display};
 use std::future::Future;
+use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use uuid::Uuid;
 
+use super::capture::StatementLog;
+use super::fake::seed_from_env;
+use super::migrate::MIGRATION_TABLES;
+use super::{
+    Changes, DataSnapshot, DatabaseTransaction, Fake, Fixtures, Introspect, Migrator, Row,
+    SqlDialect, Statement, TestConnection, TransactionalConnection, wait_until,
+};
+
 /// Configuration for database connections
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DatabaseConfig {
@@ -15,6 +27,11 @@ pub struct DatabaseConfig {
     pub user_url: String,
     /// Maximum number of connections to the database
     pub max_connections: Option<usize>,
+    /// Directory of migrations applied to every database the backend creates
+    ///
+    /// The sqlx backends run it with `sqlx::migrate::Migrator`, the others with
+    /// [`Migrator`]. Migrations given to the backend with `WithMigrations` take precedence.
+    pub migrations: Option<PathBuf>,
 }
 
 impl Default for DatabaseConfig {
@@ -32,9 +49,16 @@ impl DatabaseConfig {
             admin_url: admin_url.into(),
             user_url: user_url.into(),
             max_connections: None,
+            migrations: None,
         }
     }
 
+    /// Apply the migrations in a directory to every database the backend creates
+    pub fn with_migrations(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.migrations = Some(dir.into());
+        self
+    }
+
     /// Get a configuration from environment variables
     /// Uses ADMIN_DATABASE_URL and DATABASE_URL
     pub fn from_env() -> std::result::Result<Self, std::env::VarError> {
@@ -44,6 +68,29 @@ impl DatabaseConfig {
         let admin_url = std::env::var("ADMIN_DATABASE_URL").unwrap_or(user_url.clone());
         Ok(Self::new(admin_url, user_url))
     }
+
+    /// Get a configuration for a server of the given dialect from environment variables
+    ///
+    /// Uses the dialect specific variable (`POSTGRES_DATABASE_URL` or `MYSQL_DATABASE_URL`)
+    /// for both connection strings, falling back to DATABASE_URL and ADMIN_DATABASE_URL when
+    /// they point to a server of that dialect. Returns `None` if no server is configured.
+    pub fn for_dialect(dialect: SqlDialect) -> Option<Self> {
+        #[cfg(feature = "dotenvy")]
+        let _ = dotenvy::from_filename(".env");
+        let matching = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .filter(|url| dialect.matches_url(url))
+        };
+
+        if let Some(url) = matching(dialect.url_env()) {
+            return Some(Self::new(url.clone(), url));
+        }
+
+        let user_url = matching("DATABASE_URL")?;
+        let admin_url = matching("ADMIN_DATABASE_URL").unwrap_or(user_url.clone());
+        Some(Self::new(admin_url, user_url))
+    }
 }
 
 /// A unique database name
@@ -62,6 +109,15 @@ impl DatabaseName {
     pub fn as_str(&self) -> &str {
         &self.0
     }
+
+    /// Returns the seed of the fake data generated for this database
+    ///
+    /// Derived from the name, so every test database generates different data unless
+    /// `TESTKIT_SEED` is set.
+    pub fn seed(&self) -> u64 {
+        let digest = Sha256::digest(self.0.as_bytes());
+        u64::from_be_bytes(digest[..8].try_into().expect("digest is 32 bytes"))
+    }
 }
 
 impl Display for DatabaseName {
@@ -144,6 +200,35 @@ where
     pub db_name: DatabaseName,
     /// The connection pool
     pub connection_pool: Option<Arc<Mutex<Vec<B::Connection>>>>,
+    /// Drops the database once the last clone of this instance goes away
+    drop_guard: Arc<DropGuard<B>>,
+}
+
+/// Shared cleanup state for all clones of a [`TestDatabaseInstance`]
+struct DropGuard<B>
+where
+    B: DatabaseBackend + 'static + Clone + Debug + Send + Sync,
+{
+    backend: B,
+    db_name: DatabaseName,
+    keep: AtomicBool,
+    /// The statements sent to the database, recorded by the connections
+    statements: Arc<StatementLog>,
+}
+
+impl<B> DropGuard<B>
+where
+    B: DatabaseBackend + 'static + Clone + Debug + Send + Sync,
+{
+    fn new(backend: B, db_name: DatabaseName) -> Arc<Self> {
+        let statements = StatementLog::register(&backend.connection_string(&db_name));
+        Arc::new(Self {
+            backend,
+            db_name,
+            keep: AtomicBool::new(false),
+            statements,
+        })
+    }
 }
 
 impl<B> Debug for TestDatabaseInstance<B>
@@ -175,6 +260,7 @@ where
         backend.create_database(&pool, &db_name).await?;
 
         let inst = Self {
+            drop_guard: DropGuard::new(backend.clone(), db_name.clone()),
             backend,
             pool,
             db_name,
@@ -197,6 +283,7 @@ where
         backend.create_database(&pool, &db_name).await?;
 
         let inst = Self {
+            drop_guard: DropGuard::new(backend.clone(), db_name.clone()),
             backend,
             pool,
             db_name,
@@ -216,6 +303,48 @@ where
         &self.db_name
     }
 
+    /// Returns the seed of the fake data generated for this database
+    ///
+    /// Taken from the `TESTKIT_SEED` environment variable, or derived from the database
+    /// name. Failed tests print it, so a rerun with `TESTKIT_SEED` set generates the same data.
+    pub fn seed(&self) -> u64 {
+        seed_from_env().unwrap_or_else(|| self.db_name.seed())
+    }
+
+    /// Returns a fake data generator starting from the [`seed`](Self::seed) of this database
+    ///
+    /// Every call starts over, so hold on to the generator to get different values.
+    pub fn fake(&self) -> Fake {
+        Fake::new(self.seed())
+    }
+
+    /// Returns the statements executed on this database so far, oldest first
+    ///
+    /// Every connection to the database records its statements with their parameters,
+    /// duration and row count. See [`assert_query_count!`](crate::assert_query_count) and
+    /// [`assert_no_n_plus_one!`](crate::assert_no_n_plus_one).
+    pub fn queries(&self) -> Vec<Statement> {
+        self.drop_guard.statements.statements()
+    }
+
+    /// Forget the statements recorded so far
+    pub fn clear_queries(&self) {
+        self.drop_guard.statements.clear();
+    }
+
+    /// Keep the database after the last handle to it is dropped
+    ///
+    /// This is used to leave the database of a failed test in place so it can be
+    /// inspected. Kept databases can be removed later with `testkit reset`.
+    pub fn keep(&self) {
+        self.drop_guard.keep.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true if the database will be kept after the last handle is dropped
+    pub fn is_kept(&self) -> bool {
+        self.drop_guard.keep.load(Ordering::SeqCst)
+    }
+
     /// Create a single connection to the database without using the pool
     /// This is useful for cases where a single connection is needed for a specific operation
     pub async fn connect(&self) -> Result<B::Connection, B::Error> {
@@ -271,6 +400,120 @@ where
         Ok(())
     }
 
+    /// Apply migrations to the database
+    ///
+    /// Migrations that were already applied to this database are skipped.
+    pub async fn migrate(&self, migrator: &Migrator) -> Result<(), B::Error>
+    where
+        B::Connection: TransactionalConnection,
+    {
+        let conn = self.acquire_connection().await?;
+        let result = migrator.run(&conn).await;
+        self.release_connection(conn).await?;
+
+        result
+            .map(|_| ())
+            .map_err(|e| B::Error::from(e.to_string()))
+    }
+
+    /// Insert fixtures into the database, see [`Fixtures`]
+    pub async fn load_fixtures(&self, fixtures: &Fixtures) -> Result<(), B::Error>
+    where
+        B::Connection: TestConnection,
+    {
+        let conn = self.acquire_connection().await?;
+        let result = fixtures.load(&conn).await;
+        self.release_connection(conn).await?;
+
+        result.map_err(|e| B::Error::from(e.to_string()))
+    }
+
+    /// Returns the rows a function inserts, updates and deletes, per table
+    ///
+    /// Reads every table except the migration tables before and after running the
+    /// function with a connection to the database, and matches the rows by primary key.
+    /// Meant for the small tables of tests, as every row is read twice.
+    ///
+    /// ```rust,ignore
+    /// let changes = ctx.db.capture_changes(|conn| async move {
+    ///     conn.execute("UPDATE users SET name = 'Bob' WHERE id = 1", &[]).await
+    /// }).await?;
+    /// assert_eq!(changes.changed_tables(), vec!["users"]);
+    /// ```
+    pub async fn capture_changes<F, Fut, E>(&self, operation: F) -> Result<Changes, B::Error>
+    where
+        B::Connection: TestConnection,
+        F: FnOnce(B::Connection) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+        E: Display,
+    {
+        let conn = self.acquire_connection().await?;
+        let result = async {
+            let before = DataSnapshot::read(&conn, &[])
+                .await
+                .map_err(|e| B::Error::from(e.to_string()))?;
+            operation(conn.clone())
+                .await
+                .map_err(|e| B::Error::from(e.to_string()))?;
+            let after = DataSnapshot::read(&conn, &[])
+                .await
+                .map_err(|e| B::Error::from(e.to_string()))?;
+            Ok(before.changes(&after))
+        }
+        .await;
+        self.release_connection(conn).await?;
+
+        result
+    }
+
+    /// Poll a query until a predicate over its rows holds, see [`wait_until`]
+    ///
+    /// Returns the rows that satisfied the predicate. On timeout the error shows the
+    /// rows of the last poll.
+    pub async fn wait_until<P>(
+        &self,
+        sql: &str,
+        predicate: P,
+        timeout: Duration,
+        interval: Duration,
+    ) -> Result<Vec<Row>, B::Error>
+    where
+        B::Connection: TestConnection,
+        P: Fn(&[Row]) -> bool,
+    {
+        let conn = self.acquire_connection().await?;
+        let result = wait_until(&conn, sql, predicate, timeout, interval).await;
+        self.release_connection(conn).await?;
+
+        result.map_err(|e| B::Error::from(e.to_string()))
+    }
+
+    /// Empty every table except the migration tables and the excluded ones
+    ///
+    /// Meant for suites that share one database between tests, where it is much faster
+    /// than creating a new database. Postgres truncates all tables in one `TRUNCATE ...
+    /// RESTART IDENTITY`, so sequences start over. MySQL truncates each table with
+    /// foreign key checks disabled, which also resets the AUTO_INCREMENT counters, and
+    /// turns the checks back on even if a table can't be truncated.
+    ///
+    /// Fails without emptying anything if an excluded table references one of the
+    /// tables to truncate, since its rows would be left pointing nowhere.
+    ///
+    /// ```rust,ignore
+    /// // Keep the reference data loaded by the migrations
+    /// ctx.db.reset(&["countries"]).await?;
+    /// ```
+    pub async fn reset(&self, exclude: &[&str]) -> Result<(), B::Error>
+    where
+        B::Connection: TransactionalConnection,
+    {
+        let conn = self.acquire_connection().await?;
+        let result = reset_tables(&conn, exclude).await;
+        self.release_connection(conn).await?;
+
+        result
+    }
+
     /// Setup the database with a function
     /// The connection handling approach needs to match the expected B::Connection type
     pub async fn setup<F, Fut>(&self, setup_fn: F) -> Result<(), B::Error>
@@ -293,14 +536,95 @@ where
     }
 }
 
-impl<B> Drop for TestDatabaseInstance<B>
+/// Truncate the tables of a database, see [`TestDatabaseInstance::reset`]
+async fn reset_tables<C, E>(conn: &C, exclude: &[&str]) -> Result<(), E>
+where
+    C: TransactionalConnection,
+    E: From<String>,
+{
+    let reset_error = |e: &dyn Display| E::from(format!("failed to reset the database: {}", e));
+
+    let tables: Vec<String> = conn
+        .tables()
+        .await
+        .map_err(|e| reset_error(&e))?
+        .into_iter()
+        .filter(|table| {
+            !MIGRATION_TABLES.contains(&table.as_str()) && !exclude.contains(&table.as_str())
+        })
+        .collect();
+    if tables.is_empty() {
+        return Ok(());
+    }
+
+    let foreign_keys = conn.foreign_keys().await.map_err(|e| reset_error(&e))?;
+    if let Some(key) = foreign_keys
+        .iter()
+        .find(|key| !tables.contains(&key.table) && tables.contains(&key.referenced_table))
+    {
+        return Err(E::from(format!(
+            "can't reset the database: the excluded table {} references {} through {}, \
+             exclude {} as well",
+            key.table, key.referenced_table, key.name, key.referenced_table
+        )));
+    }
+
+    let dialect = conn.dialect();
+    let quoted: Vec<String> = tables.iter().map(|t| dialect.quote_identifier(t)).collect();
+
+    // The transaction keeps the statements on one session. MySQL commits implicitly
+    // at every TRUNCATE, so only Postgres rolls back a failed reset.
+    let mut tx = conn
+        .begin_transaction()
+        .await
+        .map_err(|e| reset_error(&e))?;
+    let result = match dialect {
+        SqlDialect::Postgres => {
+            tx.batch_execute(&format!(
+                "TRUNCATE TABLE {} RESTART IDENTITY",
+                quoted.join(", ")
+            ))
+            .await
+        }
+        SqlDialect::MySql => {
+            let truncates: Vec<String> = quoted
+                .iter()
+                .map(|table| format!("TRUNCATE TABLE {};", table))
+                .collect();
+            let truncated = tx
+                .batch_execute(&format!(
+                    "SET FOREIGN_KEY_CHECKS = 0; {}",
+                    truncates.join(" ")
+                ))
+                .await;
+            let restored = tx.batch_execute("SET FOREIGN_KEY_CHECKS = 1").await;
+            truncated.and(restored)
+        }
+    };
+
+    match result {
+        Ok(()) => tx.commit().await.map_err(|e| reset_error(&e)),
+        Err(e) => {
+            let _ = tx.rollback().await;
+            Err(reset_error(&e))
+        }
+    }
+}
+
+impl<B> Drop for DropGuard<B>
 where
     B: DatabaseBackend + Clone + Debug + Send + Sync + 'static,
 {
     fn drop(&mut self) {
-        let name = self.db_name.clone();
+        let name = &self.db_name;
+        StatementLog::unregister(&self.backend.connection_string(name));
 
-        if let Err(err) = self.backend.drop_database(&name) {
+        if self.keep.load(Ordering::SeqCst) {
+            tracing::info!("Keeping database {} after Drop", name);
+            return;
+        }
+
+        if let Err(err) = self.backend.drop_database(name) {
             tracing::error!("Failed to drop database {}: {}", name, err);
         } else {
             tracing::info!("Successfully dropped database {} during Drop", name);
@@ -317,4 +641,11 @@ mod tests {
         let name = DatabaseName::new(None);
         assert_ne!(name.as_str(), "");
     }
+
+    #[test]
+    fn test_database_name_seed() {
+        let name = DatabaseName::new(None);
+        assert_eq!(name.seed(), name.clone().seed());
+        assert_ne!(name.seed(), DatabaseName::new(None).seed());
+    }
 }
@@ -1,57 +1,200 @@
 use async_trait::async_trait;
+use std::fmt::Debug;
 
-/// Trait for managing database transactions
+use super::{DatabaseBackend, DatabasePool, SqlDialect, TestConnection, TestDatabaseInstance};
+
+/// Transaction isolation level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IsolationLevel {
+    ReadUncommitted,
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl IsolationLevel {
+    /// The SQL spelling of the isolation level
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            IsolationLevel::ReadUncommitted => "READ UNCOMMITTED",
+            IsolationLevel::ReadCommitted => "READ COMMITTED",
+            IsolationLevel::RepeatableRead => "REPEATABLE READ",
+            IsolationLevel::Serializable => "SERIALIZABLE",
+        }
+    }
+}
+
+impl std::fmt::Display for IsolationLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_sql())
+    }
+}
+
+/// Options for starting a transaction
+///
+/// The default options leave every characteristic at the server default.
+/// `deferrable` only has an effect on PostgreSQL, and only for serializable
+/// read-only transactions; MySQL has no equivalent and ignores it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TxOptions {
+    /// Isolation level, or `None` for the server default
+    pub isolation: Option<IsolationLevel>,
+    /// Start the transaction in read-only mode
+    pub read_only: bool,
+    /// Start the transaction in deferrable mode
+    pub deferrable: bool,
+}
+
+impl TxOptions {
+    /// Options for a transaction with the given isolation level
+    pub fn with_isolation(isolation: IsolationLevel) -> Self {
+        Self {
+            isolation: Some(isolation),
+            ..Self::default()
+        }
+    }
+
+    /// The `SET TRANSACTION` statement applying these options
+    ///
+    /// Returns `None` when every option is at its default. On PostgreSQL the statement has
+    /// to be the first one inside the transaction; on MySQL it has to run right before the
+    /// transaction starts, and applies to that transaction only.
+    pub fn set_transaction_sql(&self, dialect: SqlDialect) -> Option<String> {
+        let mut characteristics = Vec::new();
+        if let Some(isolation) = self.isolation {
+            characteristics.push(format!("ISOLATION LEVEL {}", isolation));
+        }
+        if self.read_only {
+            characteristics.push("READ ONLY".to_string());
+        }
+        if self.deferrable && dialect == SqlDialect::Postgres {
+            characteristics.push("DEFERRABLE".to_string());
+        }
+
+        if characteristics.is_empty() {
+            None
+        } else {
+            Some(format!("SET TRANSACTION {}", characteristics.join(", ")))
+        }
+    }
+}
+
+/// A transaction on a test database
 ///
-/// This trait allows for common transaction operations like beginning, committing, and
-/// rolling back transactions. It's used by the `with_transaction` operator to provide
-/// automatic transaction management.
+/// Statements run inside the transaction through the [`TestConnection`] methods. `commit`
+/// and `rollback` finish the transaction; finishing it twice or running a statement on a
+/// finished transaction returns an error.
 #[async_trait]
-pub trait DBTransactionManager<Tx, Conn>: Send + Sync {
+pub trait DatabaseTransaction: TestConnection {
+    /// Commit the transaction
+    async fn commit(&mut self) -> Result<(), Self::Error>;
+
+    /// Rollback the transaction
+    async fn rollback(&mut self) -> Result<(), Self::Error>;
+
+    /// The options the transaction was started with
+    fn options(&self) -> &TxOptions;
+}
+
+/// A connection that transactions can be started on
+///
+/// Implemented by the connection type of every backend.
+#[async_trait]
+pub trait TransactionalConnection: TestConnection {
+    /// The transaction type returned by begin_transaction
+    type Transaction: DatabaseTransaction<Error = Self::Error> + Send + Sync;
+
+    /// Begin a transaction on this connection
+    async fn begin_transaction(&self) -> Result<Self::Transaction, Self::Error> {
+        self.begin_transaction_with(TxOptions::default()).await
+    }
+
+    /// Begin a transaction on this connection with the given options
+    async fn begin_transaction_with(
+        &self,
+        options: TxOptions,
+    ) -> Result<Self::Transaction, Self::Error>;
+}
+
+/// Trait for starting transactions on a test database
+///
+/// Implemented for the `TestDatabaseInstance` of every backend whose connections implement
+/// [`TransactionalConnection`], so generic code can begin a transaction without knowing the
+/// backend. Each transaction runs on its own connection from the pool of the database.
+#[async_trait]
+pub trait TransactionManager: Send + Sync {
     /// The error type for transaction operations
-    type Error: Send + Sync;
+    type Error: std::error::Error + Send + Sync + 'static;
 
     /// The transaction type returned by begin_transaction
     type Tx: DatabaseTransaction<Error = Self::Error> + Send + Sync;
 
     /// Begin a new transaction
-    async fn begin_transaction(&mut self) -> Result<Self::Tx, Self::Error>;
+    async fn begin_transaction(&self) -> Result<Self::Tx, Self::Error> {
+        self.begin_transaction_with(TxOptions::default()).await
+    }
+
+    /// Begin a new transaction with the given options
+    async fn begin_transaction_with(&self, options: TxOptions) -> Result<Self::Tx, Self::Error>;
 
     /// Commit a transaction
-    async fn commit_transaction(tx: &mut Self::Tx) -> Result<(), Self::Error>;
+    async fn commit_transaction(tx: &mut Self::Tx) -> Result<(), Self::Error> {
+        tx.commit().await
+    }
 
     /// Rollback a transaction
-    async fn rollback_transaction(tx: &mut Self::Tx) -> Result<(), Self::Error>;
+    async fn rollback_transaction(tx: &mut Self::Tx) -> Result<(), Self::Error> {
+        tx.rollback().await
+    }
 }
 
-/// Trait for objects that represent database transactions
-///
-/// This trait allows database-specific transaction types to be used
-/// with the transaction management system.
 #[async_trait]
-pub trait DatabaseTransaction: Send + Sync {
-    /// The error type for transaction operations
-    type Error: Send + Sync;
-
-    /// Commit the transaction
-    async fn commit(&mut self) -> Result<(), Self::Error>;
-
-    /// Rollback the transaction
-    async fn rollback(&mut self) -> Result<(), Self::Error>;
+impl<DB> TransactionManager for TestDatabaseInstance<DB>
+where
+    DB: DatabaseBackend + Send + Sync + Debug + 'static,
+    DB::Connection: TransactionalConnection,
+    <DB::Connection as TestConnection>::Error: From<DB::Error>,
+{
+    type Error = <DB::Connection as TestConnection>::Error;
+    type Tx = <DB::Connection as TransactionalConnection>::Transaction;
+
+    async fn begin_transaction_with(&self, options: TxOptions) -> Result<Self::Tx, Self::Error> {
+        let conn = self.pool.acquire().await?;
+        conn.begin_transaction_with(options).await
+    }
 }
 
-// Implementation of TransactionManager for TestDatabaseInstance
 #[cfg(test)]
 pub mod tests {
     use super::*;
     use crate::{
-        DatabaseBackend, DatabaseConfig, DatabaseName, DatabasePool, TestDatabaseConnection,
-        TestDatabaseInstance,
+        DatabaseBackend, DatabaseConfig, DatabaseName, DatabasePool, Row, SqlDialect,
+        TestDatabaseConnection, TestDatabaseInstance, Value,
     };
     use std::fmt::Debug;
 
+    // Mock error type for testing
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct MockError(String);
+
+    impl std::fmt::Display for MockError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    impl std::error::Error for MockError {}
+
+    impl From<String> for MockError {
+        fn from(s: String) -> Self {
+            MockError(s)
+        }
+    }
+
     // Mock transaction type for testing
     #[derive(Debug, Clone, Default)]
     pub struct MockTransaction {
+        options: TxOptions,
         committed: bool,
         rolled_back: bool,
     }
@@ -64,21 +207,53 @@ pub mod tests {
         pub fn is_rolled_back(&self) -> bool {
             self.rolled_back
         }
+
+        fn check_active(&self) -> Result<(), MockError> {
+            if self.committed || self.rolled_back {
+                return Err(MockError("transaction is already finished".to_string()));
+            }
+            Ok(())
+        }
     }
 
     #[async_trait]
-    impl DatabaseTransaction for MockTransaction {
-        type Error = String;
+    impl TestConnection for MockTransaction {
+        type Error = MockError;
+
+        fn dialect(&self) -> SqlDialect {
+            SqlDialect::Postgres
+        }
 
+        async fn execute(&self, _sql: &str, _params: &[Value]) -> Result<u64, Self::Error> {
+            self.check_active().map(|_| 0)
+        }
+
+        async fn query(&self, _sql: &str, _params: &[Value]) -> Result<Vec<Row>, Self::Error> {
+            self.check_active().map(|_| Vec::new())
+        }
+
+        async fn batch_execute(&self, _sql: &str) -> Result<(), Self::Error> {
+            self.check_active()
+        }
+    }
+
+    #[async_trait]
+    impl DatabaseTransaction for MockTransaction {
         async fn commit(&mut self) -> Result<(), Self::Error> {
+            self.check_active()?;
             self.committed = true;
             Ok(())
         }
 
         async fn rollback(&mut self) -> Result<(), Self::Error> {
+            self.check_active()?;
             self.rolled_back = true;
             Ok(())
         }
+
+        fn options(&self) -> &TxOptions {
+            &self.options
+        }
     }
 
     // Mock connection type for testing
@@ -108,7 +283,7 @@ pub mod tests {
     #[async_trait]
     impl DatabasePool for MockPool {
         type Connection = MockConnection;
-        type Error = String;
+        type Error = MockError;
 
         async fn acquire(&self) -> Result<Self::Connection, Self::Error> {
             Ok(MockConnection(0))
@@ -131,7 +306,7 @@ pub mod tests {
     impl DatabaseBackend for MockBackend {
         type Connection = MockConnection;
         type Pool = MockPool;
-        type Error = String;
+        type Error = MockError;
 
         async fn new(_config: DatabaseConfig) -> Result<Self, Self::Error> {
             Ok(MockBackend)
@@ -173,22 +348,109 @@ pub mod tests {
         }
     }
 
-    // Mock implementation of TransactionManager for TestDatabaseInstance with MockBackend
     #[async_trait]
-    impl DBTransactionManager<MockTransaction, MockConnection> for TestDatabaseInstance<MockBackend> {
-        type Error = String;
-        type Tx = MockTransaction;
+    impl TestConnection for MockConnection {
+        type Error = MockError;
+
+        fn dialect(&self) -> SqlDialect {
+            SqlDialect::Postgres
+        }
 
-        async fn begin_transaction(&mut self) -> Result<Self::Tx, Self::Error> {
-            Ok(MockTransaction::default())
+        async fn execute(&self, _sql: &str, _params: &[Value]) -> Result<u64, Self::Error> {
+            Ok(0)
         }
 
-        async fn commit_transaction(tx: &mut Self::Tx) -> Result<(), Self::Error> {
-            tx.commit().await
+        async fn query(&self, _sql: &str, _params: &[Value]) -> Result<Vec<Row>, Self::Error> {
+            Ok(Vec::new())
         }
 
-        async fn rollback_transaction(tx: &mut Self::Tx) -> Result<(), Self::Error> {
-            tx.rollback().await
+        async fn batch_execute(&self, _sql: &str) -> Result<(), Self::Error> {
+            Ok(())
         }
     }
+
+    #[async_trait]
+    impl TransactionalConnection for MockConnection {
+        type Transaction = MockTransaction;
+
+        async fn begin_transaction_with(
+            &self,
+            options: TxOptions,
+        ) -> Result<Self::Transaction, Self::Error> {
+            Ok(MockTransaction {
+                options,
+                ..MockTransaction::default()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transaction_manager() {
+        let config = DatabaseConfig::new("mock://admin", "mock://user");
+        let db = TestDatabaseInstance::new(MockBackend, config)
+            .await
+            .unwrap();
+
+        let mut tx = db.begin_transaction().await.unwrap();
+        tx.execute("INSERT INTO users VALUES (1)", &[])
+            .await
+            .unwrap();
+        TestDatabaseInstance::<MockBackend>::commit_transaction(&mut tx)
+            .await
+            .unwrap();
+        assert!(tx.is_committed());
+
+        // A finished transaction can't be used or finished again
+        assert!(tx.execute("SELECT 1", &[]).await.is_err());
+        assert!(tx.rollback().await.is_err());
+        assert!(!tx.is_rolled_back());
+    }
+
+    #[tokio::test]
+    async fn test_transaction_options() {
+        let config = DatabaseConfig::new("mock://admin", "mock://user");
+        let db = TestDatabaseInstance::new(MockBackend, config)
+            .await
+            .unwrap();
+
+        let tx = db.begin_transaction().await.unwrap();
+        assert_eq!(tx.options(), &TxOptions::default());
+
+        let options = TxOptions {
+            isolation: Some(IsolationLevel::Serializable),
+            read_only: true,
+            deferrable: true,
+        };
+        let tx = db.begin_transaction_with(options).await.unwrap();
+        assert_eq!(tx.options().isolation, Some(IsolationLevel::Serializable));
+        assert!(tx.options().read_only);
+    }
+
+    #[test]
+    fn test_set_transaction_sql() {
+        assert_eq!(
+            TxOptions::default().set_transaction_sql(SqlDialect::Postgres),
+            None
+        );
+
+        let options = TxOptions {
+            isolation: Some(IsolationLevel::RepeatableRead),
+            read_only: true,
+            deferrable: true,
+        };
+        assert_eq!(
+            options.set_transaction_sql(SqlDialect::Postgres).unwrap(),
+            "SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY, DEFERRABLE"
+        );
+        assert_eq!(
+            options.set_transaction_sql(SqlDialect::MySql).unwrap(),
+            "SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY"
+        );
+        assert_eq!(
+            TxOptions::with_isolation(IsolationLevel::ReadCommitted)
+                .set_transaction_sql(SqlDialect::MySql)
+                .unwrap(),
+            "SET TRANSACTION ISOLATION LEVEL READ COMMITTED"
+        );
+    }
 }
@@ -0,0 +1,328 @@
+use std::fmt::Display;
+use std::sync::Arc;
+
+/// A backend-neutral SQL value used for query parameters and results
+///
+/// Backends map their native types onto the closest variant. Types without a
+/// natural counterpart, such as decimals, dates and UUIDs, are returned as text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// SQL NULL
+    Null,
+    /// A boolean
+    Bool(bool),
+    /// Any integer type
+    Int(i64),
+    /// Any floating point type
+    Float(f64),
+    /// Strings and values without a dedicated variant, in their text form
+    Text(String),
+    /// Binary data
+    Bytes(Vec<u8>),
+}
+
+impl Value {
+    /// Returns true if the value is NULL
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// Returns the name of the variant, used in conversion errors
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Value::Null => "NULL",
+            Value::Bool(_) => "bool",
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::Text(_) => "text",
+            Value::Bytes(_) => "bytes",
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Null => write!(f, "NULL"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::Text(s) => write!(f, "{}", s),
+            Value::Bytes(bytes) => {
+                write!(f, "\\x")?;
+                bytes.iter().try_for_each(|b| write!(f, "{:02x}", b))
+            }
+        }
+    }
+}
+
+macro_rules! value_from {
+    ($variant:ident: $($ty:ty),+) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(value: $ty) -> Self {
+                    Value::$variant(value.into())
+                }
+            }
+        )+
+    };
+}
+
+value_from!(Bool: bool);
+value_from!(Int: i8, i16, i32, i64, u8, u16, u32);
+value_from!(Float: f32, f64);
+value_from!(Text: String, &str);
+value_from!(Bytes: Vec<u8>, &[u8]);
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+/// Error returned when a [`Value`] can't be converted or a column doesn't exist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueError(String);
+
+impl ValueError {
+    /// Create a new error with the given message
+    pub fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+impl Display for ValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ValueError {}
+
+/// Conversion from a [`Value`] into a Rust type
+pub trait FromValue: Sized {
+    /// Convert the value, failing if it has an incompatible type
+    fn from_value(value: &Value) -> Result<Self, ValueError>;
+}
+
+fn mismatch<T>(value: &Value, target: &str) -> Result<T, ValueError> {
+    Err(ValueError::new(format!(
+        "can't convert {} value {} into {}",
+        value.kind(),
+        value,
+        target
+    )))
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Self, ValueError> {
+        Ok(value.clone())
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<Self, ValueError> {
+        match value {
+            Value::Bool(b) => Ok(*b),
+            // MySQL stores booleans as TINYINT(1)
+            Value::Int(0) => Ok(false),
+            Value::Int(1) => Ok(true),
+            _ => mismatch(value, "bool"),
+        }
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Result<Self, ValueError> {
+        match value {
+            Value::Int(i) => Ok(*i),
+            Value::Text(s) => s.parse().or_else(|_| mismatch(value, "i64")),
+            _ => mismatch(value, "i64"),
+        }
+    }
+}
+
+macro_rules! from_value_int {
+    ($($ty:ty),+) => {
+        $(
+            impl FromValue for $ty {
+                fn from_value(value: &Value) -> Result<Self, ValueError> {
+                    let int = i64::from_value(value)?;
+                    <$ty>::try_from(int).or_else(|_| mismatch(value, stringify!($ty)))
+                }
+            }
+        )+
+    };
+}
+
+from_value_int!(i8, i16, i32, u8, u16, u32, u64, usize);
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<Self, ValueError> {
+        match value {
+            Value::Float(x) => Ok(*x),
+            Value::Int(i) => Ok(*i as f64),
+            Value::Text(s) => s.parse().or_else(|_| mismatch(value, "f64")),
+            _ => mismatch(value, "f64"),
+        }
+    }
+}
+
+impl FromValue for f32 {
+    fn from_value(value: &Value) -> Result<Self, ValueError> {
+        f64::from_value(value).map(|x| x as f32)
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<Self, ValueError> {
+        match value {
+            Value::Text(s) => Ok(s.clone()),
+            Value::Bytes(bytes) => {
+                String::from_utf8(bytes.clone()).or_else(|_| mismatch(value, "String"))
+            }
+            _ => mismatch(value, "String"),
+        }
+    }
+}
+
+impl FromValue for Vec<u8> {
+    fn from_value(value: &Value) -> Result<Self, ValueError> {
+        match value {
+            Value::Bytes(bytes) => Ok(bytes.clone()),
+            Value::Text(s) => Ok(s.as_bytes().to_vec()),
+            _ => mismatch(value, "Vec<u8>"),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Self, ValueError> {
+        match value {
+            Value::Null => Ok(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+/// A column position or name used to look up a value in a [`Row`]
+pub trait ColumnIndex {
+    /// Returns the position of the column in the row
+    fn index(&self, columns: &[String]) -> Option<usize>;
+}
+
+impl ColumnIndex for usize {
+    fn index(&self, columns: &[String]) -> Option<usize> {
+        (*self < columns.len()).then_some(*self)
+    }
+}
+
+impl ColumnIndex for &str {
+    fn index(&self, columns: &[String]) -> Option<usize> {
+        columns.iter().position(|column| column == self)
+    }
+}
+
+/// A row returned by [`TestConnection::query`](crate::TestConnection::query)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    columns: Arc<[String]>,
+    values: Vec<Value>,
+}
+
+impl Row {
+    /// Create a row from column names shared between the rows of a result and its values
+    pub fn new(columns: Arc<[String]>, values: Vec<Value>) -> Self {
+        debug_assert_eq!(columns.len(), values.len());
+        Self { columns, values }
+    }
+
+    /// Returns the column names
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Returns the values in column order
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    /// Returns the number of columns
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if the row has no columns
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns the value of a column, or `None` if there is no such column
+    pub fn value(&self, column: impl ColumnIndex) -> Option<&Value> {
+        column.index(&self.columns).map(|i| &self.values[i])
+    }
+
+    /// Returns the value of a column converted into `T`
+    pub fn try_get<T: FromValue>(
+        &self,
+        column: impl ColumnIndex + Display,
+    ) -> Result<T, ValueError> {
+        let value = self
+            .value(&column)
+            .ok_or_else(|| ValueError::new(format!("no column {} in row", column)))?;
+        T::from_value(value).map_err(|e| ValueError::new(format!("column {}: {}", column, e)))
+    }
+
+    /// Returns the value of a column converted into `T`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the column doesn't exist or the value can't be converted.
+    pub fn get<T: FromValue>(&self, column: impl ColumnIndex + Display) -> T {
+        self.try_get(column).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+impl<C: ColumnIndex> ColumnIndex for &C {
+    fn index(&self, columns: &[String]) -> Option<usize> {
+        (*self).index(columns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row() -> Row {
+        let columns: Arc<[String]> =
+            vec!["id".to_string(), "name".to_string(), "score".to_string()].into();
+        Row::new(
+            columns,
+            vec![Value::Int(7), Value::from("Alice"), Value::Null],
+        )
+    }
+
+    #[test]
+    fn test_row_lookup() {
+        let row = row();
+
+        assert_eq!(row.get::<i64>("id"), 7);
+        assert_eq!(row.get::<i32>(0), 7);
+        assert_eq!(row.get::<String>("name"), "Alice");
+        assert_eq!(row.get::<Option<f64>>("score"), None);
+        assert!(row.value("missing").is_none());
+        assert!(row.try_get::<i64>("name").is_err());
+        assert!(row.try_get::<i64>(3).is_err());
+    }
+
+    #[test]
+    fn test_value_conversions() {
+        assert_eq!(Value::from(Some(3_i32)), Value::Int(3));
+        assert_eq!(Value::from(None::<&str>), Value::Null);
+        assert_eq!(bool::from_value(&Value::Int(1)), Ok(true));
+        assert_eq!(i64::from_value(&Value::Text("42".into())), Ok(42));
+        assert_eq!(f64::from_value(&Value::Text("1.50".into())), Ok(1.5));
+        assert!(u8::from_value(&Value::Int(300)).is_err());
+        assert_eq!(Value::Bytes(vec![0xde, 0xad]).to_string(), "\\xdead");
+    }
+}
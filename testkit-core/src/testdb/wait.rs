@@ -0,0 +1,84 @@
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+use super::{Row, TestConnection, render_rows};
+
+/// Error returned by [`wait_until`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WaitError {
+    /// The predicate didn't hold before the timeout
+    Timeout {
+        sql: String,
+        timeout: Duration,
+        /// The rows of the last query, rendered as a table
+        last: String,
+    },
+    /// The query failed
+    Query { sql: String, message: String },
+}
+
+impl Display for WaitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WaitError::Timeout { sql, timeout, last } => write!(
+                f,
+                "condition not met after {:?}\nquery: {}\nlast result:\n{}",
+                timeout, sql, last
+            ),
+            WaitError::Query { sql, message } => {
+                write!(f, "failed to poll {}: {}", sql, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WaitError {}
+
+/// Poll a query until a predicate over its rows holds
+///
+/// Runs the query every `interval` and returns the rows that satisfied the predicate.
+/// After `timeout` it fails with the last rows the query returned. Meant for state
+/// written by background workers, instead of sleeping for a fixed time.
+///
+/// ```rust,ignore
+/// let rows = wait_until(
+///     &conn,
+///     "SELECT status FROM jobs WHERE id = 1",
+///     |rows| rows.first().is_some_and(|r| r.try_get::<String>(0).is_ok_and(|s| s == "done")),
+///     Duration::from_secs(5),
+///     Duration::from_millis(50),
+/// )
+/// .await?;
+/// ```
+pub async fn wait_until<C, P>(
+    conn: &C,
+    sql: &str,
+    predicate: P,
+    timeout: Duration,
+    interval: Duration,
+) -> Result<Vec<Row>, WaitError>
+where
+    C: TestConnection,
+    P: Fn(&[Row]) -> bool,
+{
+    let started = Instant::now();
+    loop {
+        let rows = conn.query(sql, &[]).await.map_err(|e| WaitError::Query {
+            sql: sql.to_string(),
+            message: e.to_string(),
+        })?;
+        if predicate(&rows) {
+            return Ok(rows);
+        }
+
+        let elapsed = started.elapsed();
+        if elapsed >= timeout {
+            return Err(WaitError::Timeout {
+                sql: sql.to_string(),
+                timeout,
+                last: render_rows(&rows),
+            });
+        }
+        tokio::time::sleep(interval.min(timeout - elapsed)).await;
+    }
+}
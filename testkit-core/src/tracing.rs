@@ -7,9 +7,9 @@ pub use ::tracing::*;
 /// Only initializes if RUST_ENV is set to "DEBUG"
 pub fn init_tracing() {
     // Only initialize tracing if RUST_ENV is set to "DEBUG"
-    if let Ok(env) = std::env::var("RUST_ENV") {
-        if env.to_lowercase() == "debug" {
-            let _ = ::tracing_subscriber::fmt::try_init();
-        }
+    if let Ok(env) = std::env::var("RUST_ENV")
+        && env.to_lowercase() == "debug"
+    {
+        let _ = ::tracing_subscriber::fmt::try_init();
     }
 }
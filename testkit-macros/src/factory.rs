@@ -0,0 +1,274 @@
+// Expansion of #[derive(Factory)]
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    Data, DeriveInput, Expr, Fields, Ident, LitStr, Path, Type, meta::ParseNestedMeta,
+    spanned::Spanned,
+};
+
+/// Arguments of the `#[factory(...)]` attribute on the struct
+#[derive(Default)]
+struct StructArgs {
+    table: Option<LitStr>,
+    krate: Option<Path>,
+}
+
+impl StructArgs {
+    fn parse(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("table") {
+            self.table = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("crate") {
+            let path: LitStr = meta.value()?.parse()?;
+            self.krate = Some(path.parse()?);
+        } else {
+            return Err(meta.error("unsupported argument, expected `table` or `crate`"));
+        }
+
+        Ok(())
+    }
+}
+
+/// How the default value of a field is built
+enum Init {
+    Default,
+    Sequence,
+    SequenceFormat(LitStr),
+    Expr(Expr),
+}
+
+/// A field and the arguments of its `#[factory(...)]` attribute
+struct Field {
+    ident: Ident,
+    ty: Type,
+    column: String,
+    init: Init,
+    primary_key: bool,
+    generated: bool,
+    skip: bool,
+    association: Option<Type>,
+}
+
+impl Field {
+    fn new(field: &syn::Field) -> syn::Result<Self> {
+        let ident = field
+            .ident
+            .clone()
+            .ok_or_else(|| syn::Error::new(field.span(), "expected a named field"))?;
+        let mut parsed = Field {
+            column: ident.to_string(),
+            ident,
+            ty: field.ty.clone(),
+            init: Init::Default,
+            primary_key: false,
+            generated: false,
+            skip: false,
+            association: None,
+        };
+
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("factory")) {
+            attr.parse_nested_meta(|meta| parsed.parse(meta))?;
+        }
+
+        Ok(parsed)
+    }
+
+    fn parse(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("primary_key") {
+            self.primary_key = true;
+        } else if meta.path.is_ident("generated") {
+            self.generated = true;
+        } else if meta.path.is_ident("skip") {
+            self.skip = true;
+        } else if meta.path.is_ident("column") {
+            self.column = meta.value()?.parse::<LitStr>()?.value();
+        } else if meta.path.is_ident("sequence") {
+            self.init = if meta.input.peek(syn::Token![=]) {
+                Init::SequenceFormat(meta.value()?.parse()?)
+            } else {
+                Init::Sequence
+            };
+        } else if meta.path.is_ident("default") {
+            self.init = Init::Expr(meta.value()?.parse()?);
+        } else if meta.path.is_ident("association") {
+            self.association = Some(meta.value()?.parse()?);
+        } else {
+            return Err(meta.error(
+                "unsupported argument, expected one of `primary_key`, `generated`, `skip`, `column`, `sequence`, `default` or `association`",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the expression building the default value of the field
+    fn init(&self) -> TokenStream {
+        match &self.init {
+            Init::Default => quote!(::std::default::Default::default()),
+            Init::Sequence => quote!(n as _),
+            Init::SequenceFormat(format) => quote!(::std::format!(#format, n = n).into()),
+            Init::Expr(expr) => quote!(::std::convert::Into::into(#expr)),
+        }
+    }
+}
+
+/// Expand `#[derive(Factory)]` into an implementation of `Model`
+pub(crate) fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let mut args = StructArgs::default();
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("factory")) {
+        attr.parse_nested_meta(|meta| args.parse(meta))?;
+    }
+    let krate = args
+        .krate
+        .as_ref()
+        .map(|path| quote!(#path))
+        .unwrap_or_else(|| quote!(::testkit_core));
+
+    let name = &input.ident;
+    let table = args
+        .table
+        .map(|table| table.value())
+        .unwrap_or_else(|| format!("{}s", snake_case(&name.to_string())));
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields
+                .named
+                .iter()
+                .map(Field::new)
+                .collect::<syn::Result<Vec<_>>>()?,
+            _ => {
+                return Err(syn::Error::new(
+                    input.span(),
+                    "#[derive(Factory)] requires a struct with named fields",
+                ));
+            }
+        },
+        _ => {
+            return Err(syn::Error::new(
+                input.span(),
+                "#[derive(Factory)] can only be used on structs",
+            ));
+        }
+    };
+
+    let primary_key = fields
+        .iter()
+        .find(|f| f.primary_key)
+        .or_else(|| fields.iter().find(|f| !f.skip && f.ident == "id"))
+        .ok_or_else(|| {
+            syn::Error::new(
+                input.span(),
+                "no primary key, name a field `id` or mark it with #[factory(primary_key)]",
+            )
+        })?;
+    let key_column = &primary_key.column;
+    let key_field = &primary_key.ident;
+
+    let inits = fields.iter().map(|f| {
+        let ident = &f.ident;
+        let init = f.init();
+        quote!(#ident: #init)
+    });
+
+    let values = fields.iter().filter(|f| !f.skip && !f.generated).map(|f| {
+        let ident = &f.ident;
+        let column = &f.column;
+        quote! {
+            (
+                ::std::string::String::from(#column),
+                #krate::Value::from(::std::clone::Clone::clone(&self.#ident)),
+            )
+        }
+    });
+
+    let reads = fields.iter().map(|f| {
+        let ident = &f.ident;
+        let column = &f.column;
+        if f.skip {
+            quote!(#ident: ::std::default::Default::default())
+        } else {
+            quote!(#ident: row.try_get(#column)?)
+        }
+    });
+
+    let associations: Vec<TokenStream> = fields
+        .iter()
+        .filter_map(|f| {
+            let model = f.association.as_ref()?;
+            let ident = &f.ident;
+            let ty = &f.ty;
+            Some(quote! {
+                if self.#ident == <#ty as ::std::default::Default>::default() {
+                    let parent = #krate::Factory::<#model>::new().create(conn).await?;
+                    let key = #krate::Model::primary_key(&parent);
+                    self.#ident = #krate::FromValue::from_value(&key).map_err(|e| {
+                        #krate::FactoryError::Decode {
+                            table: ::std::string::String::from(#table),
+                            message: ::std::string::ToString::to_string(&e),
+                        }
+                    })?;
+                }
+            })
+        })
+        .collect();
+
+    let associate = if associations.is_empty() {
+        quote!()
+    } else {
+        quote! {
+            fn associate<'a, C>(&'a mut self, conn: &'a C) -> #krate::AssociateFuture<'a>
+            where
+                C: #krate::TestConnection + #krate::TestDatabaseConnection,
+            {
+                ::std::boxed::Box::pin(async move {
+                    #(#associations)*
+                    ::std::result::Result::Ok(())
+                })
+            }
+        }
+    };
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #krate::Model for #name #ty_generics #where_clause {
+            const TABLE: &'static str = #table;
+            const PRIMARY_KEY: &'static str = #key_column;
+
+            fn build(n: u64) -> Self {
+                let _ = n;
+                Self { #(#inits,)* }
+            }
+
+            fn values(&self) -> ::std::vec::Vec<(::std::string::String, #krate::Value)> {
+                ::std::vec![#(#values),*]
+            }
+
+            fn from_row(row: &#krate::Row) -> ::std::result::Result<Self, #krate::ValueError> {
+                ::std::result::Result::Ok(Self { #(#reads,)* })
+            }
+
+            fn primary_key(&self) -> #krate::Value {
+                #krate::Value::from(::std::clone::Clone::clone(&self.#key_field))
+            }
+
+            #associate
+        }
+    })
+}
+
+/// Convert a type name such as `OrderItem` to `order_item`
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
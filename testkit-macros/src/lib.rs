@@ -0,0 +1,151 @@
+//! Procedural macros for testkit
+//!
+//! These macros are re-exported by `testkit-core` as `testkit_core::macros`, so most
+//! users never depend on this crate directly.
+
+use proc_macro::TokenStream;
+use syn::{DeriveInput, ItemFn, parse_macro_input};
+
+mod factory;
+mod matrix;
+mod test_attr;
+
+/// Turn an async function taking a `TestContext` into a database test
+///
+/// The generated test creates the backend and a fresh database from the default
+/// configuration, applies the optional migrations, runs the optional setup function,
+/// loads the optional fixtures, passes the context to the test body and reports on the
+/// database when the test fails.
+///
+/// # Arguments
+///
+/// - `backend = "postgres"` - the backend to use when the context type doesn't name
+///   one (`postgres`, `sqlx-postgres`, `mysql`, `sqlx-mysql`, or `any` for
+///   `testkit_any::AnyBackend`)
+/// - `migrations = "migrations"` - a directory of SQL migrations, relative to the crate
+///   root, applied to the database before the setup function
+/// - `setup = path::to::setup` - an async function taking `&mut` connection that runs
+///   before the test body
+/// - `fixtures = ["tests/fixtures/users.yaml"]` - fixture files, relative to the crate
+///   root, loaded after the setup function, see `testkit_core::Fixtures`
+/// - `keep_on_failure` - keep the database of a failed test, overriding
+///   `TESTKIT_KEEP_ON_FAILURE`
+/// - `crate = "path"` - the path to testkit-core when it is renamed or re-exported
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use testkit_core::TestContext;
+/// use testkit_core::macros as testkit;
+/// use testkit_postgres::{PostgresBackend, PostgresConnection, PostgresError};
+///
+/// async fn create_users(conn: &mut PostgresConnection) -> Result<(), PostgresError> {
+///     conn.client().execute("CREATE TABLE users (id SERIAL)", &[]).await?;
+///     Ok(())
+/// }
+///
+/// #[testkit::test(setup = create_users)]
+/// async fn inserts_a_user(ctx: TestContext<PostgresBackend>) -> Result<(), PostgresError> {
+///     let conn = ctx.db.acquire_connection().await?;
+///     conn.client().execute("INSERT INTO users DEFAULT VALUES", &[]).await?;
+///     Ok(())
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn test(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut test_args = test_attr::TestArgs::default();
+    let parser = syn::meta::parser(|meta| test_args.parse(meta));
+    parse_macro_input!(args with parser);
+
+    let func = parse_macro_input!(item as ItemFn);
+
+    test_attr::expand(test_args, func)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Run an async test function against every enabled backend
+///
+/// The function is expanded into a module with one `#[tokio::test]` per backend
+/// (`tokio_postgres`, `sqlx_postgres`, `mysql_async` and `sqlx_mysql`) whose crate is a
+/// dependency and whose cargo feature is enabled. The context argument is written as
+/// `TestContext` and the body is compiled once per backend, so it should stick to
+/// backend-agnostic APIs such as `TestConnection`.
+///
+/// Each test connects to the server configured for its dialect with
+/// `POSTGRES_DATABASE_URL` or `MYSQL_DATABASE_URL`, falling back to `DATABASE_URL`
+/// when it points to a server of that dialect. Tests for servers that aren't
+/// configured or can't be reached are skipped and pass.
+///
+/// # Arguments
+///
+/// Takes the same `migrations`, `setup`, `fixtures`, `keep_on_failure` and `crate`
+/// arguments as [`test`](macro@test). The setup function is called with every connection
+/// type, so it is usually generic over `TestConnection`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use testkit_core::{TestConnection, TestContext};
+/// use testkit_core::macros as testkit;
+///
+/// async fn create_users<C: TestConnection>(conn: &mut C) -> Result<(), C::Error> {
+///     conn.batch_execute("CREATE TABLE users (id INT PRIMARY KEY)").await
+/// }
+///
+/// #[testkit::matrix(setup = create_users)]
+/// async fn inserts_a_user(ctx: TestContext) -> Result<(), Box<dyn std::error::Error>> {
+///     let conn = ctx.db.acquire_connection().await?;
+///     conn.batch_execute("INSERT INTO users VALUES (1)").await?;
+///     Ok(())
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn matrix(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut test_args = test_attr::TestArgs::default();
+    let parser = syn::meta::parser(|meta| test_args.parse(meta));
+    parse_macro_input!(args with parser);
+
+    let func = parse_macro_input!(item as ItemFn);
+
+    matrix::expand(test_args, func)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `Model` for a struct stored as a row of a table, so it can be created with a
+/// `Factory`
+///
+/// Fields map to the columns of the same name. The struct attribute takes
+/// `table = "name"`, defaulting to the snake case name of the struct with an `s`
+/// appended, and `crate = "path"`. Field attributes are documented on
+/// `testkit_core::Model`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use testkit_core::Factory;
+/// use testkit_core::macros as testkit;
+///
+/// #[derive(Debug, Clone, testkit::Factory)]
+/// struct User {
+///     #[factory(generated)]
+///     id: i32,
+///     #[factory(sequence = "user{n}@example.com")]
+///     email: String,
+///     admin: bool,
+/// }
+///
+/// let admins = Factory::<User>::new()
+///     .with(|u| u.admin = true)
+///     .create_many(&conn, 10)
+///     .await?;
+/// ```
+#[proc_macro_derive(Factory, attributes(factory))]
+pub fn derive_factory(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+
+    factory::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
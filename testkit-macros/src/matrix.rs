@@ -0,0 +1,128 @@
+// Expansion of the #[matrix] attribute
+use proc_macro_crate::{FoundCrate, crate_name};
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{Ident, ItemFn, spanned::Spanned};
+
+use crate::test_attr::{ContextSource, TestArgs, context_arg, context_backend, expand_test};
+
+/// A backend the matrix can run a test against
+struct MatrixBackend {
+    /// Name of the generated test function
+    test: &'static str,
+    /// Package providing the backend
+    package: &'static str,
+    /// Macro of the package that expands its input only when the backend is enabled
+    gate: &'static str,
+    /// Backend type exported by the package
+    backend: &'static str,
+    /// Variant of `SqlDialect` spoken by the backend
+    dialect: &'static str,
+}
+
+const BACKENDS: &[MatrixBackend] = &[
+    MatrixBackend {
+        test: "tokio_postgres",
+        package: "testkit-postgres",
+        gate: "__testkit_if_tokio_postgres",
+        backend: "PostgresBackend",
+        dialect: "Postgres",
+    },
+    MatrixBackend {
+        test: "sqlx_postgres",
+        package: "testkit-postgres",
+        gate: "__testkit_if_sqlx_postgres",
+        backend: "SqlxPostgresBackend",
+        dialect: "Postgres",
+    },
+    MatrixBackend {
+        test: "mysql_async",
+        package: "testkit-mysql",
+        gate: "__testkit_if_mysql_async",
+        backend: "MySqlBackend",
+        dialect: "MySql",
+    },
+    MatrixBackend {
+        test: "sqlx_mysql",
+        package: "testkit-mysql",
+        gate: "__testkit_if_sqlx_mysql",
+        backend: "SqlxMySqlBackend",
+        dialect: "MySql",
+    },
+];
+
+/// Find the path of a backend package among the dependencies of the crate being compiled
+fn package_path(package: &str) -> Option<TokenStream> {
+    match crate_name(package).ok()? {
+        FoundCrate::Itself => Some(quote!(crate)),
+        FoundCrate::Name(name) => {
+            let ident = Ident::new(&name, Span::call_site());
+            Some(quote!(::#ident))
+        }
+    }
+}
+
+/// Expand the attributed function into a module with one `#[tokio::test]` per backend
+pub(crate) fn expand(args: TestArgs, func: ItemFn) -> syn::Result<TokenStream> {
+    if let Some(backend) = &args.backend {
+        return Err(syn::Error::new(
+            backend.span(),
+            "#[testkit::matrix] runs against every enabled backend, use #[testkit::test] to pick one",
+        ));
+    }
+
+    let arg = context_arg(&func, "matrix")?;
+
+    if context_backend(&arg.ty)?.is_some() {
+        return Err(syn::Error::new(
+            arg.ty.span(),
+            "#[testkit::matrix] picks the backend, use `TestContext` or `TestContext<_>`",
+        ));
+    }
+
+    let tests: Vec<TokenStream> = BACKENDS
+        .iter()
+        .filter_map(|entry| {
+            let package = package_path(entry.package)?;
+            let gate = format_ident!("{}", entry.gate);
+            let backend_ty = format_ident!("{}", entry.backend);
+            let backend = quote!(#package::#backend_ty);
+            let dialect = format_ident!("{}", entry.dialect);
+            let name = format_ident!("{}", entry.test);
+
+            let test = expand_test(
+                &args,
+                &func,
+                arg,
+                &name,
+                &backend,
+                ContextSource::Dialect(quote!(#dialect)),
+            );
+
+            Some(quote! {
+                #package::#gate! {
+                    #test
+                }
+            })
+        })
+        .collect();
+
+    if tests.is_empty() {
+        return Err(syn::Error::new(
+            func.sig.ident.span(),
+            "#[testkit::matrix] found no backends, add testkit-postgres or testkit-mysql to the dependencies",
+        ));
+    }
+
+    let vis = &func.vis;
+    let name = &func.sig.ident;
+
+    Ok(quote! {
+        #vis mod #name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            #(#tests)*
+        }
+    })
+}
@@ -0,0 +1,294 @@
+// Expansion of the #[test] attribute
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    FnArg, GenericArgument, Ident, ItemFn, LitBool, LitStr, PatType, Path, PathArguments,
+    ReturnType, Token, Type, meta::ParseNestedMeta, punctuated::Punctuated, spanned::Spanned,
+};
+
+/// Arguments accepted by the #[test] attribute
+#[derive(Default)]
+pub(crate) struct TestArgs {
+    pub(crate) backend: Option<LitStr>,
+    setup: Option<Path>,
+    migrations: Option<LitStr>,
+    fixtures: Vec<LitStr>,
+    keep_on_failure: Option<bool>,
+    krate: Option<Path>,
+}
+
+impl TestArgs {
+    /// Parse a single `name = value` or flag argument
+    pub(crate) fn parse(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("backend") {
+            self.backend = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("setup") {
+            self.setup = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("migrations") {
+            self.migrations = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("fixtures") {
+            let value = meta.value()?;
+            let content;
+            syn::bracketed!(content in value);
+            self.fixtures = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?
+                .into_iter()
+                .collect();
+        } else if meta.path.is_ident("keep_on_failure") {
+            let keep = if meta.input.peek(syn::Token![=]) {
+                meta.value()?.parse::<LitBool>()?.value
+            } else {
+                true
+            };
+            self.keep_on_failure = Some(keep);
+        } else if meta.path.is_ident("crate") {
+            let path: LitStr = meta.value()?.parse()?;
+            self.krate = Some(path.parse()?);
+        } else {
+            return Err(meta.error(
+                "unsupported argument, expected one of `backend`, `setup`, `migrations`, `fixtures`, `keep_on_failure` or `crate`",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the path to testkit-core
+    pub(crate) fn krate(&self) -> TokenStream {
+        self.krate
+            .as_ref()
+            .map(|path| quote!(#path))
+            .unwrap_or_else(|| quote!(::testkit_core))
+    }
+}
+
+/// Map a backend name to the path of its backend type
+fn backend_path(name: &LitStr) -> syn::Result<TokenStream> {
+    let path = match name.value().as_str() {
+        "postgres" | "tokio-postgres" => quote!(::testkit_postgres::PostgresBackend),
+        "sqlx-postgres" => quote!(::testkit_postgres::SqlxPostgresBackend),
+        "mysql" | "mysql-async" => quote!(::testkit_mysql::MySqlBackend),
+        "sqlx-mysql" => quote!(::testkit_mysql::SqlxMySqlBackend),
+        "any" => quote!(::testkit_any::AnyBackend),
+        other => {
+            return Err(syn::Error::new(
+                name.span(),
+                format!(
+                    "unknown backend `{}`, expected `postgres`, `sqlx-postgres`, `mysql`, `sqlx-mysql` or `any`",
+                    other
+                ),
+            ));
+        }
+    };
+
+    Ok(path)
+}
+
+/// Find the backend type in a `TestContext<Backend>` argument type
+///
+/// Returns `None` when the context type has no generic argument or uses `_`.
+pub(crate) fn context_backend(ty: &Type) -> syn::Result<Option<&Type>> {
+    let segment = match ty {
+        Type::Path(type_path) => type_path.path.segments.last(),
+        _ => None,
+    };
+
+    let segment = match segment {
+        Some(segment) if segment.ident == "TestContext" => segment,
+        _ => {
+            return Err(syn::Error::new(
+                ty.span(),
+                "expected the test argument to be a `TestContext<Backend>`",
+            ));
+        }
+    };
+
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return Ok(None);
+    };
+
+    match args.args.first() {
+        Some(GenericArgument::Type(Type::Infer(_))) | None => Ok(None),
+        Some(GenericArgument::Type(backend)) => Ok(Some(backend)),
+        Some(other) => Err(syn::Error::new(
+            other.span(),
+            "expected a backend type such as `PostgresBackend`",
+        )),
+    }
+}
+
+/// Fill in the backend of a `TestContext` argument type that doesn't name one
+fn with_backend(ty: &Type, backend: &TokenStream) -> Type {
+    let mut ty = ty.clone();
+
+    if let Type::Path(type_path) = &mut ty
+        && let Some(segment) = type_path.path.segments.last_mut()
+    {
+        segment.arguments = PathArguments::AngleBracketed(syn::parse_quote!(<#backend>));
+    }
+
+    ty
+}
+
+/// Check the signature of an attributed test function and return its context argument
+pub(crate) fn context_arg<'a>(func: &'a ItemFn, attr: &str) -> syn::Result<&'a PatType> {
+    if func.sig.asyncness.is_none() {
+        return Err(syn::Error::new(
+            func.sig.fn_token.span(),
+            format!("#[testkit::{}] can only be used on async functions", attr),
+        ));
+    }
+
+    if func.sig.inputs.len() != 1 {
+        return Err(syn::Error::new(
+            func.sig.inputs.span(),
+            format!(
+                "#[testkit::{}] functions take a single `TestContext` argument",
+                attr
+            ),
+        ));
+    }
+
+    let Some(FnArg::Typed(arg)) = func.sig.inputs.first() else {
+        return Err(syn::Error::new(
+            func.sig.inputs.span(),
+            format!("#[testkit::{}] can't be used on methods", attr),
+        ));
+    };
+
+    Ok(arg)
+}
+
+/// How the generated test gets its context
+pub(crate) enum ContextSource {
+    /// From the default configuration
+    Default,
+    /// From the server configured for a dialect, skipping the test when there is none
+    Dialect(TokenStream),
+}
+
+/// Generate a `#[tokio::test]` named `name` that runs the body of `func` against `backend`
+pub(crate) fn expand_test(
+    args: &TestArgs,
+    func: &ItemFn,
+    arg: &PatType,
+    name: &Ident,
+    backend: &TokenStream,
+    source: ContextSource,
+) -> TokenStream {
+    let krate = args.krate();
+    let context_ty = with_backend(&arg.ty, backend);
+
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+        ..
+    } = func;
+    let output = &sig.output;
+    let outcome_ty = match output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+    let pat = &arg.pat;
+
+    let harness = format_ident!("__testkit_harness");
+    let ctx = format_ident!("__testkit_ctx");
+
+    let keep_on_failure = args
+        .keep_on_failure
+        .map(|keep| quote!(.keep_on_failure(#keep)));
+
+    let context = match source {
+        ContextSource::Default => quote! {
+            let #ctx: #krate::TestContext<#backend> = #harness.context::<#backend>().await;
+        },
+        ContextSource::Dialect(dialect) => quote! {
+            let ::core::option::Option::Some(#ctx) =
+                #harness.context_for::<#backend>(#krate::SqlDialect::#dialect).await
+            else {
+                return <#outcome_ty as #krate::SkippedOutcome>::skipped();
+            };
+        },
+    };
+
+    // Relative directories are resolved against the crate the test belongs to
+    let migrations = args.migrations.as_ref().map(|dir| {
+        quote! {
+            #harness.migrate(
+                &#ctx.db,
+                &::std::path::Path::new(::core::env!("CARGO_MANIFEST_DIR"))
+                    .join(#dir)
+                    .to_string_lossy(),
+            )
+            .await;
+        }
+    });
+
+    let setup = args.setup.as_ref().map(|setup| {
+        quote! {
+            let mut __testkit_conn = #harness.setup_connection(&#ctx.db).await;
+            #harness.expect_setup(#setup(&mut __testkit_conn).await);
+            #harness.release_connection(&#ctx.db, __testkit_conn).await;
+        }
+    });
+
+    let fixtures = (!args.fixtures.is_empty()).then(|| {
+        let files = &args.fixtures;
+        quote! {
+            #harness.load_fixtures(
+                &#ctx.db,
+                &[#(
+                    ::std::path::Path::new(::core::env!("CARGO_MANIFEST_DIR")).join(#files)
+                ),*],
+            )
+            .await;
+        }
+    });
+
+    quote! {
+        #(#attrs)*
+        #[::tokio::test]
+        #vis async fn #name() #output {
+            let #harness = #krate::TestHarness::new(
+                ::core::concat!(::core::module_path!(), "::", ::core::stringify!(#name))
+            ) #keep_on_failure;
+            #context
+            let __testkit_guard = #harness.guard(&#ctx.db);
+            #migrations
+            #setup
+            #fixtures
+            let __testkit_outcome: #outcome_ty = async move {
+                let #pat: #context_ty = #ctx;
+                #block
+            }
+            .await;
+            __testkit_guard.finish(__testkit_outcome)
+        }
+    }
+}
+
+/// Expand the attributed function into a `#[tokio::test]`
+pub(crate) fn expand(args: TestArgs, func: ItemFn) -> syn::Result<TokenStream> {
+    let arg = context_arg(&func, "test")?;
+
+    let backend = match (context_backend(&arg.ty)?, &args.backend) {
+        (Some(backend), _) => quote!(#backend),
+        (None, Some(name)) => backend_path(name)?,
+        (None, None) => {
+            return Err(syn::Error::new(
+                arg.ty.span(),
+                "the backend is unknown, use `TestContext<Backend>` or pass `backend = \"...\"`",
+            ));
+        }
+    };
+
+    Ok(expand_test(
+        &args,
+        &func,
+        arg,
+        &func.sig.ident,
+        &backend,
+        ContextSource::Default,
+    ))
+}
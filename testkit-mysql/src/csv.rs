@@ -0,0 +1,191 @@
+#![cfg(feature = "with-mysql-async")]
+use std::path::Path;
+
+use testkit_core::{CsvOptions, SqlDialect, Value};
+
+use crate::error::MySqlError;
+
+/// Build the `LOAD DATA LOCAL INFILE` statement loading a CSV file into a table
+///
+/// The file has to go through a [`NullMarkers`] on its way to the server, which turns
+/// the NULL markers into the bare word `NULL`.
+pub(crate) fn load_data_statement(
+    table: &str,
+    path: &Path,
+    columns: &[String],
+    options: &CsvOptions,
+    crlf: bool,
+) -> String {
+    let dialect = SqlDialect::MySql;
+    let text = |s: String| dialect.literal(&Value::Text(s));
+    let columns: Vec<String> = columns
+        .iter()
+        .map(|column| dialect.quote_identifier(column))
+        .collect();
+
+    format!(
+        "LOAD DATA LOCAL INFILE {} INTO TABLE {} CHARACTER SET utf8mb4 \
+         FIELDS TERMINATED BY {} OPTIONALLY ENCLOSED BY {} ESCAPED BY '' \
+         LINES TERMINATED BY {}{} ({})",
+        text(path.display().to_string()),
+        dialect.quote_identifier(table),
+        text(options.delimiter.to_string()),
+        text(options.quote.to_string()),
+        text(if crlf { "\r\n" } else { "\n" }.to_string()),
+        if options.header {
+            " IGNORE 1 LINES"
+        } else {
+            ""
+        },
+        columns.join(", ")
+    )
+}
+
+/// Rewrites the NULL markers of a CSV stream for `LOAD DATA`
+///
+/// Once a field is read, `LOAD DATA` can't tell whether it was quoted, so `""` and an
+/// empty unquoted field would both match an empty marker. It does read the bare word
+/// `NULL` as NULL, and `"NULL"` as text. Unquoted fields equal to the marker are
+/// replaced with `NULL`, and unquoted fields that happen to read `NULL` are quoted.
+/// Everything else passes through unchanged.
+pub(crate) struct NullMarkers {
+    null: Vec<u8>,
+    delimiter: u8,
+    quote: u8,
+    field: Vec<u8>,
+    quoted: bool,
+    after_cr: bool,
+    in_line: bool,
+}
+
+impl NullMarkers {
+    pub(crate) fn new(options: &CsvOptions) -> Result<Self, MySqlError> {
+        if !options.delimiter.is_ascii() || !options.quote.is_ascii() {
+            return Err(MySqlError::Generic(
+                "LOAD DATA needs an ASCII delimiter and quote".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            null: options.null.as_bytes().to_vec(),
+            delimiter: options.delimiter as u8,
+            quote: options.quote as u8,
+            field: Vec::new(),
+            quoted: false,
+            after_cr: false,
+            in_line: false,
+        })
+    }
+
+    /// Rewrite the next chunk of the file, holding back the field it ends in
+    pub(crate) fn rewrite(&mut self, chunk: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(chunk.len());
+
+        for &b in chunk {
+            let after_cr = std::mem::take(&mut self.after_cr);
+            self.in_line = !matches!(b, b'\n' | b'\r');
+            if self.quoted {
+                // A doubled quote leaves and reenters the quotes
+                self.quoted = b != self.quote;
+                self.field.push(b);
+            } else if b == self.quote {
+                self.quoted = true;
+                self.field.push(b);
+            } else if b == self.delimiter {
+                self.flush(&mut out);
+                out.push(b);
+            } else if b == b'\n' && after_cr {
+                // The field already ended at the \r of a \r\n
+                out.push(b);
+            } else if b == b'\n' || b == b'\r' {
+                self.flush(&mut out);
+                self.after_cr = b == b'\r';
+                out.push(b);
+            } else {
+                self.field.push(b);
+            }
+        }
+
+        out
+    }
+
+    /// Rewrite the last field of a file that doesn't end with a line break
+    pub(crate) fn finish(&mut self) -> Vec<u8> {
+        let mut out = Vec::new();
+        if self.in_line {
+            self.flush(&mut out);
+        }
+        out
+    }
+
+    fn flush(&mut self, out: &mut Vec<u8>) {
+        let field = std::mem::take(&mut self.field);
+        let unquoted = !field.contains(&self.quote);
+
+        if unquoted && field == self.null {
+            out.extend_from_slice(b"NULL");
+        } else if unquoted && field == b"NULL" {
+            out.push(self.quote);
+            out.extend_from_slice(b"NULL");
+            out.push(self.quote);
+        } else {
+            out.extend_from_slice(&field);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewrite(options: &CsvOptions, chunks: &[&str]) -> String {
+        let mut markers = NullMarkers::new(options).unwrap();
+        let mut out = Vec::new();
+        for chunk in chunks {
+            out.extend(markers.rewrite(chunk.as_bytes()));
+        }
+        out.extend(markers.finish());
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_null_markers() {
+        let options = CsvOptions::default();
+
+        // Only unquoted empty fields are NULL
+        assert_eq!(
+            rewrite(&options, &["id,name,email\n1,,\"\"\n2,\"a,b\",NULL\n"]),
+            "id,name,email\n1,NULL,\"\"\n2,\"a,b\",\"NULL\"\n"
+        );
+
+        // Fields and quotes split across chunks, \r\n line breaks, no final line break
+        assert_eq!(
+            rewrite(&options, &["1,\"say \"", "\"hi\"\"\",", "\r", "\n2,x,"]),
+            "1,\"say \"\"hi\"\"\",NULL\r\n2,x,NULL"
+        );
+
+        let options = CsvOptions::default().with_null("\\N");
+        assert_eq!(
+            rewrite(&options, &["1,\\N,,\"\\N\"\n"]),
+            "1,NULL,,\"\\N\"\n"
+        );
+    }
+
+    #[test]
+    fn test_load_data_statement() {
+        let options = CsvOptions::default();
+        let statement = load_data_statement(
+            "users",
+            Path::new("users.csv"),
+            &["id".to_string(), "name".to_string()],
+            &options,
+            false,
+        );
+        assert_eq!(
+            statement,
+            "LOAD DATA LOCAL INFILE 'users.csv' INTO TABLE `users` CHARACTER SET utf8mb4 \
+             FIELDS TERMINATED BY ',' OPTIONALLY ENCLOSED BY '\"' ESCAPED BY '' \
+             LINES TERMINATED BY '\n' IGNORE 1 LINES (`id`, `name`)"
+        );
+    }
+}
@@ -27,6 +27,10 @@ pub enum MySqlError {
     #[error("Transaction error: {0}")]
     TransactionError(String),
 
+    /// Error running migrations
+    #[error("Migration error: {0}")]
+    MigrationError(String),
+
     /// Generic error
     #[error("MySQL error: {0}")]
     Generic(String),
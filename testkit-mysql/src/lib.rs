@@ -1,16 +1,54 @@
+mod csv;
 mod error;
 mod mysql_async;
 mod sqlx_mysql;
+mod value;
 
 #[cfg(feature = "with-mysql-async")]
-pub use mysql_async::{MySqlBackend, MySqlConnection, MySqlPool, mysql_backend_with_config};
+pub use mysql_async::{
+    MySqlBackend, MySqlConnection, MySqlPool, MySqlTransaction, mysql_backend_with_config,
+};
 
 #[cfg(feature = "with-sqlx")]
 pub use sqlx_mysql::{
-    SqlxMySqlBackend, SqlxMySqlConnection, SqlxMySqlPool, sqlx_mysql_backend_with_config,
+    SqlxMySqlBackend, SqlxMySqlConnection, SqlxMySqlPool, SqlxMySqlTransaction,
+    sqlx_mysql_backend_with_config,
 };
 
 pub use error::MySqlError;
 
 // Re-export core types from testkit-core
-pub use testkit_core::{DatabaseBackend, DatabaseConfig, DatabaseName, DatabasePool};
+pub use testkit_core::{
+    CsvOptions, DatabaseBackend, DatabaseConfig, DatabaseName, DatabasePool, DatabaseTransaction,
+    IsolationLevel, Row, ScriptError, SqlDialect, SqlScript, TestConnection, TransactionManager,
+    TransactionalConnection, TxOptions, Value, WithMigrations,
+};
+
+// Used by `#[testkit::matrix]` so that tests only expand for the backends enabled here
+#[cfg(feature = "with-mysql-async")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __testkit_if_mysql_async {
+    ($($item:tt)*) => { $($item)* };
+}
+
+#[cfg(not(feature = "with-mysql-async"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __testkit_if_mysql_async {
+    ($($item:tt)*) => {};
+}
+
+#[cfg(feature = "with-sqlx")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __testkit_if_sqlx_mysql {
+    ($($item:tt)*) => { $($item)* };
+}
+
+#[cfg(not(feature = "with-sqlx"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __testkit_if_sqlx_mysql {
+    ($($item:tt)*) => {};
+}
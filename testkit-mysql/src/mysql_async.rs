@@ -1,100 +1,175 @@
 #![cfg(feature = "with-mysql-async")]
+use std::path::Path;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
 
-use tokio::sync::Mutex;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+use tokio::sync::{Mutex, MutexGuard};
 
-use mysql_async::{Conn, Opts, Pool, prelude::*};
+use mysql_async::consts::{ColumnFlags, ColumnType};
+use mysql_async::{Column, Conn, Opts, Pool, prelude::*};
 use testkit_core::{
-    DatabaseBackend, DatabaseConfig, DatabaseName, DatabasePool, TestDatabaseConnection,
+    CsvOptions, DatabaseBackend, DatabaseConfig, DatabaseName, DatabasePool, DatabaseTransaction,
+    Migrator, Row, SqlDialect, TestConnection, TestDatabaseConnection, TransactionalConnection,
+    TxOptions, Value, WithMigrations, record,
 };
 
+use crate::csv::{NullMarkers, load_data_statement};
 use crate::error::MySqlError;
+use crate::value::{self, ColumnKind};
+
+/// Size of the chunks a CSV file is streamed to the server in
+const CSV_CHUNK_SIZE: usize = 64 * 1024;
 
 /// A MySQL connection using mysql-async
 #[derive(Clone)]
 pub struct MySqlConnection {
     /// The connection to the database
     conn: Arc<Mutex<Conn>>,
+    /// The rollback left behind by a dropped transaction
+    rollback: Arc<PendingRollback>,
     /// The connection string used to create this connection
     connection_string: String,
 }
 
+/// The rollback of a transaction dropped without commit or rollback
+///
+/// The ROLLBACK is sent before the next statement on the connection or, once the
+/// last clone of the connection is dropped, before it goes back to the pool.
+struct PendingRollback {
+    conn: Arc<Mutex<Conn>>,
+    pending: AtomicBool,
+}
+
+impl Drop for PendingRollback {
+    fn drop(&mut self) {
+        if !self.pending.load(Ordering::Acquire) {
+            return;
+        }
+
+        // The task holds the connection, so it's only returned to the pool once the
+        // ROLLBACK has completed
+        let conn = self.conn.clone();
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move {
+                    let mut conn_guard = conn.lock().await;
+                    if let Err(e) = conn_guard.query_drop("ROLLBACK").await {
+                        tracing::warn!("Failed to roll back dropped MySQL transaction: {}", e);
+                    }
+                });
+            }
+            Err(_) => {
+                tracing::warn!(
+                    "MySQL transaction dropped outside of a runtime and could not be rolled back"
+                );
+            }
+        }
+    }
+}
+
 /// A MySQL transaction
 pub struct MySqlTransaction {
     // We need to own the connection to ensure the transaction stays alive
-    conn: Arc<Mutex<Conn>>,
+    conn: MySqlConnection,
+    // The options the transaction was started with
+    options: TxOptions,
     // Track if the transaction is completed
     completed: bool,
 }
 
 impl MySqlTransaction {
-    // Create a new transaction
-    pub(crate) fn new(conn: Arc<Mutex<Conn>>) -> Self {
-        Self {
-            conn,
-            completed: false,
-        }
-    }
-
-    /// Execute a query within this transaction
-    pub async fn execute<Q: AsRef<str>>(
+    /// Execute a parameterized query within this transaction
+    pub async fn exec_drop<Q: AsRef<str>, P: Into<mysql_async::Params> + Send>(
         &self,
         query: Q,
-        params: mysql_async::Params,
+        params: P,
     ) -> Result<(), MySqlError> {
-        let mut conn_guard = self.conn.lock().await;
-        conn_guard
-            .exec_drop(query.as_ref(), params)
-            .await
-            .map_err(|e| MySqlError::QueryExecutionError(e.to_string()))?;
-
-        Ok(())
+        self.check_active()?;
+        self.conn.exec_drop(query, params).await
     }
 
-    /// Commit the transaction
-    pub async fn commit(mut self) -> Result<(), MySqlError> {
-        // Mark the transaction as completed
-        self.completed = true;
-
-        // Commit the transaction
-        let mut conn_guard = self.conn.lock().await;
-        conn_guard
-            .exec_drop("COMMIT", ())
-            .await
-            .map_err(|e| MySqlError::TransactionError(e.to_string()))?;
-
+    fn check_active(&self) -> Result<(), MySqlError> {
+        if self.completed {
+            return Err(MySqlError::TransactionError(
+                "transaction is already finished".to_string(),
+            ));
+        }
         Ok(())
     }
 
-    /// Rollback the transaction
-    pub async fn rollback(mut self) -> Result<(), MySqlError> {
+    async fn finish(&mut self, statement: &str) -> Result<(), MySqlError> {
+        self.check_active()?;
+
         // Mark the transaction as completed
         self.completed = true;
 
-        // Rollback the transaction
-        let mut conn_guard = self.conn.lock().await;
+        let mut conn_guard = self.conn.conn.lock().await;
         conn_guard
-            .exec_drop("ROLLBACK", ())
+            .query_drop(statement)
             .await
-            .map_err(|e| MySqlError::TransactionError(e.to_string()))?;
-
-        Ok(())
+            .map_err(|e| MySqlError::TransactionError(e.to_string()))
     }
 }
 
-// Ensure transaction is rolled back if dropped without explicit commit/rollback
+// A transaction dropped without an explicit commit or rollback leaves its
+// rollback pending on the shared connection, see `PendingRollback`.
 impl Drop for MySqlTransaction {
     fn drop(&mut self) {
         if !self.completed {
-            // We need to rollback the transaction if it's not completed
-            // This is a sync function, so we can't use async/await here
-            tracing::warn!("MySQL transaction was not committed or rolled back explicitly");
+            self.conn.rollback.pending.store(true, Ordering::Release);
         }
     }
 }
 
+impl TestDatabaseConnection for MySqlTransaction {
+    fn connection_string(&self) -> String {
+        self.conn.connection_string.clone()
+    }
+}
+
+#[async_trait]
+impl TestConnection for MySqlTransaction {
+    type Error = MySqlError;
+
+    fn dialect(&self) -> SqlDialect {
+        SqlDialect::MySql
+    }
+
+    async fn execute(&self, sql: &str, params: &[Value]) -> Result<u64, Self::Error> {
+        self.check_active()?;
+        self.conn.execute(sql, params).await
+    }
+
+    async fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>, Self::Error> {
+        self.check_active()?;
+        self.conn.query(sql, params).await
+    }
+
+    async fn batch_execute(&self, sql: &str) -> Result<(), Self::Error> {
+        self.check_active()?;
+        self.conn.batch_execute(sql).await
+    }
+}
+
+#[async_trait]
+impl DatabaseTransaction for MySqlTransaction {
+    async fn commit(&mut self) -> Result<(), Self::Error> {
+        self.finish("COMMIT").await
+    }
+
+    async fn rollback(&mut self) -> Result<(), Self::Error> {
+        self.finish("ROLLBACK").await
+    }
+    fn options(&self) -> &TxOptions {
+        &self.options
+    }
+}
+
 impl MySqlConnection {
     /// Create a new connection to the database
     pub async fn connect(connection_string: String) -> Result<Self, MySqlError> {
@@ -107,10 +182,32 @@ impl MySqlConnection {
             .await
             .map_err(|e| MySqlError::ConnectionError(e.to_string()))?;
 
-        Ok(Self {
-            conn: Arc::new(Mutex::new(conn)),
+        Ok(Self::new(conn, connection_string))
+    }
+
+    /// Wrap a connection to the database
+    fn new(conn: Conn, connection_string: String) -> Self {
+        let conn = Arc::new(Mutex::new(conn));
+        Self {
+            rollback: Arc::new(PendingRollback {
+                conn: conn.clone(),
+                pending: AtomicBool::new(false),
+            }),
+            conn,
             connection_string,
-        })
+        }
+    }
+
+    /// Lock the connection, rolling back a dropped transaction first
+    async fn lock(&self) -> Result<MutexGuard<'_, Conn>, MySqlError> {
+        let mut conn_guard = self.conn.lock().await;
+        if self.rollback.pending.swap(false, Ordering::AcqRel) {
+            conn_guard
+                .query_drop("ROLLBACK")
+                .await
+                .map_err(|e| MySqlError::TransactionError(e.to_string()))?;
+        }
+        Ok(conn_guard)
     }
 
     /// Get a reference to the connection
@@ -120,7 +217,7 @@ impl MySqlConnection {
 
     /// Execute a query directly
     pub async fn query_drop<Q: AsRef<str>>(&self, query: Q) -> Result<(), MySqlError> {
-        let mut conn_guard = self.conn.lock().await;
+        let mut conn_guard = self.lock().await?;
         conn_guard
             .query_drop(query.as_ref())
             .await
@@ -133,25 +230,13 @@ impl MySqlConnection {
         query: Q,
         params: P,
     ) -> Result<(), MySqlError> {
-        let mut conn_guard = self.conn.lock().await;
+        let mut conn_guard = self.lock().await?;
         conn_guard
             .exec_drop(query.as_ref(), params)
             .await
             .map_err(|e| MySqlError::QueryExecutionError(e.to_string()))
     }
 
-    /// Start a transaction
-    pub async fn begin_transaction(&self) -> Result<MySqlTransaction, MySqlError> {
-        let mut conn_guard = self.conn.lock().await;
-        conn_guard
-            .exec_drop("BEGIN", ())
-            .await
-            .map_err(|e| MySqlError::TransactionError(e.to_string()))?;
-
-        // Create the transaction with a clone of the connection
-        Ok(MySqlTransaction::new(self.conn.clone()))
-    }
-
     /// Get the connection string
     pub fn connection_string(&self) -> &str {
         &self.connection_string
@@ -164,7 +249,7 @@ impl MySqlConnection {
         F: FnMut(mysql_async::Row) -> T + Send + 'static,
         T: Send + 'static,
     {
-        let mut conn_guard = self.conn.lock().await;
+        let mut conn_guard = self.lock().await?;
         conn_guard
             .query_map(query.as_ref(), f)
             .await
@@ -176,7 +261,7 @@ impl MySqlConnection {
         &self,
         query: Q,
     ) -> Result<T, MySqlError> {
-        let mut conn_guard = self.conn.lock().await;
+        let mut conn_guard = self.lock().await?;
         conn_guard
             .query_first(query.as_ref())
             .await
@@ -189,6 +274,85 @@ impl MySqlConnection {
         let use_stmt = format!("USE `{}`", database_name);
         self.query_drop(use_stmt).await
     }
+
+    /// Bulk load a CSV file into a table and return the number of rows loaded
+    ///
+    /// The file is streamed to the server in chunks with `LOAD DATA LOCAL INFILE`, which
+    /// the server only accepts with `local_infile = ON`. See [`CsvOptions`] for header
+    /// mapping and NULL markers.
+    pub async fn load_csv(
+        &self,
+        table: &str,
+        path: impl AsRef<Path>,
+        options: CsvOptions,
+    ) -> Result<u64, MySqlError> {
+        let path = path.as_ref();
+        let read_error = |e: std::io::Error| {
+            MySqlError::Generic(format!("failed to read {}: {}", path.display(), e))
+        };
+
+        let columns = match options.resolve_columns(path).await.map_err(read_error)? {
+            Some(columns) => columns,
+            None => self.table_columns(table).await?,
+        };
+        let mut first_line = String::new();
+        tokio::io::BufReader::new(tokio::fs::File::open(path).await.map_err(read_error)?)
+            .read_line(&mut first_line)
+            .await
+            .map_err(read_error)?;
+        let statement = load_data_statement(
+            table,
+            path,
+            &columns,
+            &options,
+            first_line.ends_with("\r\n"),
+        );
+
+        let file = tokio::fs::File::open(path).await.map_err(read_error)?;
+        let markers = NullMarkers::new(&options)?;
+        let mut conn = self.lock().await?;
+        conn.set_infile_handler(async move {
+            let chunks = futures::stream::try_unfold(Some((file, markers)), |state| async move {
+                let Some((mut file, mut markers)) = state else {
+                    return Ok(None);
+                };
+                let mut chunk = vec![0; CSV_CHUNK_SIZE];
+                let read = file.read(&mut chunk).await?;
+                if read == 0 {
+                    // The last field isn't followed by a line break
+                    let rest = markers.finish();
+                    return Ok((!rest.is_empty()).then(|| (Bytes::from(rest), None)));
+                }
+                let chunk = markers.rewrite(&chunk[..read]);
+                Ok(Some((Bytes::from(chunk), Some((file, markers)))))
+            });
+            Ok(chunks.boxed())
+        });
+
+        conn.query_drop(statement)
+            .await
+            .map_err(|e| MySqlError::QueryExecutionError(e.to_string()))?;
+        Ok(conn.affected_rows())
+    }
+
+    /// Returns the columns of a table in the current database, in order
+    async fn table_columns(&self, table: &str) -> Result<Vec<String>, MySqlError> {
+        let name = table.rsplit('.').next().unwrap_or(table);
+        let rows = TestConnection::query(
+            self,
+            "SELECT COLUMN_NAME FROM information_schema.COLUMNS
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ?
+             ORDER BY ORDINAL_POSITION",
+            &[Value::from(name)],
+        )
+        .await?;
+        rows.iter()
+            .map(|row| {
+                row.try_get(0)
+                    .map_err(|e| MySqlError::QueryExecutionError(e.to_string()))
+            })
+            .collect()
+    }
 }
 
 /// A MySQL connection pool using mysql-async
@@ -214,23 +378,21 @@ impl DatabasePool for MySqlPool {
             .map_err(|e| MySqlError::ConnectionError(e.to_string()))?;
 
         // Create the MySqlConnection
-        let mysql_conn = MySqlConnection {
-            conn: Arc::new(Mutex::new(conn)),
-            connection_string: self.connection_string.clone(),
-        };
+        let mysql_conn = MySqlConnection::new(conn, self.connection_string.clone());
 
         // Extract the database name from the connection string and select it
-        if let Some(db_name) = self.connection_string.split('/').last() {
-            if !db_name.is_empty() {
-                mysql_conn.select_database(db_name).await?;
-            }
+        if let Some(db_name) = self.connection_string.split('/').next_back()
+            && !db_name.is_empty()
+        {
+            mysql_conn.select_database(db_name).await?;
         }
 
         Ok(mysql_conn)
     }
 
     async fn release(&self, conn: Self::Connection) -> Result<(), Self::Error> {
-        let _conn_guard = conn.conn.lock().await;
+        // Roll back a dropped transaction before the connection is reused
+        let _conn_guard = conn.lock().await?;
         // Just drop the connection - the pool will handle returning it
         // Since MySQL Async connections don't have a close method with no args
         Ok(())
@@ -245,6 +407,15 @@ impl DatabasePool for MySqlPool {
 #[derive(Clone, Debug)]
 pub struct MySqlBackend {
     config: DatabaseConfig,
+    migrations: Option<Migrator>,
+}
+
+impl WithMigrations<Migrator> for MySqlBackend {
+    /// Apply the migrations to every database right after it is created
+    fn with_migrations(mut self, migrator: Migrator) -> Self {
+        self.migrations = Some(migrator);
+        self
+    }
 }
 
 #[async_trait]
@@ -261,7 +432,15 @@ impl DatabaseBackend for MySqlBackend {
             ));
         }
 
-        Ok(Self { config })
+        Ok(Self {
+            migrations: config
+                .migrations
+                .as_deref()
+                .map(Migrator::from_dir)
+                .transpose()
+                .map_err(|e| MySqlError::MigrationError(e.to_string()))?,
+            config,
+        })
     }
 
     /// Create a new connection pool for the given database
@@ -327,10 +506,7 @@ impl DatabaseBackend for MySqlBackend {
             .map_err(|e| MySqlError::ConnectionError(e.to_string()))?;
 
         // Create a MySqlConnection to use our select_database method
-        let mysql_conn = MySqlConnection {
-            conn: Arc::new(Mutex::new(pool_conn)),
-            connection_string: pool.connection_string.clone(),
-        };
+        let mysql_conn = MySqlConnection::new(pool_conn, pool.connection_string.clone());
 
         // Select the database for all future connections from this pool
         mysql_conn.select_database(db_name).await?;
@@ -338,6 +514,13 @@ impl DatabaseBackend for MySqlBackend {
         // Release the connection back to the pool
         drop(mysql_conn);
 
+        if let Some(migrator) = &self.migrations {
+            migrator
+                .run_on_pool(pool)
+                .await
+                .map_err(|e| MySqlError::MigrationError(e.to_string()))?;
+        }
+
         Ok(())
     }
 
@@ -433,52 +616,222 @@ impl TestDatabaseConnection for MySqlConnection {
     }
 }
 
-/// Trait for MySQL transaction operations
 #[async_trait]
-pub trait MysqlTransaction: Send + Sync {
-    /// Commit the transaction
-    async fn commit(self) -> Result<(), MySqlError>;
+impl TestConnection for MySqlConnection {
+    type Error = MySqlError;
 
-    /// Rollback the transaction
-    async fn rollback(self) -> Result<(), MySqlError>;
-}
+    fn dialect(&self) -> SqlDialect {
+        SqlDialect::MySql
+    }
 
-/// Generic transaction trait
-#[async_trait]
-#[allow(unused)]
-pub trait TransactionTrait: Send + Sync {
-    /// Error type
-    type Error: std::error::Error + Send + Sync;
+    async fn execute(&self, sql: &str, params: &[Value]) -> Result<u64, Self::Error> {
+        record(&self.connection_string, sql, params, |n| Some(*n), async {
+            let mut conn_guard = self.lock().await?;
+            let affected_rows = if params.is_empty() {
+                let result = conn_guard
+                    .query_iter(sql)
+                    .await
+                    .map_err(|e| MySqlError::QueryExecutionError(e.to_string()))?;
+                let affected_rows = result.affected_rows();
+                result
+                    .drop_result()
+                    .await
+                    .map_err(|e| MySqlError::QueryExecutionError(e.to_string()))?;
+                affected_rows
+            } else {
+                let result = conn_guard
+                    .exec_iter(sql, to_params(params))
+                    .await
+                    .map_err(|e| MySqlError::QueryExecutionError(e.to_string()))?;
+                let affected_rows = result.affected_rows();
+                result
+                    .drop_result()
+                    .await
+                    .map_err(|e| MySqlError::QueryExecutionError(e.to_string()))?;
+                affected_rows
+            };
+
+            Ok(affected_rows)
+        })
+        .await
+    }
 
-    /// Commit the transaction
-    async fn commit(self) -> Result<(), Self::Error>;
+    async fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>, Self::Error> {
+        let rows = |rows: &Vec<Row>| Some(rows.len() as u64);
+        record(&self.connection_string, sql, params, rows, async {
+            let mut conn_guard = self.lock().await?;
+            if params.is_empty() {
+                collect_rows(
+                    conn_guard
+                        .query_iter(sql)
+                        .await
+                        .map_err(|e| MySqlError::QueryExecutionError(e.to_string()))?,
+                )
+                .await
+            } else {
+                collect_rows(
+                    conn_guard
+                        .exec_iter(sql, to_params(params))
+                        .await
+                        .map_err(|e| MySqlError::QueryExecutionError(e.to_string()))?,
+                )
+                .await
+            }
+        })
+        .await
+    }
 
-    /// Rollback the transaction
-    async fn rollback(self) -> Result<(), Self::Error>;
+    async fn batch_execute(&self, sql: &str) -> Result<(), Self::Error> {
+        record(
+            &self.connection_string,
+            sql,
+            &[],
+            |_| None,
+            self.query_drop(sql),
+        )
+        .await
+    }
 }
 
-// Implement MysqlTransaction for MySqlTransaction
-#[async_trait]
-impl MysqlTransaction for MySqlTransaction {
-    async fn commit(self) -> Result<(), MySqlError> {
-        self.commit().await
+/// Convert neutral values into positional parameters
+fn to_params(params: &[Value]) -> mysql_async::Params {
+    mysql_async::Params::Positional(
+        params
+            .iter()
+            .map(|value| match value {
+                Value::Null => mysql_async::Value::NULL,
+                Value::Bool(b) => mysql_async::Value::Int(i64::from(*b)),
+                Value::Int(i) => mysql_async::Value::Int(*i),
+                Value::Float(x) => mysql_async::Value::Double(*x),
+                Value::Text(s) => mysql_async::Value::Bytes(s.as_bytes().to_vec()),
+                Value::Bytes(bytes) => mysql_async::Value::Bytes(bytes.clone()),
+            })
+            .collect(),
+    )
+}
+
+/// Collect the current result set into neutral rows
+async fn collect_rows<P: Protocol>(
+    mut result: mysql_async::QueryResult<'_, '_, P>,
+) -> Result<Vec<Row>, MySqlError> {
+    let columns = result.columns().unwrap_or_else(|| Vec::new().into());
+    let names: Arc<[String]> = columns
+        .iter()
+        .map(|column| column.name_str().into_owned())
+        .collect();
+
+    let rows: Vec<mysql_async::Row> = result
+        .collect()
+        .await
+        .map_err(|e| MySqlError::QueryExecutionError(e.to_string()))?;
+    result
+        .drop_result()
+        .await
+        .map_err(|e| MySqlError::QueryExecutionError(e.to_string()))?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let values = row
+                .unwrap()
+                .into_iter()
+                .zip(columns.iter())
+                .map(|(value, column)| to_value(value, column))
+                .collect();
+            Row::new(names.clone(), values)
+        })
+        .collect())
+}
+
+/// Character set number MySQL uses for binary strings
+const BINARY_CHARSET: u16 = 63;
+
+fn column_kind(column: &Column) -> ColumnKind {
+    let unsigned = column.flags().contains(ColumnFlags::UNSIGNED_FLAG);
+
+    match column.column_type() {
+        ColumnType::MYSQL_TYPE_TINY
+        | ColumnType::MYSQL_TYPE_SHORT
+        | ColumnType::MYSQL_TYPE_LONG
+        | ColumnType::MYSQL_TYPE_INT24
+        | ColumnType::MYSQL_TYPE_LONGLONG
+        | ColumnType::MYSQL_TYPE_YEAR => {
+            if unsigned {
+                ColumnKind::UInt
+            } else {
+                ColumnKind::Int
+            }
+        }
+        ColumnType::MYSQL_TYPE_FLOAT | ColumnType::MYSQL_TYPE_DOUBLE => ColumnKind::Float,
+        ColumnType::MYSQL_TYPE_BIT => ColumnKind::Binary,
+        ColumnType::MYSQL_TYPE_TINY_BLOB
+        | ColumnType::MYSQL_TYPE_MEDIUM_BLOB
+        | ColumnType::MYSQL_TYPE_LONG_BLOB
+        | ColumnType::MYSQL_TYPE_BLOB
+        | ColumnType::MYSQL_TYPE_VARCHAR
+        | ColumnType::MYSQL_TYPE_VAR_STRING
+        | ColumnType::MYSQL_TYPE_STRING
+            if column.character_set() == BINARY_CHARSET =>
+        {
+            ColumnKind::Binary
+        }
+        _ => ColumnKind::Text,
     }
+}
 
-    async fn rollback(self) -> Result<(), MySqlError> {
-        self.rollback().await
+/// Convert a value of the given column into a neutral value
+fn to_value(value: mysql_async::Value, column: &Column) -> Value {
+    match value {
+        mysql_async::Value::NULL => Value::Null,
+        mysql_async::Value::Int(i) => Value::Int(i),
+        mysql_async::Value::UInt(u) => value::from_unsigned(u),
+        mysql_async::Value::Float(x) => Value::Float(x.into()),
+        mysql_async::Value::Double(x) => Value::Float(x),
+        mysql_async::Value::Date(year, month, day, hour, minute, second, micros) => {
+            if column.column_type() == ColumnType::MYSQL_TYPE_DATE {
+                Value::Text(value::format_date(year, month, day))
+            } else {
+                Value::Text(value::format_datetime(
+                    (year, month, day),
+                    (hour, minute, second, micros),
+                ))
+            }
+        }
+        mysql_async::Value::Time(negative, days, hour, minute, second, micros) => Value::Text(
+            value::format_time(negative, days, (hour, minute, second, micros)),
+        ),
+        mysql_async::Value::Bytes(bytes) => value::from_text(column_kind(column), &bytes),
     }
 }
 
-// Implement TransactionTrait for MySqlTransaction
 #[async_trait]
-impl TransactionTrait for MySqlTransaction {
-    type Error = MySqlError;
+impl TransactionalConnection for MySqlConnection {
+    type Transaction = MySqlTransaction;
 
-    async fn commit(self) -> Result<(), Self::Error> {
-        MysqlTransaction::commit(self).await
-    }
+    /// Start a transaction on this connection and the clones sharing it
+    async fn begin_transaction_with(
+        &self,
+        options: TxOptions,
+    ) -> Result<Self::Transaction, Self::Error> {
+        let mut conn_guard = self.lock().await?;
+
+        // SET TRANSACTION applies to the next transaction started on this session
+        if let Some(set_transaction) = options.set_transaction_sql(SqlDialect::MySql) {
+            conn_guard
+                .query_drop(set_transaction)
+                .await
+                .map_err(|e| MySqlError::TransactionError(e.to_string()))?;
+        }
+        conn_guard
+            .query_drop("BEGIN")
+            .await
+            .map_err(|e| MySqlError::TransactionError(e.to_string()))?;
 
-    async fn rollback(self) -> Result<(), Self::Error> {
-        MysqlTransaction::rollback(self).await
+        // Create the transaction with a clone of the connection
+        Ok(MySqlTransaction {
+            conn: self.clone(),
+            options,
+            completed: false,
+        })
     }
 }
@@ -3,14 +3,26 @@ use std::fmt::Debug;
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use sqlx::mysql::{MySqlPoolOptions, MySqlQueryResult};
-use sqlx::{MySqlPool as SqlxPool, query};
+use sqlx::migrate::Migrator;
+use sqlx::mysql::{MySqlArguments, MySqlPoolOptions, MySqlRow, MySqlValueRef};
+use sqlx::pool::PoolConnection;
+use sqlx::query::Query;
+use sqlx::{
+    Column as _, Executor, MySql, MySqlConnection as RawConnection, MySqlPool as SqlxPool,
+    Row as _, Transaction, TypeInfo, ValueRef, query,
+};
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
 
 use testkit_core::{
-    DatabaseBackend, DatabaseConfig, DatabaseName, DatabasePool, TestDatabaseConnection,
+    DatabaseBackend, DatabaseConfig, DatabaseName, DatabasePool, DatabaseTransaction, Row,
+    ScriptError, SqlDialect, SqlScript, TestConnection, TestDatabaseConnection,
+    TransactionalConnection, TxOptions, Value, WithMigrations, record,
 };
 
 use crate::error::MySqlError;
+use crate::value::{
+    ColumnKind, format_date, format_datetime, format_time, from_text, from_unsigned,
+};
 
 /// A MySQL connection using SQLx
 #[derive(Clone)]
@@ -34,15 +46,6 @@ impl SqlxMySqlConnection {
         })
     }
 
-    /// Execute a query on the connection
-    pub async fn execute<Q: AsRef<str>>(&self, query: Q) -> Result<MySqlQueryResult, MySqlError> {
-        let query_str = query.as_ref();
-        sqlx::query(query_str)
-            .execute(&*self.pool)
-            .await
-            .map_err(|e| MySqlError::QueryExecutionError(e.to_string()))
-    }
-
     /// Get the connection string
     pub fn connection_string(&self) -> &str {
         &self.connection_string
@@ -60,6 +63,413 @@ impl TestDatabaseConnection for SqlxMySqlConnection {
     }
 }
 
+impl SqlxMySqlConnection {
+    /// Acquire a connection from the pool for a single operation
+    async fn acquire(&self) -> Result<PoolConnection<MySql>, MySqlError> {
+        self.pool
+            .acquire()
+            .await
+            .map_err(|e| MySqlError::ConnectionError(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl TestConnection for SqlxMySqlConnection {
+    type Error = MySqlError;
+
+    fn dialect(&self) -> SqlDialect {
+        SqlDialect::MySql
+    }
+
+    async fn execute(&self, sql: &str, params: &[Value]) -> Result<u64, Self::Error> {
+        let mut conn = self.acquire().await?;
+        let statement = execute_on(&mut conn, sql, params);
+        record(
+            &self.connection_string,
+            sql,
+            params,
+            |n| Some(*n),
+            statement,
+        )
+        .await
+    }
+
+    async fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>, Self::Error> {
+        let mut conn = self.acquire().await?;
+        let rows = |rows: &Vec<Row>| Some(rows.len() as u64);
+        let statement = query_on(&mut conn, sql, params);
+        record(&self.connection_string, sql, params, rows, statement).await
+    }
+
+    async fn batch_execute(&self, sql: &str) -> Result<(), Self::Error> {
+        let mut conn = self.acquire().await?;
+        let statement = batch_execute_on(&mut conn, sql);
+        record(&self.connection_string, sql, &[], |_| None, statement).await
+    }
+
+    /// Insert on a single pooled connection, so `LAST_INSERT_ID()` reads the generated key
+    async fn insert_returning(
+        &self,
+        table: &str,
+        key: &str,
+        values: &[(String, Value)],
+    ) -> Result<Option<Row>, Self::Error> {
+        let mut tx = self.begin_transaction().await?;
+        let row = tx.insert_returning(table, key, values).await?;
+        tx.commit().await?;
+        Ok(row)
+    }
+
+    /// Run the script on a single pooled connection, so `SET`s and temporary tables
+    /// carry over from one statement to the next
+    async fn execute_script<S>(&self, script: S) -> Result<(), ScriptError>
+    where
+        S: Into<SqlScript> + Send,
+    {
+        PinnedConnection::new(self).execute_script(script).await
+    }
+}
+
+/// A pooled connection held for a sequence of statements
+///
+/// The connection is acquired with the first statement and released when this is dropped.
+struct PinnedConnection<'a> {
+    owner: &'a SqlxMySqlConnection,
+    conn: Mutex<Option<PoolConnection<MySql>>>,
+}
+
+impl<'a> PinnedConnection<'a> {
+    fn new(owner: &'a SqlxMySqlConnection) -> Self {
+        Self {
+            owner,
+            conn: Mutex::new(None),
+        }
+    }
+
+    /// Lock the connection, acquiring it on first use
+    async fn lock(&self) -> Result<MappedMutexGuard<'_, PoolConnection<MySql>>, MySqlError> {
+        let mut guard = self.conn.lock().await;
+        if guard.is_none() {
+            *guard = Some(self.owner.acquire().await?);
+        }
+        Ok(MutexGuard::map(guard, |conn| {
+            conn.as_mut().expect("the connection was just acquired")
+        }))
+    }
+}
+
+#[async_trait]
+impl TestConnection for PinnedConnection<'_> {
+    type Error = MySqlError;
+
+    fn dialect(&self) -> SqlDialect {
+        SqlDialect::MySql
+    }
+
+    async fn execute(&self, sql: &str, params: &[Value]) -> Result<u64, Self::Error> {
+        let mut conn = self.lock().await?;
+        let statement = execute_on(&mut conn, sql, params);
+        record(
+            &self.owner.connection_string,
+            sql,
+            params,
+            |n| Some(*n),
+            statement,
+        )
+        .await
+    }
+
+    async fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>, Self::Error> {
+        let mut conn = self.lock().await?;
+        let rows = |rows: &Vec<Row>| Some(rows.len() as u64);
+        let statement = query_on(&mut conn, sql, params);
+        record(&self.owner.connection_string, sql, params, rows, statement).await
+    }
+
+    async fn batch_execute(&self, sql: &str) -> Result<(), Self::Error> {
+        let mut conn = self.lock().await?;
+        let statement = batch_execute_on(&mut conn, sql);
+        record(&self.owner.connection_string, sql, &[], |_| None, statement).await
+    }
+}
+
+#[async_trait]
+impl TransactionalConnection for SqlxMySqlConnection {
+    type Transaction = SqlxMySqlTransaction;
+
+    /// Begin a transaction on a dedicated connection from the pool
+    async fn begin_transaction_with(
+        &self,
+        options: TxOptions,
+    ) -> Result<Self::Transaction, Self::Error> {
+        let mut conn = self.acquire().await?;
+
+        // SET TRANSACTION applies to the next transaction started on this session
+        if let Some(set_transaction) = options.set_transaction_sql(SqlDialect::MySql) {
+            batch_execute_on(&mut conn, &set_transaction)
+                .await
+                .map_err(|e| MySqlError::TransactionError(e.to_string()))?;
+        }
+        let tx = Transaction::begin(conn)
+            .await
+            .map_err(|e| MySqlError::TransactionError(e.to_string()))?;
+
+        Ok(SqlxMySqlTransaction {
+            transaction: Mutex::new(Some(tx)),
+            options,
+            connection_string: self.connection_string.clone(),
+        })
+    }
+}
+
+/// A MySQL transaction using SQLx
+pub struct SqlxMySqlTransaction {
+    transaction: Mutex<Option<Transaction<'static, MySql>>>,
+    options: TxOptions,
+    connection_string: String,
+}
+
+impl SqlxMySqlTransaction {
+    /// Take the transaction out to finish it
+    async fn take(&self) -> Result<Transaction<'static, MySql>, MySqlError> {
+        self.transaction
+            .lock()
+            .await
+            .take()
+            .ok_or_else(finished_error)
+    }
+}
+
+fn finished_error() -> MySqlError {
+    MySqlError::TransactionError("transaction is already finished".to_string())
+}
+
+impl TestDatabaseConnection for SqlxMySqlTransaction {
+    fn connection_string(&self) -> String {
+        self.connection_string.clone()
+    }
+}
+
+#[async_trait]
+impl TestConnection for SqlxMySqlTransaction {
+    type Error = MySqlError;
+
+    fn dialect(&self) -> SqlDialect {
+        SqlDialect::MySql
+    }
+
+    async fn execute(&self, sql: &str, params: &[Value]) -> Result<u64, Self::Error> {
+        let mut guard = self.transaction.lock().await;
+        let tx = guard.as_mut().ok_or_else(finished_error)?;
+        let statement = execute_on(tx, sql, params);
+        record(
+            &self.connection_string,
+            sql,
+            params,
+            |n| Some(*n),
+            statement,
+        )
+        .await
+    }
+
+    async fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>, Self::Error> {
+        let mut guard = self.transaction.lock().await;
+        let tx = guard.as_mut().ok_or_else(finished_error)?;
+        let rows = |rows: &Vec<Row>| Some(rows.len() as u64);
+        let statement = query_on(tx, sql, params);
+        record(&self.connection_string, sql, params, rows, statement).await
+    }
+
+    async fn batch_execute(&self, sql: &str) -> Result<(), Self::Error> {
+        let mut guard = self.transaction.lock().await;
+        let tx = guard.as_mut().ok_or_else(finished_error)?;
+        let statement = batch_execute_on(tx, sql);
+        record(&self.connection_string, sql, &[], |_| None, statement).await
+    }
+}
+
+#[async_trait]
+impl DatabaseTransaction for SqlxMySqlTransaction {
+    async fn commit(&mut self) -> Result<(), Self::Error> {
+        self.take()
+            .await?
+            .commit()
+            .await
+            .map_err(|e| MySqlError::TransactionError(e.to_string()))
+    }
+
+    async fn rollback(&mut self) -> Result<(), Self::Error> {
+        self.take()
+            .await?
+            .rollback()
+            .await
+            .map_err(|e| MySqlError::TransactionError(e.to_string()))
+    }
+    fn options(&self) -> &TxOptions {
+        &self.options
+    }
+}
+
+/// Execute a statement with neutral parameters on a single connection
+async fn execute_on(
+    conn: &mut RawConnection,
+    sql: &str,
+    params: &[Value],
+) -> Result<u64, MySqlError> {
+    let result = bind_params(query(sql), params)
+        .execute(conn)
+        .await
+        .map_err(|e| MySqlError::QueryExecutionError(e.to_string()))?;
+    Ok(result.rows_affected())
+}
+
+/// Run a query with neutral parameters on a single connection
+async fn query_on(
+    conn: &mut RawConnection,
+    sql: &str,
+    params: &[Value],
+) -> Result<Vec<Row>, MySqlError> {
+    let rows: Vec<MySqlRow> = bind_params(query(sql), params)
+        .fetch_all(conn)
+        .await
+        .map_err(|e| MySqlError::QueryExecutionError(e.to_string()))?;
+
+    let Some(first) = rows.first() else {
+        return Ok(Vec::new());
+    };
+    let columns: Arc<[String]> = first
+        .columns()
+        .iter()
+        .map(|column| column.name().to_string())
+        .collect();
+
+    rows.iter()
+        .map(|row| {
+            let values = (0..columns.len())
+                .map(|i| {
+                    row.try_get_raw(i)
+                        .map_err(|e| MySqlError::QueryExecutionError(e.to_string()))
+                        .and_then(to_value)
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(Row::new(columns.clone(), values))
+        })
+        .collect()
+}
+
+/// Execute one or more statements without parameters on a single connection
+async fn batch_execute_on(conn: &mut RawConnection, sql: &str) -> Result<(), MySqlError> {
+    // A bare string runs through the text protocol, which allows several statements
+    conn.execute(sql)
+        .await
+        .map_err(|e| MySqlError::QueryExecutionError(e.to_string()))?;
+    Ok(())
+}
+
+/// Bind neutral values as positional parameters
+fn bind_params<'q>(
+    mut query: Query<'q, MySql, MySqlArguments>,
+    params: &[Value],
+) -> Query<'q, MySql, MySqlArguments> {
+    for param in params {
+        query = match param.clone() {
+            Value::Null => query.bind(None::<String>),
+            Value::Bool(b) => query.bind(b),
+            Value::Int(i) => query.bind(i),
+            Value::Float(x) => query.bind(x),
+            Value::Text(s) => query.bind(s),
+            Value::Bytes(bytes) => query.bind(bytes),
+        };
+    }
+    query
+}
+
+/// Convert a value of a row into a neutral value
+///
+/// SQLx always uses the binary protocol, so values are decoded from the raw bytes
+/// according to the column type.
+fn to_value(value: MySqlValueRef<'_>) -> Result<Value, MySqlError> {
+    if value.is_null() {
+        return Ok(Value::Null);
+    }
+
+    let type_name = value.type_info().name().to_string();
+    let bytes = <&[u8] as sqlx::Decode<MySql>>::decode(value)
+        .map_err(|e| MySqlError::QueryExecutionError(e.to_string()))?;
+    let invalid =
+        || MySqlError::QueryExecutionError(format!("invalid {} value: {:?}", type_name, bytes));
+
+    let value = match type_name.as_str() {
+        "BOOLEAN" | "TINYINT" | "SMALLINT" | "MEDIUMINT" | "INT" | "BIGINT" | "YEAR" => {
+            Value::Int(match *bytes {
+                [b] => i64::from(b as i8),
+                [a, b] => i64::from(i16::from_le_bytes([a, b])),
+                [a, b, c, d] => i64::from(i32::from_le_bytes([a, b, c, d])),
+                _ => i64::from_le_bytes(bytes.try_into().map_err(|_| invalid())?),
+            })
+        }
+        name if name.ends_with(" UNSIGNED") => from_unsigned(match *bytes {
+            [b] => u64::from(b),
+            [a, b] => u64::from(u16::from_le_bytes([a, b])),
+            [a, b, c, d] => u64::from(u32::from_le_bytes([a, b, c, d])),
+            _ => u64::from_le_bytes(bytes.try_into().map_err(|_| invalid())?),
+        }),
+        "FLOAT" => Value::Float(f64::from(f32::from_le_bytes(
+            bytes.try_into().map_err(|_| invalid())?,
+        ))),
+        "DOUBLE" => Value::Float(f64::from_le_bytes(bytes.try_into().map_err(|_| invalid())?)),
+        "DATE" | "DATETIME" | "TIMESTAMP" => Value::Text(decode_datetime(&type_name, bytes)),
+        "TIME" => Value::Text(decode_time(bytes)),
+        "BINARY" | "VARBINARY" | "BLOB" | "TINYBLOB" | "MEDIUMBLOB" | "LONGBLOB" | "BIT"
+        | "GEOMETRY" => from_text(ColumnKind::Binary, bytes),
+        _ => from_text(ColumnKind::Text, bytes),
+    };
+
+    Ok(value)
+}
+
+/// Decode a binary DATE, DATETIME or TIMESTAMP, whose first byte is its length
+fn decode_datetime(type_name: &str, bytes: &[u8]) -> String {
+    let len = bytes.first().map_or(0, |len| usize::from(*len));
+    let field = |i: usize| bytes.get(i).copied().unwrap_or(0);
+    if len == 0 {
+        return if type_name == "DATE" {
+            "0000-00-00".to_string()
+        } else {
+            "0000-00-00 00:00:00".to_string()
+        };
+    }
+
+    let date = (u16::from_le_bytes([field(1), field(2)]), field(3), field(4));
+    if type_name == "DATE" {
+        return format_date(date.0, date.1, date.2);
+    }
+
+    let micros = if len > 7 {
+        u32::from_le_bytes([field(8), field(9), field(10), field(11)])
+    } else {
+        0
+    };
+    format_datetime(date, (field(5), field(6), field(7), micros))
+}
+
+/// Decode a binary TIME, whose first byte is its length
+fn decode_time(bytes: &[u8]) -> String {
+    let len = bytes.first().map_or(0, |len| usize::from(*len));
+    let field = |i: usize| bytes.get(i).copied().unwrap_or(0);
+    if len == 0 {
+        return "00:00:00".to_string();
+    }
+
+    let days = u32::from_le_bytes([field(2), field(3), field(4), field(5)]);
+    let micros = if len > 8 {
+        u32::from_le_bytes([field(9), field(10), field(11), field(12)])
+    } else {
+        0
+    };
+    format_time(field(1) == 1, days, (field(6), field(7), field(8), micros))
+}
+
 /// A MySQL connection pool using SQLx
 #[derive(Clone)]
 pub struct SqlxMySqlPool {
@@ -95,6 +505,28 @@ impl DatabasePool for SqlxMySqlPool {
 #[derive(Clone, Debug)]
 pub struct SqlxMySqlBackend {
     config: DatabaseConfig,
+    migrator: Option<&'static Migrator>,
+    migrations: Option<testkit_core::Migrator>,
+}
+
+impl WithMigrations<&'static Migrator> for SqlxMySqlBackend {
+    /// Run the migrations on every database right after it is created
+    ///
+    /// MySQL has no template databases, so the migrations run once per database.
+    fn with_migrations(mut self, migrator: &'static Migrator) -> Self {
+        self.migrator = Some(migrator);
+        self
+    }
+}
+
+impl WithMigrations<testkit_core::Migrator> for SqlxMySqlBackend {
+    /// Apply the migrations to every database right after it is created
+    ///
+    /// They run after the sqlx migrations.
+    fn with_migrations(mut self, migrator: testkit_core::Migrator) -> Self {
+        self.migrations = Some(migrator);
+        self
+    }
 }
 
 #[async_trait]
@@ -111,7 +543,11 @@ impl DatabaseBackend for SqlxMySqlBackend {
             ));
         }
 
-        Ok(Self { config })
+        Ok(Self {
+            config,
+            migrator: None,
+            migrations: None,
+        })
     }
 
     /// Create a new connection pool for the given database
@@ -120,12 +556,11 @@ impl DatabaseBackend for SqlxMySqlBackend {
         name: &DatabaseName,
         config: &DatabaseConfig,
     ) -> Result<Self::Pool, Self::Error> {
-        // Create connection pool
+        // Create a lazy connection pool, the database doesn't exist until create_database runs
         let connection_string = self.connection_string(name);
         let pool = MySqlPoolOptions::new()
             .max_connections(config.max_connections.unwrap_or(20) as u32)
-            .connect(&connection_string)
-            .await
+            .connect_lazy(&connection_string)
             .map_err(|e| MySqlError::ConnectionError(e.to_string()))?;
 
         Ok(SqlxMySqlPool {
@@ -150,7 +585,7 @@ impl DatabaseBackend for SqlxMySqlBackend {
 
     async fn create_database(
         &self,
-        _pool: &Self::Pool,
+        pool: &Self::Pool,
         name: &DatabaseName,
     ) -> Result<(), Self::Error> {
         // Connect to the default/admin database
@@ -170,6 +605,31 @@ impl DatabaseBackend for SqlxMySqlBackend {
             .await
             .map_err(|e| MySqlError::DatabaseCreationError(e.to_string()))?;
 
+        let loaded;
+        let migrator = match (self.migrator, &self.config.migrations) {
+            (Some(migrator), _) => Some(migrator),
+            (None, Some(dir)) => {
+                loaded = Migrator::new(dir.as_path())
+                    .await
+                    .map_err(|e| MySqlError::MigrationError(e.to_string()))?;
+                Some(&loaded)
+            }
+            (None, None) => None,
+        };
+        if let Some(migrator) = migrator {
+            migrator
+                .run(&*pool.pool)
+                .await
+                .map_err(|e| MySqlError::MigrationError(e.to_string()))?;
+        }
+
+        if let Some(migrator) = &self.migrations {
+            migrator
+                .run_on_pool(pool)
+                .await
+                .map_err(|e| MySqlError::MigrationError(e.to_string()))?;
+        }
+
         Ok(())
     }
 
@@ -0,0 +1,114 @@
+//! Conversion between testkit's neutral `Value` and MySQL values
+//!
+//! Shared by the mysql-async and sqlx connections, which both describe columns by type
+//! and hand out some values as text.
+
+use testkit_core::Value;
+
+/// How the values of a column are converted into a [`Value`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "with-mysql-async"), allow(dead_code))]
+pub(crate) enum ColumnKind {
+    /// Signed integer types
+    Int,
+    /// Unsigned integer types
+    UInt,
+    /// FLOAT and DOUBLE
+    Float,
+    /// Binary strings, blobs and bits
+    Binary,
+    /// Everything else, including decimals and temporal types
+    Text,
+}
+
+/// Convert a value sent as text, which the text protocol does for every type
+pub(crate) fn from_text(kind: ColumnKind, bytes: &[u8]) -> Value {
+    let text = String::from_utf8_lossy(bytes);
+
+    match kind {
+        ColumnKind::Int => text
+            .parse()
+            .map_or_else(|_| Value::Text(text.into()), Value::Int),
+        ColumnKind::UInt => text
+            .parse()
+            .map_or_else(|_| Value::Text(text.into()), from_unsigned),
+        ColumnKind::Float => text
+            .parse()
+            .map_or_else(|_| Value::Text(text.into()), Value::Float),
+        ColumnKind::Binary => Value::Bytes(bytes.to_vec()),
+        ColumnKind::Text => Value::Text(text.into()),
+    }
+}
+
+/// Convert an unsigned integer, falling back to text above `i64::MAX`
+pub(crate) fn from_unsigned(value: u64) -> Value {
+    i64::try_from(value).map_or_else(|_| Value::Text(value.to_string()), Value::Int)
+}
+
+/// Format a DATE as `YYYY-MM-DD`
+pub(crate) fn format_date(year: u16, month: u8, day: u8) -> String {
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Format a DATETIME or TIMESTAMP as `YYYY-MM-DD HH:MM:SS[.ffffff]`
+pub(crate) fn format_datetime(
+    (year, month, day): (u16, u8, u8),
+    (hour, minute, second, micros): (u8, u8, u8, u32),
+) -> String {
+    format!(
+        "{} {}",
+        format_date(year, month, day),
+        format_clock(u32::from(hour), minute, second, micros)
+    )
+}
+
+/// Format a TIME, which may be negative and span several days, as `[-]HH:MM:SS[.ffffff]`
+pub(crate) fn format_time(
+    negative: bool,
+    days: u32,
+    (hour, minute, second, micros): (u8, u8, u8, u32),
+) -> String {
+    let sign = if negative { "-" } else { "" };
+    let hours = days * 24 + u32::from(hour);
+    format!("{}{}", sign, format_clock(hours, minute, second, micros))
+}
+
+fn format_clock(hours: u32, minute: u8, second: u8, micros: u32) -> String {
+    let mut text = format!("{:02}:{:02}:{:02}", hours, minute, second);
+    if micros > 0 {
+        text.push_str(format!(".{:06}", micros).trim_end_matches('0'));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_text() {
+        assert_eq!(from_text(ColumnKind::Int, b"-42"), Value::Int(-42));
+        assert_eq!(
+            from_text(ColumnKind::UInt, b"18446744073709551615"),
+            Value::Text("18446744073709551615".into())
+        );
+        assert_eq!(from_text(ColumnKind::Float, b"1.5"), Value::Float(1.5));
+        assert_eq!(
+            from_text(ColumnKind::Text, b"12.50"),
+            Value::Text("12.50".into())
+        );
+        assert_eq!(
+            from_text(ColumnKind::Binary, b"\x01"),
+            Value::Bytes(vec![1])
+        );
+    }
+
+    #[test]
+    fn test_format_temporal() {
+        assert_eq!(
+            format_datetime((2024, 2, 29), (13, 5, 9, 120_000)),
+            "2024-02-29 13:05:09.12"
+        );
+        assert_eq!(format_time(true, 1, (2, 0, 0, 0)), "-26:00:00");
+    }
+}
@@ -1,7 +1,11 @@
 #![cfg(feature = "with-mysql-async")]
 use mysql_async::prelude::*;
-use testkit_core::{DatabaseConfig, TestDatabaseInstance, boxed_async, with_boxed_database};
-use testkit_mysql::{DatabasePool, MySqlError, mysql_backend_with_config};
+use testkit_core::{
+    CsvOptions, DatabaseConfig, DatabaseTransaction, Explain, Introspect, IsolationLevel,
+    TestConnection, TestContext, TestDatabaseInstance, TransactionalConnection, TxOptions, Value,
+    boxed_async, with_boxed_database,
+};
+use testkit_mysql::{DatabasePool, MySqlBackend, MySqlError, mysql_backend_with_config};
 
 fn test_config() -> DatabaseConfig {
     DatabaseConfig::new(
@@ -276,3 +280,322 @@ async fn test_mysql_fluent_api() {
     // When the ctx is dropped, the transaction is automatically rolled back
     // and the database is dropped, cleanup is automatic!
 }
+
+type TestResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+/// Create a test database, or None when MySQL is unavailable
+async fn test_context() -> Option<TestContext<MySqlBackend>> {
+    let config = test_config();
+    let result = match mysql_backend_with_config(config.clone()).await {
+        Ok(backend) => TestDatabaseInstance::new(backend, config).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(db) => Some(TestContext::new(db)),
+        Err(e) if is_connection_error(&e) => {
+            println!("Skipping test: MySQL appears to be unavailable");
+            None
+        }
+        Err(e) => panic!("Failed to create test database: {:?}", e),
+    }
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_mysql_query_values() -> TestResult {
+    let Some(ctx) = test_context().await else {
+        return Ok(());
+    };
+    let conn = ctx.db.acquire_connection().await?;
+    conn.batch_execute(
+        "CREATE TABLE samples (
+            id INT PRIMARY KEY, small TINYINT, big BIGINT UNSIGNED, ratio DOUBLE,
+            price DECIMAL(10,2), day DATE, stamp DATETIME, span TIME, data VARBINARY(8),
+            note VARCHAR(20));
+         INSERT INTO samples VALUES (1, -5, 18446744073709551615, 2.5, 19.99, '2024-02-29',
+             '2024-02-29 13:45:00', '-26:00:01', x'00ff', NULL)",
+    )
+    .await?;
+
+    let expected = vec![
+        Value::Int(1),
+        Value::Int(-5),
+        Value::Text("18446744073709551615".to_string()),
+        Value::Float(2.5),
+        Value::Text("19.99".to_string()),
+        Value::Text("2024-02-29".to_string()),
+        Value::Text("2024-02-29 13:45:00".to_string()),
+        Value::Text("-26:00:01".to_string()),
+        Value::Bytes(vec![0x00, 0xff]),
+        Value::Null,
+    ];
+
+    // Without parameters values come over the text protocol, with them the binary one
+    let rows = conn.query("SELECT * FROM samples", &[]).await?;
+    assert_eq!(rows[0].values(), expected.as_slice());
+    let rows = conn
+        .query("SELECT * FROM samples WHERE id = ?", &[Value::Int(1)])
+        .await?;
+    assert_eq!(rows[0].values(), expected.as_slice());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_mysql_transaction_options() -> TestResult {
+    let Some(ctx) = test_context().await else {
+        return Ok(());
+    };
+    let conn = ctx.db.acquire_connection().await?;
+    conn.batch_execute("CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR(50) NOT NULL)")
+        .await?;
+
+    // SET TRANSACTION only applies to the next transaction, so it has to precede BEGIN
+    let options = TxOptions {
+        isolation: Some(IsolationLevel::Serializable),
+        read_only: true,
+        deferrable: false,
+    };
+    let mut tx = conn.begin_transaction_with(options).await?;
+    assert_eq!(tx.options(), &options);
+    let insert = tx
+        .batch_execute("INSERT INTO users (id, name) VALUES (1, 'Alice')")
+        .await;
+    assert!(insert.is_err(), "read-only transactions reject writes");
+    tx.rollback().await?;
+
+    // The options don't carry over to the next transaction on the connection
+    let mut tx = conn.begin_transaction().await?;
+    tx.batch_execute("INSERT INTO users (id, name) VALUES (1, 'Alice')")
+        .await?;
+    tx.commit().await?;
+    let rows = conn.query("SELECT COUNT(*) FROM users", &[]).await?;
+    assert_eq!(rows[0].try_get::<i64>(0)?, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_mysql_load_csv() -> TestResult {
+    let Some(ctx) = test_context().await else {
+        return Ok(());
+    };
+    let conn = ctx.db.acquire_connection().await?;
+    conn.batch_execute(
+        "CREATE TABLE events (id BIGINT PRIMARY KEY, kind VARCHAR(20) NOT NULL, note VARCHAR(50))",
+    )
+    .await?;
+
+    // Only the unquoted empty field is NULL, the quoted one and the word NULL are text
+    let path = std::env::temp_dir().join(format!("testkit_events_{}.csv", uuid::Uuid::new_v4()));
+    std::fs::write(
+        &path,
+        "note,id,kind\r\n,1,click\r\n\"\",2,click\r\nNULL,3,click\r\n\"a, \"\"b\"\"\",4,view",
+    )?;
+    let loaded = conn.load_csv("events", &path, CsvOptions::default()).await;
+    std::fs::remove_file(&path)?;
+    assert_eq!(loaded?, 4);
+
+    let rows = conn
+        .query("SELECT id, kind, note FROM events ORDER BY id", &[])
+        .await?;
+    let notes: Vec<Option<String>> = rows
+        .iter()
+        .map(|row| row.try_get(2))
+        .collect::<Result<_, _>>()?;
+    assert_eq!(
+        notes,
+        vec![
+            None,
+            Some(String::new()),
+            Some("NULL".to_string()),
+            Some("a, \"b\"".to_string())
+        ]
+    );
+    assert_eq!(rows[3].try_get::<String>(1)?, "view");
+
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_mysql_introspection() -> TestResult {
+    let Some(ctx) = test_context().await else {
+        return Ok(());
+    };
+    let conn = ctx.db.acquire_connection().await?;
+    conn.batch_execute(
+        "CREATE TABLE authors (id INT AUTO_INCREMENT PRIMARY KEY, name VARCHAR(50) NOT NULL,
+             status VARCHAR(10) DEFAULT 'active');
+         CREATE TABLE books (id INT PRIMARY KEY, author_id INT NOT NULL, title VARCHAR(100),
+             CONSTRAINT books_author_fk FOREIGN KEY (author_id) REFERENCES authors (id)
+                 ON DELETE CASCADE);
+         CREATE UNIQUE INDEX books_author_title_idx ON books (author_id, title)",
+    )
+    .await?;
+
+    assert_eq!(conn.tables().await?, vec!["authors", "books"]);
+
+    let columns = conn.columns("authors").await?;
+    let names: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["id", "name", "status"]);
+    assert_eq!(columns[0].generated.as_deref(), Some("auto_increment"));
+    assert_eq!(columns[1].data_type, "varchar(50)");
+    assert!(!columns[1].nullable);
+    assert_eq!(columns[2].default.as_deref(), Some("active"));
+
+    let foreign_keys = conn.foreign_keys().await?;
+    assert_eq!(foreign_keys.len(), 1);
+    let key = &foreign_keys[0];
+    assert_eq!(key.name, "books_author_fk");
+    assert_eq!(key.columns, vec!["author_id"]);
+    assert_eq!(key.referenced_table, "authors");
+    assert_eq!(key.referenced_columns, vec!["id"]);
+    assert_eq!(key.on_delete, "CASCADE");
+
+    let indexes = conn.indexes("books").await?;
+    let index = indexes
+        .iter()
+        .find(|i| i.name == "books_author_title_idx")
+        .expect("the unique index");
+    assert_eq!(index.columns, vec!["author_id", "title"]);
+    assert!(index.unique && !index.primary);
+    assert_eq!(index.method, "btree");
+    assert!(indexes.iter().any(|i| i.primary && i.columns == ["id"]));
+
+    // AUTO_INCREMENT columns stand in for sequences
+    let sequences = conn.sequences().await?;
+    assert_eq!(sequences.len(), 1);
+    assert_eq!(sequences[0].table.as_deref(), Some("authors"));
+    assert_eq!(sequences[0].column.as_deref(), Some("id"));
+
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_mysql_reset() -> TestResult {
+    let Some(ctx) = test_context().await else {
+        return Ok(());
+    };
+    let conn = ctx.db.acquire_connection().await?;
+    conn.batch_execute(
+        "CREATE TABLE customers (id INT AUTO_INCREMENT PRIMARY KEY, name VARCHAR(50));
+         CREATE TABLE invoices (id INT AUTO_INCREMENT PRIMARY KEY, customer_id INT NOT NULL,
+             FOREIGN KEY (customer_id) REFERENCES customers (id));
+         INSERT INTO customers (name) VALUES ('Alice'), ('Bob');
+         INSERT INTO invoices (customer_id) VALUES (1), (2)",
+    )
+    .await?;
+
+    let error = ctx
+        .db
+        .reset(&["invoices"])
+        .await
+        .expect_err("invoices reference customers");
+    assert!(
+        error
+            .to_string()
+            .contains("the excluded table invoices references customers"),
+        "{}",
+        error
+    );
+
+    // TRUNCATE of a referenced table needs the foreign key checks off
+    ctx.db.reset(&[]).await?;
+    for table in ["customers", "invoices"] {
+        let rows = conn
+            .query(&format!("SELECT COUNT(*) FROM {}", table), &[])
+            .await?;
+        assert_eq!(rows[0].try_get::<i64>(0)?, 0, "rows in {}", table);
+    }
+
+    // The checks are back on and AUTO_INCREMENT starts over
+    let conn = ctx.db.acquire_connection().await?;
+    let rows = conn.query("SELECT @@FOREIGN_KEY_CHECKS", &[]).await?;
+    assert_eq!(rows[0].try_get::<i64>(0)?, 1);
+    assert!(
+        conn.batch_execute("INSERT INTO invoices (customer_id) VALUES (7)")
+            .await
+            .is_err()
+    );
+    conn.batch_execute("INSERT INTO customers (name) VALUES ('Carol')")
+        .await?;
+    let rows = conn.query("SELECT id FROM customers", &[]).await?;
+    assert_eq!(rows[0].try_get::<i64>(0)?, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_mysql_statement_capture() -> TestResult {
+    let Some(ctx) = test_context().await else {
+        return Ok(());
+    };
+    let conn = ctx.db.acquire_connection().await?;
+    conn.batch_execute("CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR(50) NOT NULL)")
+        .await?;
+    ctx.db.clear_queries();
+
+    conn.execute(
+        "INSERT INTO users (id, name) VALUES (?, ?), (?, ?)",
+        &[
+            Value::Int(1),
+            Value::from("Alice"),
+            Value::Int(2),
+            Value::from("Bob"),
+        ],
+    )
+    .await?;
+    assert!(conn.query("SELECT missing FROM users", &[]).await.is_err());
+
+    let queries = ctx.db.queries();
+    assert_eq!(queries.len(), 2);
+    assert_eq!(queries[0].rows, Some(2));
+    assert!(queries[1].error.is_some());
+
+    let rows = testkit_core::assert_query_count!(ctx, == 1, {
+        conn.query("SELECT name FROM users WHERE id = ?", &[Value::Int(2)])
+            .await?
+    });
+    assert_eq!(rows[0].try_get::<String>(0)?, "Bob");
+    assert_eq!(
+        ctx.db.queries().last().map(|q| q.sql.as_str()),
+        Some("SELECT name FROM users WHERE id = ?")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_mysql_query_plans() -> TestResult {
+    let Some(ctx) = test_context().await else {
+        return Ok(());
+    };
+    let conn = ctx.db.acquire_connection().await?;
+    conn.batch_execute(
+        "CREATE TABLE members (id INT PRIMARY KEY, email VARCHAR(100) NOT NULL, note VARCHAR(100));
+         CREATE INDEX members_email_idx ON members (email);
+         INSERT INTO members (id, email)
+         WITH RECURSIVE seq (n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 1000)
+         SELECT n, CONCAT('member', n, '@example.com') FROM seq",
+    )
+    .await?;
+    conn.query("ANALYZE TABLE members", &[]).await?;
+
+    // EXPLAIN FORMAT=JSON reports the chosen key and access type of each table
+    let lookup = "SELECT id FROM members WHERE email = 'member42@example.com'";
+    let plan = testkit_core::assert_plan!(conn, lookup, uses_index = "members_email_idx");
+    assert!(plan.full_scans().is_empty(), "{}", plan);
+
+    let plan = conn
+        .explain("SELECT id FROM members WHERE note = 'vip'")
+        .await?;
+    assert_eq!(plan.full_scans(), vec!["members"], "{}", plan);
+
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use std::path::Path;
+use testkit_core::{CsvOptions, SqlDialect, Value};
+use tokio::io::AsyncReadExt;
+
+use crate::PostgresError;
+
+/// Size of the chunks a CSV file is streamed to the server in
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Build the `COPY ... FROM STDIN` statement loading a CSV file into a table
+pub(crate) async fn copy_statement(
+    table: &str,
+    path: &Path,
+    options: &CsvOptions,
+) -> Result<String, PostgresError> {
+    let dialect = SqlDialect::Postgres;
+    let text = |s: String| dialect.literal(&Value::Text(s));
+
+    let columns = options
+        .resolve_columns(path)
+        .await
+        .map_err(|e| read_error(path, e))?
+        .map(|columns| {
+            let columns: Vec<String> = columns
+                .iter()
+                .map(|c| dialect.quote_identifier(c))
+                .collect();
+            format!(" ({})", columns.join(", "))
+        })
+        .unwrap_or_default();
+
+    Ok(format!(
+        "COPY {}{} FROM STDIN WITH (FORMAT csv, HEADER {}, DELIMITER {}, QUOTE {}, NULL {})",
+        dialect.quote_identifier(table),
+        columns,
+        options.header,
+        text(options.delimiter.to_string()),
+        text(options.quote.to_string()),
+        text(options.null.clone())
+    ))
+}
+
+/// Open a CSV file for streaming
+pub(crate) async fn open(path: &Path) -> Result<CsvFile<'_>, PostgresError> {
+    let file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| read_error(path, e))?;
+    Ok(CsvFile { path, file })
+}
+
+/// A CSV file read in chunks
+pub(crate) struct CsvFile<'a> {
+    path: &'a Path,
+    file: tokio::fs::File,
+}
+
+impl CsvFile<'_> {
+    /// Read the next chunk, or `None` at the end of the file
+    pub(crate) async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, PostgresError> {
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let read = self
+            .file
+            .read(&mut chunk)
+            .await
+            .map_err(|e| read_error(self.path, e))?;
+        chunk.truncate(read);
+        Ok((read > 0).then_some(chunk))
+    }
+}
+
+fn read_error(path: &Path, e: std::io::Error) -> PostgresError {
+    PostgresError::Other(format!("failed to read {}: {}", path.display(), e))
+}
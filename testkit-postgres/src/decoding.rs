@@ -0,0 +1,580 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use testkit_core::{
+    DatabaseBackend, SqlDialect, TestConnection, TestContext, TestDatabaseInstance, Value,
+};
+
+use crate::PostgresError;
+
+/// How often [`ChangeStream::next_events`] reads the slot
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Numbers the slots created by this process, as a database may have several streams
+static SLOT_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A column value of a decoded change
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeColumn {
+    /// The column
+    pub name: String,
+    /// The type of the column, such as `integer` or `character varying`
+    pub data_type: String,
+    /// The value, NULL for a TOASTed value the change didn't touch
+    pub value: Value,
+}
+
+/// A change decoded from the write-ahead log of a test database
+///
+/// Tables in the current schema are named without it, others are qualified.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeEvent {
+    /// A transaction started
+    Begin { xid: u32 },
+    /// A row was inserted
+    Insert {
+        table: String,
+        columns: Vec<ChangeColumn>,
+    },
+    /// A row was updated
+    Update {
+        table: String,
+        /// The old primary key, only sent when it changed or with `REPLICA IDENTITY FULL`
+        old_key: Option<Vec<ChangeColumn>>,
+        columns: Vec<ChangeColumn>,
+    },
+    /// A row was deleted
+    Delete {
+        table: String,
+        /// The primary key of the row, empty for tables without a replica identity
+        key: Vec<ChangeColumn>,
+    },
+    /// Tables were truncated
+    Truncate { tables: Vec<String> },
+    /// A transaction committed
+    Commit { xid: u32 },
+}
+
+impl ChangeEvent {
+    /// Returns the table of a row change
+    pub fn table(&self) -> Option<&str> {
+        match self {
+            ChangeEvent::Insert { table, .. }
+            | ChangeEvent::Update { table, .. }
+            | ChangeEvent::Delete { table, .. } => Some(table),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a column of the new row, or of the key of a deleted row
+    pub fn value(&self, column: &str) -> Option<&Value> {
+        let columns = match self {
+            ChangeEvent::Insert { columns, .. } | ChangeEvent::Update { columns, .. } => columns,
+            ChangeEvent::Delete { key, .. } => key,
+            _ => return None,
+        };
+        columns.iter().find(|c| c.name == column).map(|c| &c.value)
+    }
+}
+
+impl Display for ChangeEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let columns = |columns: &[ChangeColumn]| {
+            let columns: Vec<String> = columns
+                .iter()
+                .map(|c| format!("{}={}", c.name, c.value))
+                .collect();
+            columns.join(" ")
+        };
+        match self {
+            ChangeEvent::Begin { xid } => write!(f, "BEGIN {}", xid),
+            ChangeEvent::Insert { table, columns: c } => {
+                write!(f, "INSERT {}: {}", table, columns(c))
+            }
+            ChangeEvent::Update {
+                table, columns: c, ..
+            } => {
+                write!(f, "UPDATE {}: {}", table, columns(c))
+            }
+            ChangeEvent::Delete { table, key } => write!(f, "DELETE {}: {}", table, columns(key)),
+            ChangeEvent::Truncate { tables } => write!(f, "TRUNCATE {}", tables.join(", ")),
+            ChangeEvent::Commit { xid } => write!(f, "COMMIT {}", xid),
+        }
+    }
+}
+
+/// The changes to a test database, decoded from a logical replication slot
+///
+/// Uses the `test_decoding` plugin, which needs `wal_level = logical` on the server.
+/// Changes are consumed as they are read. The slot is dropped with [`close`](Self::close)
+/// or when the stream is dropped, also when the test database is kept, as an inactive slot
+/// holds back the write-ahead log of the whole server.
+///
+/// ```rust,ignore
+/// use testkit_postgres::LogicalDecoding;
+///
+/// let stream = ctx.change_stream().await?;
+/// conn.execute("INSERT INTO users (id, name) VALUES (1, 'Alice')", &[]).await?;
+///
+/// let events = stream.next_events(Duration::from_secs(1)).await?;
+/// assert!(matches!(&events[1], ChangeEvent::Insert { table, .. } if table == "users"));
+/// ```
+#[derive(Debug)]
+pub struct ChangeStream<C> {
+    conn: C,
+    slot: String,
+    schema: String,
+    /// The database the slot is dropped from if the stream isn't closed
+    database_url: Option<String>,
+    closed: bool,
+}
+
+impl<C: TestConnection> ChangeStream<C> {
+    /// Create a slot on the database of a connection, failing if the server doesn't run
+    /// with `wal_level = logical`
+    pub async fn create(conn: C, database: &str) -> Result<Self, PostgresError> {
+        let rows = conn
+            .query(
+                "SELECT current_setting('wal_level'), current_schema()::text",
+                &[],
+            )
+            .await
+            .map_err(query_error)?;
+        let setting = |i| {
+            rows.first()
+                .and_then(|row| row.try_get::<String>(i).ok())
+                .unwrap_or_default()
+        };
+        let wal_level = setting(0);
+        if wal_level != "logical" {
+            return Err(PostgresError::ConfigError(format!(
+                "change streams need wal_level = logical, the server runs with wal_level = {}; \
+                 set it in postgresql.conf or with `-c wal_level=logical` and restart the server",
+                wal_level
+            )));
+        }
+
+        let slot = slot_name(database, SLOT_COUNTER.fetch_add(1, Ordering::SeqCst));
+        let sql = format!(
+            "SELECT slot_name::text FROM pg_create_logical_replication_slot({}, 'test_decoding')",
+            literal(&slot)
+        );
+        conn.query(&sql, &[]).await.map_err(query_error)?;
+
+        Ok(Self {
+            conn,
+            slot,
+            schema: setting(1),
+            database_url: None,
+            closed: false,
+        })
+    }
+
+    /// Drop the slot from the given database when the stream is dropped without closing it
+    fn drop_slot_from(mut self, database_url: String) -> Self {
+        self.database_url = Some(database_url);
+        self
+    }
+
+    /// Returns the name of the replication slot
+    pub fn slot(&self) -> &str {
+        &self.slot
+    }
+
+    /// Returns the changes committed since the last read, without waiting
+    pub async fn poll(&self) -> Result<Vec<ChangeEvent>, PostgresError> {
+        let sql = format!(
+            "SELECT data FROM pg_logical_slot_get_changes({}, NULL, NULL, 'skip-empty-xacts', '1')",
+            literal(&self.slot)
+        );
+        let rows = self.conn.query(&sql, &[]).await.map_err(query_error)?;
+
+        rows.iter()
+            .map(|row| {
+                let data: String = row.try_get(0).map_err(query_error)?;
+                parse_event(&data, &self.schema)
+            })
+            .collect()
+    }
+
+    /// Wait for changes to be committed, returning an empty list after the timeout
+    pub async fn next_events(&self, timeout: Duration) -> Result<Vec<ChangeEvent>, PostgresError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let events = self.poll().await?;
+            if !events.is_empty() || tokio::time::Instant::now() >= deadline {
+                return Ok(events);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Drop the replication slot
+    pub async fn close(mut self) -> Result<(), PostgresError> {
+        let sql = format!("SELECT pg_drop_replication_slot({})", literal(&self.slot));
+        self.conn.query(&sql, &[]).await.map_err(query_error)?;
+        self.closed = true;
+        Ok(())
+    }
+}
+
+impl<C> Drop for ChangeStream<C> {
+    fn drop(&mut self) {
+        if self.closed {
+            return;
+        }
+        let Some(database_url) = &self.database_url else {
+            tracing::warn!(
+                "Replication slot {} was not closed, drop it with pg_drop_replication_slot",
+                self.slot
+            );
+            return;
+        };
+
+        // Drop can't wait for the connection of the stream, so use a session of its own
+        let output = std::process::Command::new("psql")
+            .arg(database_url)
+            .arg("-c")
+            .arg(format!(
+                "SELECT pg_drop_replication_slot(slot_name) FROM pg_replication_slots WHERE slot_name = {};",
+                literal(&self.slot)
+            ))
+            .output();
+
+        let error = match output {
+            Ok(output) if output.status.success() => return,
+            Ok(output) => String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            Err(e) => e.to_string(),
+        };
+        tracing::warn!(
+            "Failed to drop replication slot {}, drop it with pg_drop_replication_slot: {}",
+            self.slot,
+            error
+        );
+    }
+}
+
+/// Stream the changes to a test database with logical decoding
+///
+/// Implemented for the test databases and contexts of both Postgres backends.
+#[async_trait]
+pub trait LogicalDecoding {
+    /// The connection the stream reads the slot with
+    type Connection;
+
+    /// Create a replication slot on the test database and return its change stream
+    async fn change_stream(&self) -> Result<ChangeStream<Self::Connection>, PostgresError>;
+}
+
+#[async_trait]
+impl<B> LogicalDecoding for TestDatabaseInstance<B>
+where
+    B: DatabaseBackend<Error = PostgresError> + Send + Sync + std::fmt::Debug + 'static,
+    B::Connection: TestConnection,
+{
+    type Connection = B::Connection;
+
+    async fn change_stream(&self) -> Result<ChangeStream<Self::Connection>, PostgresError> {
+        let conn = self.connect().await?;
+        let stream = ChangeStream::create(conn, self.name().as_str()).await?;
+        Ok(stream.drop_slot_from(self.backend().connection_string(self.name())))
+    }
+}
+
+#[async_trait]
+impl<B> LogicalDecoding for TestContext<B>
+where
+    B: DatabaseBackend + Send + Sync + std::fmt::Debug + 'static,
+    TestDatabaseInstance<B>: LogicalDecoding,
+{
+    type Connection = <TestDatabaseInstance<B> as LogicalDecoding>::Connection;
+
+    async fn change_stream(&self) -> Result<ChangeStream<Self::Connection>, PostgresError> {
+        self.db.change_stream().await
+    }
+}
+
+fn query_error(e: impl Display) -> PostgresError {
+    PostgresError::QueryError(e.to_string())
+}
+
+fn literal(text: &str) -> String {
+    SqlDialect::Postgres.literal(&Value::Text(text.to_string()))
+}
+
+/// Name of a slot, unique on the server and limited to the characters slots allow
+fn slot_name(database: &str, n: usize) -> String {
+    let mut name: String = database
+        .chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            c @ ('a'..='z' | '0'..='9') => c,
+            _ => '_',
+        })
+        .take(50)
+        .collect();
+    name.push_str(&format!("_{}", n));
+    name
+}
+
+/// Parse a line of `test_decoding` output, such as
+/// `table public.users: INSERT: id[integer]:1 name[text]:'Alice'`
+fn parse_event(line: &str, schema: &str) -> Result<ChangeEvent, PostgresError> {
+    let invalid = || PostgresError::Other(format!("can't decode change: {}", line));
+    let xid = |rest: &str| rest.trim().parse::<u32>().map_err(|_| invalid());
+
+    if let Some(rest) = line.strip_prefix("BEGIN") {
+        return Ok(ChangeEvent::Begin { xid: xid(rest)? });
+    }
+    if let Some(rest) = line.strip_prefix("COMMIT") {
+        return Ok(ChangeEvent::Commit { xid: xid(rest)? });
+    }
+
+    let rest = line.strip_prefix("table ").ok_or_else(invalid)?;
+    let (tables, rest) = rest.split_once(": ").ok_or_else(invalid)?;
+    let (action, data) = rest.split_once(':').ok_or_else(invalid)?;
+    let data = data.trim_start();
+    let table = || table_name(tables, schema);
+
+    let event = match action {
+        "INSERT" => ChangeEvent::Insert {
+            table: table(),
+            columns: parse_columns(data).ok_or_else(invalid)?,
+        },
+        "UPDATE" => match data.strip_prefix("old-key: ") {
+            Some(data) => {
+                let (old, new) = data.split_once(" new-tuple: ").ok_or_else(invalid)?;
+                ChangeEvent::Update {
+                    table: table(),
+                    old_key: Some(parse_columns(old).ok_or_else(invalid)?),
+                    columns: parse_columns(new).ok_or_else(invalid)?,
+                }
+            }
+            None => ChangeEvent::Update {
+                table: table(),
+                old_key: None,
+                columns: parse_columns(data).ok_or_else(invalid)?,
+            },
+        },
+        "DELETE" => ChangeEvent::Delete {
+            table: table(),
+            key: parse_columns(data).ok_or_else(invalid)?,
+        },
+        "TRUNCATE" => ChangeEvent::Truncate {
+            tables: tables
+                .split(", ")
+                .map(|table| table_name(table, schema))
+                .collect(),
+        },
+        _ => return Err(invalid()),
+    };
+    Ok(event)
+}
+
+/// Name a table like the introspection API, without the schema when it is the current one
+fn table_name(qualified: &str, schema: &str) -> String {
+    let (table_schema, table) = split_identifier(qualified);
+    match table_schema {
+        Some(s) if s != schema => format!("{}.{}", s, table),
+        _ => table,
+    }
+}
+
+/// Split a possibly quoted `schema.table` name into its unquoted parts
+fn split_identifier(name: &str) -> (Option<String>, String) {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                current.push('"');
+            }
+            '"' => quoted = !quoted,
+            '.' if !quoted => parts.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    match parts.pop() {
+        Some(schema) => (Some(schema), current),
+        None => (None, current),
+    }
+}
+
+/// Parse the columns of a tuple, `name[type]:value` separated by spaces
+fn parse_columns(data: &str) -> Option<Vec<ChangeColumn>> {
+    let mut columns = Vec::new();
+    let mut rest = data.trim();
+    if rest == "(no-tuple-data)" {
+        return Some(columns);
+    }
+
+    while !rest.is_empty() {
+        // The name ends at the `[` opening the type, outside of quotes
+        let name_end = if let Some(quoted) = rest.strip_prefix('"') {
+            quoted.find("\"[")? + 2
+        } else {
+            rest.find('[')?
+        };
+        let (_, name) = split_identifier(&rest[..name_end]);
+        rest = &rest[name_end + 1..];
+
+        // Array types end in `[]`, so the type ends at the first `]:`
+        let type_end = rest.find("]:")?;
+        let data_type = rest[..type_end].to_string();
+        rest = &rest[type_end + 2..];
+
+        let (raw, quoted, next) = match rest.strip_prefix('\'') {
+            Some(quoted) => {
+                let mut value = String::new();
+                let mut chars = quoted.char_indices().peekable();
+                let end = loop {
+                    let (i, c) = chars.next()?;
+                    if c != '\'' {
+                        value.push(c);
+                    } else if chars.peek().is_some_and(|(_, c)| *c == '\'') {
+                        chars.next();
+                        value.push('\'');
+                    } else {
+                        break i + 1;
+                    }
+                };
+                (value, true, &quoted[end..])
+            }
+            None => {
+                let end = rest.find(' ').unwrap_or(rest.len());
+                (rest[..end].to_string(), false, &rest[end..])
+            }
+        };
+
+        columns.push(ChangeColumn {
+            value: column_value(&data_type, raw, quoted),
+            name,
+            data_type,
+        });
+        rest = next.trim_start();
+    }
+
+    Some(columns)
+}
+
+/// Convert a value printed by `test_decoding` like the backends decode query results
+fn column_value(data_type: &str, raw: String, quoted: bool) -> Value {
+    if quoted {
+        return match data_type {
+            "bytea" => decode_hex(&raw).map_or(Value::Text(raw), Value::Bytes),
+            _ => Value::Text(raw),
+        };
+    }
+
+    match (data_type, raw.as_str()) {
+        (_, "null") | (_, "unchanged-toast-datum") => Value::Null,
+        ("smallint" | "integer" | "bigint" | "oid", _) => {
+            raw.parse().map_or(Value::Text(raw), Value::Int)
+        }
+        ("real" | "double precision", _) => raw.parse().map_or(Value::Text(raw), Value::Float),
+        ("boolean", "true") => Value::Bool(true),
+        ("boolean", "false") => Value::Bool(false),
+        _ => Value::Text(raw),
+    }
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    let hex = text.strip_prefix("\\x")?;
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, data_type: &str, value: Value) -> ChangeColumn {
+        ChangeColumn {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            value,
+        }
+    }
+
+    #[test]
+    fn test_parse_insert() {
+        let event = parse_event(
+            "table public.users: INSERT: \"Id\"[integer]:1 name[text]:'it''s' ids[integer[]]:'{1,2}' \
+             b[bytea]:'\\x6869' f[double precision]:1.5 ok[boolean]:true n[numeric]:2.50 note[text]:null",
+            "public",
+        )
+        .unwrap();
+
+        assert_eq!(
+            event,
+            ChangeEvent::Insert {
+                table: "users".to_string(),
+                columns: vec![
+                    column("Id", "integer", Value::Int(1)),
+                    column("name", "text", Value::Text("it's".into())),
+                    column("ids", "integer[]", Value::Text("{1,2}".into())),
+                    column("b", "bytea", Value::Bytes(b"hi".to_vec())),
+                    column("f", "double precision", Value::Float(1.5)),
+                    column("ok", "boolean", Value::Bool(true)),
+                    column("n", "numeric", Value::Text("2.50".into())),
+                    column("note", "text", Value::Null),
+                ],
+            }
+        );
+        assert_eq!(event.value("name"), Some(&Value::Text("it's".into())));
+        assert_eq!(event.to_string().split(' ').nth(2), Some("Id=1"));
+    }
+
+    #[test]
+    fn test_parse_changes() {
+        assert_eq!(
+            parse_event("BEGIN 6992", "public").unwrap(),
+            ChangeEvent::Begin { xid: 6992 }
+        );
+        assert_eq!(
+            parse_event(
+                "table public.users: UPDATE: old-key: id[integer]:2 new-tuple: id[integer]:3 name[text]:'a b'",
+                "public"
+            )
+            .unwrap(),
+            ChangeEvent::Update {
+                table: "users".to_string(),
+                old_key: Some(vec![column("id", "integer", Value::Int(2))]),
+                columns: vec![
+                    column("id", "integer", Value::Int(3)),
+                    column("name", "text", Value::Text("a b".into())),
+                ],
+            }
+        );
+        assert_eq!(
+            parse_event("table audit.log: DELETE: (no-tuple-data)", "public").unwrap(),
+            ChangeEvent::Delete {
+                table: "audit.log".to_string(),
+                key: vec![],
+            }
+        );
+        assert_eq!(
+            parse_event(
+                "table public.a, public.\"B\": TRUNCATE: restart_seqs cascade",
+                "public"
+            )
+            .unwrap(),
+            ChangeEvent::Truncate {
+                tables: vec!["a".to_string(), "B".to_string()],
+            }
+        );
+        assert!(parse_event("message: transactional: 1", "public").is_err());
+    }
+
+    #[test]
+    fn test_slot_name() {
+        assert_eq!(slot_name("testkit_AB-c", 3), "testkit_ab_c_3");
+        assert!(slot_name(&"x".repeat(100), 12).len() <= 63);
+    }
+}
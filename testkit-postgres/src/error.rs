@@ -32,6 +32,10 @@ pub enum PostgresError {
     #[error("Pool creation error: {0}")]
     PoolCreationError(String),
 
+    /// Error running migrations
+    #[error("Migration error: {0}")]
+    MigrationError(String),
+
     /// Any other error
     #[error("Other error: {0}")]
     Other(String),
@@ -1,7 +1,3 @@
-// Common traits used across feature implementations
-mod common_traits;
-pub use common_traits::*;
-
 #[cfg(feature = "with-tokio-postgres")]
 pub mod tokio_postgres;
 
@@ -12,10 +8,26 @@ pub mod sqlx_postgres;
 mod error;
 pub use error::*;
 
+// Conversion between neutral values and the PostgreSQL wire format
+mod value;
+
+// Bulk loading of CSV files through COPY
+mod csv;
+
+// Change streams decoded from logical replication slots
+mod decoding;
+pub use decoding::{ChangeColumn, ChangeEvent, ChangeStream, LogicalDecoding};
+
+// Subscriptions to LISTEN/NOTIFY channels
+mod notify;
+pub use notify::{Listen, NOTIFICATION_TIMEOUT, Notification, Subscriber};
+
 /// Re-export the traits from testkit-core
 pub use testkit_core::{
-    DatabaseBackend, DatabaseConfig, DatabaseName, DatabasePool, TestContext,
-    TestDatabaseConnection, TestDatabaseInstance,
+    CsvOptions, DatabaseBackend, DatabaseConfig, DatabaseName, DatabasePool, DatabaseTransaction,
+    IsolationLevel, Row, ScriptError, SqlDialect, SqlScript, TestConnection, TestContext,
+    TestDatabaseConnection, TestDatabaseInstance, TransactionManager, TransactionalConnection,
+    TxOptions, Value, WithMigrations,
 };
 
 // Export feature-specific implementations
@@ -35,6 +47,35 @@ pub use testkit_core::{with_boxed_database, with_boxed_database_config};
 // Re-export the boxed_async macro for easily creating boxed async blocks
 pub use testkit_core::boxed_async;
 
+// Used by `#[testkit::matrix]` so that tests only expand for the backends enabled here
+#[cfg(feature = "with-tokio-postgres")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __testkit_if_tokio_postgres {
+    ($($item:tt)*) => { $($item)* };
+}
+
+#[cfg(not(feature = "with-tokio-postgres"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __testkit_if_tokio_postgres {
+    ($($item:tt)*) => {};
+}
+
+#[cfg(feature = "with-sqlx")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __testkit_if_sqlx_postgres {
+    ($($item:tt)*) => { $($item)* };
+}
+
+#[cfg(not(feature = "with-sqlx"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __testkit_if_sqlx_postgres {
+    ($($item:tt)*) => {};
+}
+
 /// Execute a function with a direct database connection using SQLx
 /// This is the most efficient way to perform a one-off database operation
 /// The connection is automatically closed when the operation completes
@@ -0,0 +1,202 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use testkit_core::{DatabaseBackend, TestContext};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::PostgresError;
+
+/// How long [`assert_notified!`](crate::assert_notified) waits by default
+pub const NOTIFICATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A notification sent with `NOTIFY` or `pg_notify`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    /// The channel the notification was sent on
+    pub channel: String,
+    /// The payload, empty if none was given
+    pub payload: String,
+    /// The process ID of the backend that sent the notification
+    pub process_id: u32,
+}
+
+/// A dedicated connection listening to a channel, created by [`Listen::listen`]
+///
+/// Notifications are received in the background and queued until read. Dropping the
+/// subscriber closes the connection.
+///
+/// ```rust,ignore
+/// use testkit_postgres::Listen;
+///
+/// let mut subscriber = ctx.listen("cache_invalidation").await?;
+/// invalidate_user(&conn, 1).await?;
+///
+/// let notification = subscriber.next_notification(Duration::from_secs(1)).await?;
+/// assert_eq!(notification.unwrap().payload, "user:1");
+/// ```
+#[derive(Debug)]
+pub struct Subscriber {
+    channel: String,
+    receiver: mpsc::UnboundedReceiver<Result<Notification, PostgresError>>,
+    task: JoinHandle<()>,
+}
+
+/// The sending half of a [`Subscriber`], fed by the task driving the connection
+pub(crate) type NotificationSender = mpsc::UnboundedSender<Result<Notification, PostgresError>>;
+
+impl Subscriber {
+    /// Start the task receiving the notifications of a channel
+    pub(crate) fn spawn<F, Fut>(channel: &str, receive: F) -> Self
+    where
+        F: FnOnce(NotificationSender) -> Fut,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self {
+            channel: channel.to_string(),
+            receiver,
+            task: tokio::spawn(receive(sender)),
+        }
+    }
+
+    /// Returns the channel this subscriber listens to
+    pub fn channel(&self) -> &str {
+        &self.channel
+    }
+
+    /// Wait for the next notification, `None` if none arrives within the timeout
+    pub async fn next_notification(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<Notification>, PostgresError> {
+        match tokio::time::timeout(timeout, self.receiver.recv()).await {
+            Ok(Some(notification)) => notification.map(Some),
+            Ok(None) => Err(PostgresError::ConnectionError(format!(
+                "the connection listening to {} was closed",
+                self.channel
+            ))),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Returns the notifications already received, without waiting
+    pub fn received(&mut self) -> Result<Vec<Notification>, PostgresError> {
+        let mut notifications = Vec::new();
+        while let Ok(notification) = self.receiver.try_recv() {
+            notifications.push(notification?);
+        }
+        Ok(notifications)
+    }
+
+    /// Wait for a notification, with the given payload if any
+    ///
+    /// Notifications with other payloads are skipped. The error lists them, for
+    /// [`assert_notified!`](crate::assert_notified).
+    #[doc(hidden)]
+    pub async fn expect_notification(
+        &mut self,
+        payload: Option<&str>,
+        timeout: Duration,
+    ) -> Result<Notification, String> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut skipped = Vec::new();
+        loop {
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            match self.next_notification(remaining).await {
+                Ok(Some(notification))
+                    if payload.is_none_or(|payload| notification.payload == payload) =>
+                {
+                    return Ok(notification);
+                }
+                Ok(Some(notification)) => skipped.push(notification.payload),
+                Ok(None) => break,
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+
+        let expected = match payload {
+            Some(payload) => format!("notification {:?}", payload),
+            None => "notification".to_string(),
+        };
+        let mut message = format!(
+            "expected a {} on channel {} within {:?}",
+            expected, self.channel, timeout
+        );
+        if !skipped.is_empty() {
+            message.push_str(&format!(", got {:?}", skipped));
+        }
+        Err(message)
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Subscribe to `NOTIFY` channels of a test database
+///
+/// Implemented for the test databases and contexts of both Postgres backends. Each
+/// subscriber has its own connection, outside of the pool.
+#[async_trait]
+pub trait Listen {
+    /// Listen to a channel on a new connection to the test database
+    async fn listen(&self, channel: &str) -> Result<Subscriber, PostgresError>;
+}
+
+#[async_trait]
+impl<B> Listen for TestContext<B>
+where
+    B: DatabaseBackend + Send + Sync + std::fmt::Debug + 'static,
+    testkit_core::TestDatabaseInstance<B>: Listen,
+{
+    async fn listen(&self, channel: &str) -> Result<Subscriber, PostgresError> {
+        self.db.listen(channel).await
+    }
+}
+
+/// Wait for a notification on a [`Subscriber`]
+///
+/// Takes the subscriber, optionally the expected payload, and optionally
+/// `timeout = duration` (5 seconds by default). Notifications with other payloads are
+/// skipped. Must be called in an async function; returns the notification.
+///
+/// ```rust,ignore
+/// let mut subscriber = ctx.listen("cache_invalidation").await?;
+/// conn.execute("SELECT pg_notify('cache_invalidation', 'user:1')", &[]).await?;
+///
+/// testkit_postgres::assert_notified!(subscriber, "user:1");
+/// testkit_postgres::assert_notified!(subscriber, timeout = Duration::from_millis(100));
+/// ```
+#[macro_export]
+macro_rules! assert_notified {
+    (@expect $subscriber:expr, $payload:expr, $timeout:expr) => {{
+        let payload: ::std::option::Option<&str> = $payload;
+        match $subscriber.expect_notification(payload, $timeout).await {
+            ::std::result::Result::Ok(notification) => notification,
+            ::std::result::Result::Err(message) => ::std::panic!("{}", message),
+        }
+    }};
+    ($subscriber:expr, timeout = $timeout:expr $(,)?) => {
+        $crate::assert_notified!(@expect $subscriber, ::std::option::Option::None, $timeout)
+    };
+    ($subscriber:expr, $payload:expr, timeout = $timeout:expr $(,)?) => {
+        $crate::assert_notified!(@expect $subscriber, ::std::option::Option::Some($payload), $timeout)
+    };
+    ($subscriber:expr, $payload:expr $(,)?) => {
+        $crate::assert_notified!(
+            @expect $subscriber,
+            ::std::option::Option::Some($payload),
+            $crate::NOTIFICATION_TIMEOUT
+        )
+    };
+    ($subscriber:expr $(,)?) => {
+        $crate::assert_notified!(
+            @expect $subscriber,
+            ::std::option::Option::None,
+            $crate::NOTIFICATION_TIMEOUT
+        )
+    };
+}
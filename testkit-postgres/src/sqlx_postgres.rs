@@ -1,15 +1,27 @@
 use crate::error::PostgresError;
-use crate::{TransactionManager, TransactionTrait};
+use crate::{Listen, Notification, Subscriber, csv, value};
 use async_trait::async_trait;
-use sqlx::postgres::{PgPool, PgPoolOptions, PgTransaction};
-use sqlx::query;
+use sha2::{Digest, Sha256};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::migrate::Migrator;
+use sqlx::pool::PoolConnection;
+use sqlx::postgres::types::Oid;
+use sqlx::postgres::{
+    PgArgumentBuffer, PgConnection, PgListener, PgPool, PgPoolOptions, PgTransaction, PgTypeInfo,
+    PgTypeKind, PgValueFormat, Postgres,
+};
+use sqlx::{Column, Either, Encode, Executor, Row as _, TypeInfo, ValueRef, query};
 use std::fmt::Debug;
+use std::path::Path;
 use std::process::Command;
 use std::sync::Arc;
 use testkit_core::{
-    DatabaseBackend, DatabaseConfig, DatabaseName, DatabasePool, TestDatabaseConnection,
-    TestDatabaseInstance,
+    CsvOptions, DatabaseBackend, DatabaseConfig, DatabaseName, DatabasePool, DatabaseTransaction,
+    Row, ScriptError, SqlDialect, SqlScript, TestConnection, TestDatabaseConnection,
+    TestDatabaseInstance, TransactionalConnection, TxOptions, Value, WithMigrations, record,
 };
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
 use url;
 
 /// A connection to a PostgreSQL database using sqlx
@@ -59,32 +71,6 @@ impl SqlxConnection {
         self
     }
 
-    /// Execute a query and return the number of affected rows
-    pub async fn execute(
-        &self,
-        query: &str,
-        _params: &[&(dyn std::fmt::Debug + Sync)],
-    ) -> Result<u64, PostgresError> {
-        let result = sqlx::query(query)
-            .execute(&*self.pool)
-            .await
-            .map_err(|e| PostgresError::QueryError(e.to_string()))?;
-        Ok(result.rows_affected())
-    }
-
-    /// Execute a query and return the results
-    pub async fn query(
-        &self,
-        query: &str,
-        _params: &[&(dyn std::fmt::Debug + Sync)],
-    ) -> Result<Vec<sqlx::postgres::PgRow>, PostgresError> {
-        let result = sqlx::query(query)
-            .fetch_all(&*self.pool)
-            .await
-            .map_err(|e| PostgresError::QueryError(e.to_string()))?;
-        Ok(result)
-    }
-
     /// Execute a function with a direct connection and automatically close it after use
     /// This is the most efficient way to perform a one-off database operation
     pub async fn with_connection<F, R, E>(
@@ -114,6 +100,402 @@ impl TestDatabaseConnection for SqlxConnection {
     }
 }
 
+impl SqlxConnection {
+    /// Acquire a connection from the pool for a single operation
+    async fn acquire(&self) -> Result<PoolConnection<Postgres>, PostgresError> {
+        self.pool
+            .acquire()
+            .await
+            .map_err(|e| PostgresError::ConnectionError(e.to_string()))
+    }
+
+    /// Bulk load a CSV file into a table and return the number of rows loaded
+    ///
+    /// The file is streamed to the server with `COPY ... FROM STDIN` in chunks, so large
+    /// datasets don't have to fit in memory. See [`CsvOptions`] for header mapping and NULL
+    /// markers.
+    pub async fn load_csv(
+        &self,
+        table: &str,
+        path: impl AsRef<Path>,
+        options: CsvOptions,
+    ) -> Result<u64, PostgresError> {
+        let path = path.as_ref();
+        let statement = csv::copy_statement(table, path, &options).await?;
+        let mut file = csv::open(path).await?;
+
+        let mut conn = self.acquire().await?;
+        let mut copy = conn
+            .copy_in_raw(&statement)
+            .await
+            .map_err(|e| PostgresError::QueryError(e.to_string()))?;
+
+        loop {
+            match file.next_chunk().await {
+                Ok(Some(chunk)) => {
+                    copy.send(chunk)
+                        .await
+                        .map_err(|e| PostgresError::QueryError(e.to_string()))?;
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    // Leave the connection usable for the next query
+                    let _ = copy.abort(e.to_string()).await;
+                    return Err(e);
+                }
+            }
+        }
+
+        copy.finish()
+            .await
+            .map_err(|e| PostgresError::QueryError(e.to_string()))
+    }
+
+    /// Listen to a channel on a new connection to the same database, see [`Listen`]
+    pub async fn listen(&self, channel: &str) -> Result<Subscriber, PostgresError> {
+        subscribe(&self.connection_string, channel).await
+    }
+}
+
+/// Listen to a channel with a [`PgListener`], driven by the task of the subscriber
+async fn subscribe(connection_string: &str, channel: &str) -> Result<Subscriber, PostgresError> {
+    let mut listener = PgListener::connect(connection_string)
+        .await
+        .map_err(|e| PostgresError::ConnectionError(e.to_string()))?;
+    listener
+        .listen(channel)
+        .await
+        .map_err(|e| PostgresError::QueryError(e.to_string()))?;
+
+    Ok(Subscriber::spawn(channel, |sender| async move {
+        loop {
+            let notification = listener
+                .recv()
+                .await
+                .map(|n| Notification {
+                    channel: n.channel().to_string(),
+                    payload: n.payload().to_string(),
+                    process_id: n.process_id(),
+                })
+                .map_err(|e| PostgresError::ConnectionError(e.to_string()));
+            let failed = notification.is_err();
+            if sender.send(notification).is_err() || failed {
+                break;
+            }
+        }
+    }))
+}
+
+#[async_trait]
+impl Listen for TestDatabaseInstance<SqlxPostgresBackend> {
+    async fn listen(&self, channel: &str) -> Result<Subscriber, PostgresError> {
+        subscribe(&self.backend().connection_string(self.name()), channel).await
+    }
+}
+
+#[async_trait]
+impl TestConnection for SqlxConnection {
+    type Error = PostgresError;
+
+    fn dialect(&self) -> SqlDialect {
+        SqlDialect::Postgres
+    }
+
+    async fn execute(&self, sql: &str, params: &[Value]) -> Result<u64, Self::Error> {
+        let mut conn = self.acquire().await?;
+        let statement = execute_on(&mut conn, sql, params);
+        record(
+            &self.connection_string,
+            sql,
+            params,
+            |n| Some(*n),
+            statement,
+        )
+        .await
+    }
+
+    async fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>, Self::Error> {
+        let mut conn = self.acquire().await?;
+        let rows = |rows: &Vec<Row>| Some(rows.len() as u64);
+        let statement = query_on(&mut conn, sql, params);
+        record(&self.connection_string, sql, params, rows, statement).await
+    }
+
+    async fn batch_execute(&self, sql: &str) -> Result<(), Self::Error> {
+        let mut conn = self.acquire().await?;
+        let statement = batch_execute_on(&mut conn, sql);
+        record(&self.connection_string, sql, &[], |_| None, statement).await
+    }
+
+    /// Run the script on a single pooled connection, so `SET`s and temporary tables
+    /// carry over from one statement to the next
+    async fn execute_script<S>(&self, script: S) -> Result<(), ScriptError>
+    where
+        S: Into<SqlScript> + Send,
+    {
+        PinnedConnection::new(self).execute_script(script).await
+    }
+}
+
+/// A pooled connection held for a sequence of statements
+///
+/// The connection is acquired with the first statement and released when this is dropped.
+struct PinnedConnection<'a> {
+    owner: &'a SqlxConnection,
+    conn: Mutex<Option<PoolConnection<Postgres>>>,
+}
+
+impl<'a> PinnedConnection<'a> {
+    fn new(owner: &'a SqlxConnection) -> Self {
+        Self {
+            owner,
+            conn: Mutex::new(None),
+        }
+    }
+
+    /// Lock the connection, acquiring it on first use
+    async fn lock(&self) -> Result<MappedMutexGuard<'_, PoolConnection<Postgres>>, PostgresError> {
+        let mut guard = self.conn.lock().await;
+        if guard.is_none() {
+            *guard = Some(self.owner.acquire().await?);
+        }
+        Ok(MutexGuard::map(guard, |conn| {
+            conn.as_mut().expect("the connection was just acquired")
+        }))
+    }
+}
+
+#[async_trait]
+impl TestConnection for PinnedConnection<'_> {
+    type Error = PostgresError;
+
+    fn dialect(&self) -> SqlDialect {
+        SqlDialect::Postgres
+    }
+
+    async fn execute(&self, sql: &str, params: &[Value]) -> Result<u64, Self::Error> {
+        let mut conn = self.lock().await?;
+        let statement = execute_on(&mut conn, sql, params);
+        record(
+            &self.owner.connection_string,
+            sql,
+            params,
+            |n| Some(*n),
+            statement,
+        )
+        .await
+    }
+
+    async fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>, Self::Error> {
+        let mut conn = self.lock().await?;
+        let rows = |rows: &Vec<Row>| Some(rows.len() as u64);
+        let statement = query_on(&mut conn, sql, params);
+        record(&self.owner.connection_string, sql, params, rows, statement).await
+    }
+
+    async fn batch_execute(&self, sql: &str) -> Result<(), Self::Error> {
+        let mut conn = self.lock().await?;
+        let statement = batch_execute_on(&mut conn, sql);
+        record(&self.owner.connection_string, sql, &[], |_| None, statement).await
+    }
+}
+
+#[async_trait]
+impl TransactionalConnection for SqlxConnection {
+    type Transaction = SqlxTransaction;
+
+    /// Begin a transaction on a dedicated connection from the pool
+    async fn begin_transaction_with(
+        &self,
+        options: TxOptions,
+    ) -> Result<Self::Transaction, Self::Error> {
+        let mut tx: PgTransaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| PostgresError::TransactionError(e.to_string()))?;
+
+        // sqlx issues a plain BEGIN, so the options follow as the first statement
+        if let Some(set_transaction) = options.set_transaction_sql(SqlDialect::Postgres) {
+            batch_execute_on(&mut tx, &set_transaction)
+                .await
+                .map_err(|e| PostgresError::TransactionError(e.to_string()))?;
+        }
+
+        Ok(SqlxTransaction {
+            transaction: Mutex::new(Some(tx)),
+            options,
+            connection_string: self.connection_string.clone(),
+        })
+    }
+}
+
+/// Execute a statement with neutral parameters on a single connection
+async fn execute_on(
+    conn: &mut PgConnection,
+    sql: &str,
+    params: &[Value],
+) -> Result<u64, PostgresError> {
+    let mut query = sqlx::query(sql);
+    if !params.is_empty() {
+        let (_, types) = describe(&mut *conn, sql).await?;
+        for param in encode_params(params, &types)? {
+            query = query.bind(param);
+        }
+    }
+
+    let result = query
+        .execute(conn)
+        .await
+        .map_err(|e| PostgresError::QueryError(e.to_string()))?;
+    Ok(result.rows_affected())
+}
+
+/// Run a query with neutral parameters on a single connection
+async fn query_on(
+    conn: &mut PgConnection,
+    sql: &str,
+    params: &[Value],
+) -> Result<Vec<Row>, PostgresError> {
+    let (columns, types) = describe(&mut *conn, sql).await?;
+    let mut query = sqlx::query(sql);
+    for param in encode_params(params, &types)? {
+        query = query.bind(param);
+    }
+
+    let rows = query
+        .fetch_all(conn)
+        .await
+        .map_err(|e| PostgresError::QueryError(e.to_string()))?;
+
+    let columns: Arc<[String]> = columns.into();
+    rows.iter()
+        .map(|row| {
+            let values = (0..row.len())
+                .map(|i| {
+                    let raw = row
+                        .try_get_raw(i)
+                        .map_err(|e| PostgresError::QueryError(e.to_string()))?;
+                    let oid = value_oid(&raw.type_info());
+                    let bytes = (!raw.is_null()).then(|| raw.as_bytes().ok()).flatten();
+
+                    match raw.format() {
+                        PgValueFormat::Binary => {
+                            value::decode_binary(oid, bytes).map_err(PostgresError::QueryError)
+                        }
+                        PgValueFormat::Text => Ok(value::decode_text(
+                            oid,
+                            bytes.map(String::from_utf8_lossy).as_deref(),
+                        )),
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Row::new(columns.clone(), values))
+        })
+        .collect()
+}
+
+/// Execute one or more statements without parameters on a single connection
+async fn batch_execute_on(conn: &mut PgConnection, sql: &str) -> Result<(), PostgresError> {
+    // A bare string runs through the simple query protocol, which allows several statements
+    conn.execute(sql)
+        .await
+        .map_err(|e| PostgresError::QueryError(e.to_string()))?;
+    Ok(())
+}
+
+/// Encode parameters for the types PostgreSQL infers for the statement
+fn encode_params(
+    params: &[Value],
+    types: &[PgTypeInfo],
+) -> Result<Vec<EncodedParam>, PostgresError> {
+    if types.len() != params.len() {
+        return Err(PostgresError::QueryError(format!(
+            "statement expects {} parameters, got {}",
+            types.len(),
+            params.len()
+        )));
+    }
+
+    params
+        .iter()
+        .zip(types)
+        .map(|(value, type_info)| {
+            let oid = value_oid(type_info);
+            let mut bytes = Vec::new();
+            let is_null = value::encode_binary(value, oid, type_info.name(), &mut bytes)
+                .map_err(PostgresError::QueryError)?;
+
+            Ok(EncodedParam {
+                type_info: type_info.clone(),
+                bytes: (!is_null).then_some(bytes),
+            })
+        })
+        .collect()
+}
+
+/// The OID the value module encodes and decodes a type as, enums being text and domains
+/// their base type
+fn value_oid(type_info: &PgTypeInfo) -> u32 {
+    match type_info.kind() {
+        PgTypeKind::Enum(_) => value::TEXT,
+        PgTypeKind::Domain(base) => value_oid(base),
+        _ => type_info.oid().map_or(0, |oid| oid.0),
+    }
+}
+
+/// Describe a statement, returning its column names and parameter types
+async fn describe(
+    conn: &mut PgConnection,
+    sql: &str,
+) -> Result<(Vec<String>, Vec<PgTypeInfo>), PostgresError> {
+    let describe = conn
+        .describe(sql)
+        .await
+        .map_err(|e| PostgresError::QueryError(e.to_string()))?;
+
+    let columns = describe
+        .columns()
+        .iter()
+        .map(|column| column.name().to_string())
+        .collect();
+    let types = match describe.parameters() {
+        Some(Either::Left(types)) => types.to_vec(),
+        _ => Vec::new(),
+    };
+
+    Ok((columns, types))
+}
+
+/// A parameter already encoded for the type PostgreSQL inferred for it
+struct EncodedParam {
+    type_info: PgTypeInfo,
+    bytes: Option<Vec<u8>>,
+}
+
+impl sqlx::Type<Postgres> for EncodedParam {
+    fn type_info() -> PgTypeInfo {
+        // Every value reports its own type through `produces`
+        PgTypeInfo::with_oid(Oid(value::UNKNOWN))
+    }
+}
+
+impl Encode<'_, Postgres> for EncodedParam {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        match &self.bytes {
+            Some(bytes) => {
+                buf.extend_from_slice(bytes);
+                Ok(IsNull::No)
+            }
+            None => Ok(IsNull::Yes),
+        }
+    }
+
+    fn produces(&self) -> Option<PgTypeInfo> {
+        Some(self.type_info.clone())
+    }
+}
+
 /// A connection pool for PostgreSQL using sqlx
 #[derive(Clone)]
 pub struct SqlxPool {
@@ -156,6 +538,141 @@ impl DatabasePool for SqlxPool {
 #[derive(Clone, Debug)]
 pub struct SqlxPostgresBackend {
     config: DatabaseConfig,
+    migrator: Option<&'static Migrator>,
+    migrations: Option<testkit_core::Migrator>,
+}
+
+impl SqlxPostgresBackend {
+    /// Connection string for the database with the given name on the admin server
+    fn database_url(&self, database: &str) -> String {
+        // Parse the URL
+        let url = url::Url::parse(&self.config.admin_url).expect("Failed to parse admin URL");
+
+        // Extract components
+        let scheme = url.scheme();
+        let username = url.username();
+        let password = url.password().unwrap_or("");
+        let host = url.host_str().unwrap_or("localhost");
+        let port = url.port().unwrap_or(5432);
+
+        // Format connection string with the given database name
+        format!(
+            "{}://{}:{}@{}:{}/{}",
+            scheme, username, password, host, port, database
+        )
+    }
+
+    /// Make sure a template database with the migrations applied exists and return its name
+    ///
+    /// The template is named after the versions and checksums of the migrations, so it is
+    /// created once per migration set and reused by every later test database. An advisory
+    /// lock keeps concurrent tests from creating the same template twice.
+    async fn ensure_template(
+        &self,
+        admin_pool: &PgPool,
+        migrator: &Migrator,
+    ) -> Result<String, PostgresError> {
+        let digest = migration_set_digest(migrator);
+        let template = format!("testkit_template_{}", &digest[..16]);
+        let lock_key = i64::from_str_radix(&digest[..15], 16).unwrap_or_default();
+
+        let mut conn = admin_pool
+            .acquire()
+            .await
+            .map_err(|e| PostgresError::ConnectionError(e.to_string()))?;
+        query("SELECT pg_advisory_lock($1)")
+            .bind(lock_key)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| PostgresError::DatabaseCreationError(e.to_string()))?;
+
+        let result = self.create_template(&mut conn, &template, migrator).await;
+
+        query("SELECT pg_advisory_unlock($1)")
+            .bind(lock_key)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| PostgresError::DatabaseCreationError(e.to_string()))?;
+
+        result.map(|_| template)
+    }
+
+    /// Create the template database and run the migrations on it, unless it already exists
+    async fn create_template(
+        &self,
+        conn: &mut PgConnection,
+        template: &str,
+        migrator: &Migrator,
+    ) -> Result<(), PostgresError> {
+        let exists: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM pg_database WHERE datname = $1)")
+                .bind(template)
+                .fetch_one(&mut *conn)
+                .await
+                .map_err(|e| PostgresError::DatabaseCreationError(e.to_string()))?;
+        if exists {
+            return Ok(());
+        }
+
+        tracing::debug!("Creating template database: {}", template);
+        conn.execute(format!("CREATE DATABASE \"{}\"", template).as_str())
+            .await
+            .map_err(|e| PostgresError::DatabaseCreationError(e.to_string()))?;
+
+        let migrated = async {
+            let pool = PgPoolOptions::new()
+                .max_connections(1)
+                .connect(&self.database_url(template))
+                .await
+                .map_err(|e| PostgresError::ConnectionError(e.to_string()))?;
+            let result = migrator.run(&pool).await;
+            // Copying a database fails while anyone is connected to it
+            pool.close().await;
+            result.map_err(|e| PostgresError::MigrationError(e.to_string()))
+        }
+        .await;
+
+        // Don't leave a half migrated template behind for later tests
+        if migrated.is_err() {
+            let _ = conn
+                .execute(format!("DROP DATABASE IF EXISTS \"{}\"", template).as_str())
+                .await;
+        }
+
+        migrated
+    }
+}
+
+impl WithMigrations<&'static Migrator> for SqlxPostgresBackend {
+    /// Create every database from a template the migrations were run on
+    fn with_migrations(mut self, migrator: &'static Migrator) -> Self {
+        self.migrator = Some(migrator);
+        self
+    }
+}
+
+impl WithMigrations<testkit_core::Migrator> for SqlxPostgresBackend {
+    /// Apply the migrations to every database right after it is created
+    ///
+    /// They run after the sqlx migrations, on the database itself rather than a template.
+    fn with_migrations(mut self, migrator: testkit_core::Migrator) -> Self {
+        self.migrations = Some(migrator);
+        self
+    }
+}
+
+/// Hex encoded SHA-256 over the versions and checksums of a set of sqlx migrations
+fn migration_set_digest(migrator: &Migrator) -> String {
+    let mut hasher = Sha256::new();
+    for migration in migrator.iter() {
+        hasher.update(migration.version.to_be_bytes());
+        hasher.update(&migration.checksum);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
 }
 
 #[async_trait]
@@ -165,7 +682,11 @@ impl DatabaseBackend for SqlxPostgresBackend {
     type Error = PostgresError;
 
     async fn new(config: DatabaseConfig) -> Result<Self, Self::Error> {
-        Ok(Self { config })
+        Ok(Self {
+            config,
+            migrator: None,
+            migrations: None,
+        })
     }
 
     /// Create a pool around the given connection string
@@ -181,9 +702,9 @@ impl DatabaseBackend for SqlxPostgresBackend {
 
         let pool_options = PgPoolOptions::new().max_connections(max_connections as u32);
 
+        // Connect lazily, the database doesn't exist until create_database runs
         let pool = pool_options
-            .connect(&connection_string)
-            .await
+            .connect_lazy(&connection_string)
             .map_err(|e| PostgresError::ConnectionError(e.to_string()))?;
 
         Ok(SqlxPool {
@@ -214,7 +735,7 @@ impl DatabaseBackend for SqlxPostgresBackend {
 
     async fn create_database(
         &self,
-        _pool: &Self::Pool,
+        pool: &Self::Pool,
         name: &DatabaseName,
     ) -> Result<(), Self::Error> {
         // Parse the admin URL to extract connection parameters
@@ -228,9 +749,26 @@ impl DatabaseBackend for SqlxPostgresBackend {
             .await
             .map_err(|e| PostgresError::ConnectionError(e.to_string()))?;
 
-        // Create the database
+        // Create the database, from the migrated template if there are migrations
         let db_name = name.as_str();
-        let create_query = format!("CREATE DATABASE \"{}\"", db_name);
+        let loaded;
+        let migrator = match (self.migrator, &self.config.migrations) {
+            (Some(migrator), _) => Some(migrator),
+            (None, Some(dir)) => {
+                loaded = Migrator::new(dir.as_path())
+                    .await
+                    .map_err(|e| PostgresError::MigrationError(e.to_string()))?;
+                Some(&loaded)
+            }
+            (None, None) => None,
+        };
+        let create_query = match migrator {
+            Some(migrator) => {
+                let template = self.ensure_template(&admin_pool, migrator).await?;
+                format!("CREATE DATABASE \"{}\" TEMPLATE \"{}\"", db_name, template)
+            }
+            None => format!("CREATE DATABASE \"{}\"", db_name),
+        };
 
         // Execute the create database query
         query(&create_query)
@@ -238,6 +776,13 @@ impl DatabaseBackend for SqlxPostgresBackend {
             .await
             .map_err(|e| PostgresError::DatabaseCreationError(e.to_string()))?;
 
+        if let Some(migrator) = &self.migrations {
+            migrator
+                .run_on_pool(pool)
+                .await
+                .map_err(|e| PostgresError::MigrationError(e.to_string()))?;
+        }
+
         Ok(())
     }
 
@@ -280,6 +825,21 @@ impl DatabaseBackend for SqlxPostgresBackend {
             // Continue with drop attempt even if termination fails
         }
 
+        // Replication slots of change streams keep the database in use
+        let output = Command::new("psql")
+            .arg(&database_host)
+            .arg("-c")
+            .arg(format!("SELECT pg_drop_replication_slot(slot_name) FROM pg_replication_slots WHERE database = '{}';", database_name))
+            .output();
+
+        if let Err(e) = output {
+            tracing::warn!(
+                "Failed to drop the replication slots of database {}: {}",
+                database_name,
+                e
+            );
+        }
+
         // Now drop the database
         let output = Command::new("psql")
             .arg(&database_host)
@@ -306,89 +866,96 @@ impl DatabaseBackend for SqlxPostgresBackend {
     }
 
     fn connection_string(&self, name: &DatabaseName) -> String {
-        // Parse the URL
-        let url = url::Url::parse(&self.config.admin_url).expect("Failed to parse admin URL");
-
-        // Extract components
-        let scheme = url.scheme();
-        let username = url.username();
-        let password = url.password().unwrap_or("");
-        let host = url.host_str().unwrap_or("localhost");
-        let port = url.port().unwrap_or(5432);
-
-        // Format connection string with the given database name
-        format!(
-            "{}://{}:{}@{}:{}/{}",
-            scheme,
-            username,
-            password,
-            host,
-            port,
-            name.as_str()
-        )
+        self.database_url(name.as_str())
     }
 }
 
 /// A PostgreSQL transaction using sqlx
 pub struct SqlxTransaction {
-    transaction: Option<sqlx::Transaction<'static, sqlx::Postgres>>,
+    transaction: Mutex<Option<PgTransaction<'static>>>,
+    options: TxOptions,
+    connection_string: String,
+}
+
+impl SqlxTransaction {
+    /// Take the transaction out to finish it
+    async fn take(&self) -> Result<PgTransaction<'static>, PostgresError> {
+        self.transaction
+            .lock()
+            .await
+            .take()
+            .ok_or_else(finished_error)
+    }
+}
+
+fn finished_error() -> PostgresError {
+    PostgresError::TransactionError("transaction is already finished".to_string())
+}
+
+impl TestDatabaseConnection for SqlxTransaction {
+    fn connection_string(&self) -> String {
+        self.connection_string.clone()
+    }
 }
 
 #[async_trait]
-impl TransactionTrait for SqlxTransaction {
+impl TestConnection for SqlxTransaction {
     type Error = PostgresError;
 
-    async fn commit(&mut self) -> Result<(), Self::Error> {
-        if let Some(tx) = self.transaction.take() {
-            tx.commit()
-                .await
-                .map_err(|e| PostgresError::TransactionError(e.to_string()))
-        } else {
-            Err(PostgresError::TransactionError(
-                "No transaction to commit".to_string(),
-            ))
-        }
+    fn dialect(&self) -> SqlDialect {
+        SqlDialect::Postgres
     }
 
-    async fn rollback(&mut self) -> Result<(), Self::Error> {
-        if let Some(tx) = self.transaction.take() {
-            tx.rollback()
-                .await
-                .map_err(|e| PostgresError::TransactionError(e.to_string()))
-        } else {
-            Err(PostgresError::TransactionError(
-                "No transaction to rollback".to_string(),
-            ))
-        }
+    async fn execute(&self, sql: &str, params: &[Value]) -> Result<u64, Self::Error> {
+        let mut guard = self.transaction.lock().await;
+        let tx = guard.as_mut().ok_or_else(finished_error)?;
+        let statement = execute_on(tx, sql, params);
+        record(
+            &self.connection_string,
+            sql,
+            params,
+            |n| Some(*n),
+            statement,
+        )
+        .await
+    }
+
+    async fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>, Self::Error> {
+        let mut guard = self.transaction.lock().await;
+        let tx = guard.as_mut().ok_or_else(finished_error)?;
+        let rows = |rows: &Vec<Row>| Some(rows.len() as u64);
+        let statement = query_on(tx, sql, params);
+        record(&self.connection_string, sql, params, rows, statement).await
+    }
+
+    async fn batch_execute(&self, sql: &str) -> Result<(), Self::Error> {
+        let mut guard = self.transaction.lock().await;
+        let tx = guard.as_mut().ok_or_else(finished_error)?;
+        let statement = batch_execute_on(tx, sql);
+        record(&self.connection_string, sql, &[], |_| None, statement).await
     }
 }
 
-/// Implementation of TransactionManager for PostgreSQL with sqlx
 #[async_trait]
-impl TransactionManager for TestDatabaseInstance<SqlxPostgresBackend> {
-    type Error = PostgresError;
-    type Tx = SqlxTransaction;
-    type Connection = SqlxConnection;
-
-    async fn begin_transaction(&mut self) -> Result<Self::Tx, Self::Error> {
-        // We need to create a new connection and start a transaction directly
-        let pool = &self.pool.pool;
-        let tx: PgTransaction = pool
-            .begin()
+impl DatabaseTransaction for SqlxTransaction {
+    async fn commit(&mut self) -> Result<(), Self::Error> {
+        self.take()
+            .await?
+            .commit()
             .await
-            .map_err(|e| PostgresError::TransactionError(e.to_string()))?;
-
-        Ok(SqlxTransaction {
-            transaction: Some(tx),
-        })
+            .map_err(|e| PostgresError::TransactionError(e.to_string()))
     }
 
-    async fn commit_transaction(tx: &mut Self::Tx) -> Result<(), Self::Error> {
-        tx.commit().await
+    async fn rollback(&mut self) -> Result<(), Self::Error> {
+        self.take()
+            .await?
+            .rollback()
+            .await
+            .map_err(|e| PostgresError::TransactionError(e.to_string()))
     }
 
-    async fn rollback_transaction(tx: &mut Self::Tx) -> Result<(), Self::Error> {
-        tx.rollback().await
+    fn options(&self) -> &TxOptions {
+        &self.options
     }
 }
 
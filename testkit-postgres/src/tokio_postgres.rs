@@ -1,22 +1,85 @@
 use crate::PostgresError;
-use crate::{TransactionManager, TransactionTrait};
+use crate::{Listen, Notification, Subscriber, csv, value};
 use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use futures::{SinkExt, StreamExt, TryStreamExt};
+use postgres_types::{FromSql, IsNull, Kind, ToSql, Type, to_sql_checked};
 use std::fmt::Debug;
+use std::ops::Deref;
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use testkit_core::{
-    DatabaseBackend, DatabaseConfig, DatabaseName, DatabasePool, TestDatabaseConnection,
-    TestDatabaseInstance,
+    CsvOptions, DatabaseBackend, DatabaseConfig, DatabaseName, DatabasePool, DatabaseTransaction,
+    Migrator, Row, SqlDialect, TestConnection, TestDatabaseConnection, TestDatabaseInstance,
+    TransactionalConnection, TxOptions, Value, WithMigrations, record,
 };
+use tokio_postgres::AsyncMessage;
 use url::Url;
 
 /// A connection to a PostgreSQL database using tokio-postgres
 #[derive(Clone)]
 pub struct PostgresConnection {
-    client: Arc<deadpool_postgres::Client>,
+    client: Arc<PooledClient>,
     connection_string: String,
 }
 
+/// A pooled client shared by a connection and its transactions
+///
+/// A transaction dropped without commit or rollback marks its rollback as pending.
+/// The ROLLBACK is sent before the next statement on the client or, if the client is
+/// not used again, before it goes back to the pool.
+struct PooledClient {
+    client: Option<deadpool_postgres::Client>,
+    rollback_pending: AtomicBool,
+}
+
+impl PooledClient {
+    fn new(client: deadpool_postgres::Client) -> Self {
+        Self {
+            client: Some(client),
+            rollback_pending: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Deref for PooledClient {
+    type Target = deadpool_postgres::Client;
+
+    fn deref(&self) -> &Self::Target {
+        self.client.as_ref().expect("client is only taken on drop")
+    }
+}
+
+impl Drop for PooledClient {
+    fn drop(&mut self) {
+        if !self.rollback_pending.load(Ordering::Acquire) {
+            return;
+        }
+        let Some(client) = self.client.take() else {
+            return;
+        };
+
+        // The task owns the client, so it only goes back to the pool once the
+        // ROLLBACK has completed. Without a runtime, the client is taken out of
+        // the pool and closed instead.
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move {
+                    if let Err(e) = client.batch_execute("ROLLBACK").await {
+                        tracing::warn!("Failed to roll back dropped PostgreSQL transaction: {}", e);
+                        drop(deadpool_postgres::Object::take(client));
+                    }
+                });
+            }
+            Err(_) => {
+                drop(deadpool_postgres::Object::take(client));
+            }
+        }
+    }
+}
+
 impl PostgresConnection {
     /// Create a new direct connection without using a pool
     pub async fn connect(connection_string: impl Into<String>) -> Result<Self, PostgresError> {
@@ -46,7 +109,7 @@ impl PostgresConnection {
             .map_err(|e| PostgresError::ConnectionError(e.to_string()))?;
 
         Ok(Self {
-            client: Arc::new(client),
+            client: Arc::new(PooledClient::new(client)),
             connection_string,
         })
     }
@@ -74,9 +137,108 @@ impl PostgresConnection {
     }
 
     /// Get a reference to the underlying database client
+    ///
+    /// Statements sent directly on the client don't roll back a dropped transaction
+    /// first.
     pub fn client(&self) -> &deadpool_postgres::Client {
         &self.client
     }
+
+    /// Roll back a transaction that was dropped without commit or rollback
+    async fn settle(&self) -> Result<(), PostgresError> {
+        if self.client.rollback_pending.swap(false, Ordering::AcqRel) {
+            self.client
+                .batch_execute("ROLLBACK")
+                .await
+                .map_err(|e| PostgresError::TransactionError(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Bulk load a CSV file into a table and return the number of rows loaded
+    ///
+    /// The file is streamed to the server with `COPY ... FROM STDIN` in chunks, so large
+    /// datasets don't have to fit in memory. See [`CsvOptions`] for header mapping and NULL
+    /// markers.
+    pub async fn load_csv(
+        &self,
+        table: &str,
+        path: impl AsRef<Path>,
+        options: CsvOptions,
+    ) -> Result<u64, PostgresError> {
+        let path = path.as_ref();
+        let statement = csv::copy_statement(table, path, &options).await?;
+        let mut file = csv::open(path).await?;
+        self.settle().await?;
+
+        let sink = self
+            .client
+            .copy_in::<_, Bytes>(statement.as_str())
+            .await
+            .map_err(|e| PostgresError::QueryError(e.to_string()))?;
+        futures::pin_mut!(sink);
+
+        while let Some(chunk) = file.next_chunk().await? {
+            sink.send(Bytes::from(chunk))
+                .await
+                .map_err(|e| PostgresError::QueryError(e.to_string()))?;
+        }
+
+        sink.as_mut()
+            .finish()
+            .await
+            .map_err(|e| PostgresError::QueryError(e.to_string()))
+    }
+
+    /// Listen to a channel on a new connection to the same database, see [`Listen`]
+    pub async fn listen(&self, channel: &str) -> Result<Subscriber, PostgresError> {
+        subscribe(&self.connection_string, channel).await
+    }
+}
+
+/// Listen to a channel on a new connection, driven by the task of the subscriber
+async fn subscribe(connection_string: &str, channel: &str) -> Result<Subscriber, PostgresError> {
+    let (client, mut connection) =
+        tokio_postgres::connect(connection_string, tokio_postgres::NoTls)
+            .await
+            .map_err(|e| PostgresError::ConnectionError(e.to_string()))?;
+    // The task keeps a handle to the client, so the connection lives as long as the subscriber
+    let client = Arc::new(client);
+    let handle = client.clone();
+
+    let subscriber = Subscriber::spawn(channel, |sender| async move {
+        let _client = handle;
+        let mut messages = futures::stream::poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            let notification = match message {
+                Ok(AsyncMessage::Notification(n)) => Ok(Notification {
+                    channel: n.channel().to_string(),
+                    payload: n.payload().to_string(),
+                    process_id: n.process_id() as u32,
+                }),
+                Ok(_) => continue,
+                Err(e) => Err(PostgresError::ConnectionError(e.to_string())),
+            };
+            let failed = notification.is_err();
+            if sender.send(notification).is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    let listen = format!("LISTEN {}", SqlDialect::Postgres.quote_identifier(channel));
+    client
+        .batch_execute(&listen)
+        .await
+        .map_err(|e| PostgresError::QueryError(e.to_string()))?;
+    Ok(subscriber)
+}
+
+#[async_trait]
+impl Listen for TestDatabaseInstance<PostgresBackend> {
+    async fn listen(&self, channel: &str) -> Result<Subscriber, PostgresError> {
+        subscribe(&self.backend().connection_string(self.name()), channel).await
+    }
 }
 
 impl TestDatabaseConnection for PostgresConnection {
@@ -85,6 +247,145 @@ impl TestDatabaseConnection for PostgresConnection {
     }
 }
 
+#[async_trait]
+impl TestConnection for PostgresConnection {
+    type Error = PostgresError;
+
+    fn dialect(&self) -> SqlDialect {
+        SqlDialect::Postgres
+    }
+
+    async fn execute(&self, sql: &str, params: &[Value]) -> Result<u64, Self::Error> {
+        self.settle().await?;
+        record(&self.connection_string, sql, params, |n| Some(*n), async {
+            let params: Vec<PgParam> = params.iter().map(PgParam).collect();
+            self.client
+                .execute_raw(sql, params.iter())
+                .await
+                .map_err(|e| PostgresError::QueryError(e.to_string()))
+        })
+        .await
+    }
+
+    async fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>, Self::Error> {
+        self.settle().await?;
+        let rows = |rows: &Vec<Row>| Some(rows.len() as u64);
+        record(
+            &self.connection_string,
+            sql,
+            params,
+            rows,
+            self.query_rows(sql, params),
+        )
+        .await
+    }
+
+    async fn batch_execute(&self, sql: &str) -> Result<(), Self::Error> {
+        self.settle().await?;
+        record(&self.connection_string, sql, &[], |_| None, async {
+            self.client
+                .batch_execute(sql)
+                .await
+                .map_err(|e| PostgresError::QueryError(e.to_string()))
+        })
+        .await
+    }
+}
+
+impl PostgresConnection {
+    /// Run a query and decode its rows into neutral values
+    async fn query_rows(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>, PostgresError> {
+        let params: Vec<PgParam> = params.iter().map(PgParam).collect();
+        let statement = self
+            .client
+            .prepare(sql)
+            .await
+            .map_err(|e| PostgresError::QueryError(e.to_string()))?;
+        let rows = self
+            .client
+            .query_raw(&statement, params.iter())
+            .await
+            .map_err(|e| PostgresError::QueryError(e.to_string()))?
+            .try_collect::<Vec<_>>()
+            .await
+            .map_err(|e| PostgresError::QueryError(e.to_string()))?;
+
+        let columns: Arc<[String]> = statement
+            .columns()
+            .iter()
+            .map(|column| column.name().to_string())
+            .collect();
+
+        rows.iter()
+            .map(|row| {
+                let values = (0..row.len())
+                    .map(|i| {
+                        let RawValue(raw) = row
+                            .try_get(i)
+                            .map_err(|e| PostgresError::QueryError(e.to_string()))?;
+                        value::decode_binary(value_oid(row.columns()[i].type_()), raw)
+                            .map_err(PostgresError::QueryError)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Row::new(columns.clone(), values))
+            })
+            .collect()
+    }
+}
+
+/// The OID the value module encodes and decodes a type as, enums being text and domains
+/// their base type
+fn value_oid(ty: &Type) -> u32 {
+    match ty.kind() {
+        Kind::Enum(_) => value::TEXT,
+        Kind::Domain(base) => value_oid(base),
+        _ => ty.oid(),
+    }
+}
+
+/// A neutral value bound as a parameter, encoded for the type PostgreSQL inferred
+#[derive(Debug)]
+struct PgParam<'a>(&'a Value);
+
+impl ToSql for PgParam<'_> {
+    fn to_sql(
+        &self,
+        ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn std::error::Error + Sync + Send>> {
+        let mut buf = Vec::new();
+        let is_null = value::encode_binary(self.0, value_oid(ty), ty.name(), &mut buf)?;
+        out.extend_from_slice(&buf);
+        Ok(if is_null { IsNull::Yes } else { IsNull::No })
+    }
+
+    fn accepts(_ty: &Type) -> bool {
+        true
+    }
+
+    to_sql_checked!();
+}
+
+/// The raw bytes of a column, decoded by the value module
+struct RawValue<'a>(Option<&'a [u8]>);
+
+impl<'a> FromSql<'a> for RawValue<'a> {
+    fn from_sql(
+        _ty: &Type,
+        raw: &'a [u8],
+    ) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(RawValue(Some(raw)))
+    }
+
+    fn from_sql_null(_ty: &Type) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(RawValue(None))
+    }
+
+    fn accepts(_ty: &Type) -> bool {
+        true
+    }
+}
+
 /// A connection pool for PostgreSQL using deadpool-postgres
 #[derive(Clone)]
 pub struct PostgresPool {
@@ -107,14 +408,15 @@ impl DatabasePool for PostgresPool {
 
         // Return a new PostgresConnection
         Ok(PostgresConnection {
-            client: Arc::new(client),
+            client: Arc::new(PooledClient::new(client)),
             connection_string: self.connection_string.clone(),
         })
     }
 
-    async fn release(&self, _conn: Self::Connection) -> Result<(), Self::Error> {
-        // The deadpool automatically handles connection release when the client is dropped
-        Ok(())
+    async fn release(&self, conn: Self::Connection) -> Result<(), Self::Error> {
+        // Roll back a dropped transaction before the client is reused, the deadpool
+        // handles the release itself when the client is dropped
+        conn.settle().await
     }
 
     fn connection_string(&self) -> String {
@@ -126,6 +428,15 @@ impl DatabasePool for PostgresPool {
 #[derive(Clone, Debug)]
 pub struct PostgresBackend {
     config: DatabaseConfig,
+    migrations: Option<Migrator>,
+}
+
+impl WithMigrations<Migrator> for PostgresBackend {
+    /// Apply the migrations to every database right after it is created
+    fn with_migrations(mut self, migrator: Migrator) -> Self {
+        self.migrations = Some(migrator);
+        self
+    }
 }
 
 #[async_trait]
@@ -142,7 +453,15 @@ impl DatabaseBackend for PostgresBackend {
             ));
         }
 
-        Ok(Self { config })
+        Ok(Self {
+            migrations: config
+                .migrations
+                .as_deref()
+                .map(Migrator::from_dir)
+                .transpose()
+                .map_err(|e| PostgresError::MigrationError(e.to_string()))?,
+            config,
+        })
     }
 
     /// Create a new connection pool for the given database
@@ -197,7 +516,7 @@ impl DatabaseBackend for PostgresBackend {
 
     async fn create_database(
         &self,
-        _pool: &Self::Pool,
+        pool: &Self::Pool,
         name: &DatabaseName,
     ) -> Result<(), Self::Error> {
         // Create admin connection to create the database
@@ -221,6 +540,13 @@ impl DatabaseBackend for PostgresBackend {
             .await
             .map_err(|e| PostgresError::DatabaseCreationError(e.to_string()))?;
 
+        if let Some(migrator) = &self.migrations {
+            migrator
+                .run_on_pool(pool)
+                .await
+                .map_err(|e| PostgresError::MigrationError(e.to_string()))?;
+        }
+
         Ok(())
     }
 
@@ -263,6 +589,21 @@ impl DatabaseBackend for PostgresBackend {
             // Continue with drop attempt even if termination fails
         }
 
+        // Replication slots of change streams keep the database in use
+        let output = std::process::Command::new("psql")
+            .arg(&database_host)
+            .arg("-c")
+            .arg(format!("SELECT pg_drop_replication_slot(slot_name) FROM pg_replication_slots WHERE database = '{}';", database_name))
+            .output();
+
+        if let Err(e) = output {
+            tracing::warn!(
+                "Failed to drop the replication slots of database {}: {}",
+                database_name,
+                e
+            );
+        }
+
         // Now drop the database
         let output = std::process::Command::new("psql")
             .arg(&database_host)
@@ -305,60 +646,118 @@ impl DatabaseBackend for PostgresBackend {
 
 /// A PostgreSQL transaction using tokio-postgres
 pub struct PostgresTransaction {
-    client: Arc<deadpool_postgres::Client>,
+    conn: PostgresConnection,
+    options: TxOptions,
+    finished: bool,
 }
 
-#[async_trait]
-impl TransactionTrait for PostgresTransaction {
-    type Error = PostgresError;
+impl PostgresTransaction {
+    /// Get a reference to the underlying database client
+    pub fn client(&self) -> &deadpool_postgres::Client {
+        self.conn.client()
+    }
 
-    async fn commit(&mut self) -> Result<(), Self::Error> {
-        self.client
-            .execute("COMMIT", &[])
-            .await
-            .map_err(|e| PostgresError::TransactionError(e.to_string()))?;
+    fn check_active(&self) -> Result<(), PostgresError> {
+        if self.finished {
+            return Err(PostgresError::TransactionError(
+                "transaction is already finished".to_string(),
+            ));
+        }
         Ok(())
     }
 
-    async fn rollback(&mut self) -> Result<(), Self::Error> {
-        self.client
-            .execute("ROLLBACK", &[])
+    async fn finish(&mut self, statement: &str) -> Result<(), PostgresError> {
+        self.check_active()?;
+        self.finished = true;
+        self.conn
+            .client
+            .batch_execute(statement)
             .await
-            .map_err(|e| PostgresError::TransactionError(e.to_string()))?;
-        Ok(())
+            .map_err(|e| PostgresError::TransactionError(e.to_string()))
+    }
+}
+
+// A transaction dropped without an explicit commit or rollback leaves its
+// rollback pending on the shared client, see `PooledClient`.
+impl Drop for PostgresTransaction {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.conn
+                .client
+                .rollback_pending
+                .store(true, Ordering::Release);
+        }
+    }
+}
+
+impl TestDatabaseConnection for PostgresTransaction {
+    fn connection_string(&self) -> String {
+        self.conn.connection_string.clone()
     }
 }
 
-/// Implementation of TransactionManager for PostgreSQL
 #[async_trait]
-impl TransactionManager for TestDatabaseInstance<PostgresBackend> {
+impl TestConnection for PostgresTransaction {
     type Error = PostgresError;
-    type Tx = PostgresTransaction;
-    type Connection = PostgresConnection;
 
-    async fn begin_transaction(&mut self) -> Result<Self::Tx, Self::Error> {
-        // Get a connection from the pool
-        let pool = &self.pool;
-        let client = pool.acquire().await?;
+    fn dialect(&self) -> SqlDialect {
+        SqlDialect::Postgres
+    }
 
-        // Begin transaction
-        client
-            .client
-            .execute("BEGIN", &[])
-            .await
-            .map_err(|e| PostgresError::TransactionError(e.to_string()))?;
+    async fn execute(&self, sql: &str, params: &[Value]) -> Result<u64, Self::Error> {
+        self.check_active()?;
+        self.conn.execute(sql, params).await
+    }
 
-        Ok(PostgresTransaction {
-            client: Arc::clone(&client.client),
-        })
+    async fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>, Self::Error> {
+        self.check_active()?;
+        self.conn.query(sql, params).await
     }
 
-    async fn commit_transaction(tx: &mut Self::Tx) -> Result<(), Self::Error> {
-        tx.commit().await
+    async fn batch_execute(&self, sql: &str) -> Result<(), Self::Error> {
+        self.check_active()?;
+        self.conn.batch_execute(sql).await
     }
+}
+
+#[async_trait]
+impl DatabaseTransaction for PostgresTransaction {
+    async fn commit(&mut self) -> Result<(), Self::Error> {
+        self.finish("COMMIT").await
+    }
+
+    async fn rollback(&mut self) -> Result<(), Self::Error> {
+        self.finish("ROLLBACK").await
+    }
+
+    fn options(&self) -> &TxOptions {
+        &self.options
+    }
+}
 
-    async fn rollback_transaction(tx: &mut Self::Tx) -> Result<(), Self::Error> {
-        tx.rollback().await
+#[async_trait]
+impl TransactionalConnection for PostgresConnection {
+    type Transaction = PostgresTransaction;
+
+    async fn begin_transaction_with(
+        &self,
+        options: TxOptions,
+    ) -> Result<Self::Transaction, Self::Error> {
+        let begin = match options.set_transaction_sql(SqlDialect::Postgres) {
+            Some(set_transaction) => format!("BEGIN; {}", set_transaction),
+            None => "BEGIN".to_string(),
+        };
+        self.settle().await?;
+        self.client
+            .batch_execute(&begin)
+            .await
+            .map_err(|e| PostgresError::TransactionError(e.to_string()))?;
+
+        Ok(PostgresTransaction {
+            conn: self.clone(),
+            options,
+            finished: false,
+        })
     }
 }
 
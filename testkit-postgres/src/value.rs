@@ -0,0 +1,588 @@
+//! Conversion between testkit's neutral `Value` and the PostgreSQL wire format
+//!
+//! Both drivers hand out and accept values in the binary format, keyed by the OID of
+//! the column or parameter type, so tokio-postgres and sqlx share this module.
+
+use testkit_core::Value;
+
+pub(crate) const BOOL: u32 = 16;
+pub(crate) const BYTEA: u32 = 17;
+pub(crate) const CHAR: u32 = 18;
+pub(crate) const NAME: u32 = 19;
+pub(crate) const INT8: u32 = 20;
+pub(crate) const INT2: u32 = 21;
+pub(crate) const INT4: u32 = 23;
+pub(crate) const TEXT: u32 = 25;
+pub(crate) const OID: u32 = 26;
+pub(crate) const XID: u32 = 28;
+pub(crate) const JSON: u32 = 114;
+pub(crate) const XML: u32 = 142;
+pub(crate) const FLOAT4: u32 = 700;
+pub(crate) const FLOAT8: u32 = 701;
+pub(crate) const UNKNOWN: u32 = 705;
+pub(crate) const BPCHAR: u32 = 1042;
+pub(crate) const VARCHAR: u32 = 1043;
+pub(crate) const DATE: u32 = 1082;
+pub(crate) const TIME: u32 = 1083;
+pub(crate) const TIMESTAMP: u32 = 1114;
+pub(crate) const TIMESTAMPTZ: u32 = 1184;
+pub(crate) const INTERVAL: u32 = 1186;
+pub(crate) const NUMERIC: u32 = 1700;
+pub(crate) const UUID: u32 = 2950;
+pub(crate) const VOID: u32 = 2278;
+pub(crate) const PG_LSN: u32 = 3220;
+pub(crate) const JSONB: u32 = 3802;
+
+/// Array types of the built-in types decoded here, whose elements are decoded like them
+const ARRAYS: &[u32] = &[
+    199, 1000, 1001, 1002, 1003, 1005, 1007, 1009, 1014, 1015, 1016, 1021, 1022, 1028, 1115, 1182,
+    1183, 1185, 1187, 1231, 2951, 3807,
+];
+
+/// Days between 1970-01-01 and 2000-01-01, the PostgreSQL epoch
+const PG_EPOCH_DAYS: i64 = 10_957;
+
+const NUMERIC_NEG: u16 = 0x4000;
+const NUMERIC_NAN: u16 = 0xC000;
+
+/// Returns true for types whose binary format is their text
+///
+/// The drivers pass enums as [`TEXT`], as their binary format is the label.
+fn is_textual(oid: u32) -> bool {
+    matches!(
+        oid,
+        TEXT | VARCHAR | BPCHAR | NAME | CHAR | UNKNOWN | JSON | XML
+    )
+}
+
+/// Decode a value received in the binary format
+///
+/// Fails for types this module doesn't know the binary format of, such as ranges and
+/// composites, rather than guessing; casting the column to text reads them.
+pub(crate) fn decode_binary(oid: u32, raw: Option<&[u8]>) -> Result<Value, String> {
+    let Some(raw) = raw else {
+        return Ok(Value::Null);
+    };
+
+    let value = match oid {
+        BOOL => Value::Bool(raw.first().is_some_and(|b| *b != 0)),
+        INT2 => Value::Int(i16::from_be_bytes(fixed(raw)?).into()),
+        INT4 => Value::Int(i32::from_be_bytes(fixed(raw)?).into()),
+        INT8 => Value::Int(i64::from_be_bytes(fixed(raw)?)),
+        OID => Value::Int(u32::from_be_bytes(fixed(raw)?).into()),
+        FLOAT4 => Value::Float(f32::from_be_bytes(fixed(raw)?).into()),
+        FLOAT8 => Value::Float(f64::from_be_bytes(fixed(raw)?)),
+        NUMERIC => Value::Text(decode_numeric(raw)?),
+        BYTEA => Value::Bytes(raw.to_vec()),
+        UUID => Value::Text(uuid::Uuid::from_bytes(fixed(raw)?).to_string()),
+        // jsonb is prefixed with a version byte
+        JSONB => Value::Text(String::from_utf8_lossy(raw.get(1..).unwrap_or_default()).into()),
+        DATE => Value::Text(format_date(i32::from_be_bytes(fixed(raw)?))),
+        TIME => Value::Text(format_time(i64::from_be_bytes(fixed(raw)?))),
+        TIMESTAMP => Value::Text(format_timestamp(i64::from_be_bytes(fixed(raw)?))),
+        TIMESTAMPTZ => Value::Text(format!(
+            "{}+00",
+            format_timestamp(i64::from_be_bytes(fixed(raw)?))
+        )),
+        INTERVAL => Value::Text(format_interval(
+            i64::from_be_bytes(fixed(raw.get(..8).unwrap_or_default())?),
+            i32::from_be_bytes(fixed(raw.get(8..12).unwrap_or_default())?),
+            i32::from_be_bytes(fixed(raw.get(12..).unwrap_or_default())?),
+        )),
+        XID => Value::Int(u32::from_be_bytes(fixed(raw)?).into()),
+        PG_LSN => {
+            let lsn = u64::from_be_bytes(fixed(raw)?);
+            Value::Text(format!("{:X}/{:X}", lsn >> 32, lsn & 0xFFFF_FFFF))
+        }
+        VOID => Value::Null,
+        oid if is_textual(oid) => match std::str::from_utf8(raw) {
+            Ok(text) => Value::Text(text.to_string()),
+            Err(_) => Value::Bytes(raw.to_vec()),
+        },
+        oid if ARRAYS.contains(&oid) => Value::Text(decode_array(raw)?),
+        oid => {
+            return Err(format!(
+                "can't decode values of the type with OID {} in the binary format, cast the column to text",
+                oid
+            ));
+        }
+    };
+
+    Ok(value)
+}
+
+/// Decode an array into its text form, such as `{1,2,NULL}`
+fn decode_array(raw: &[u8]) -> Result<String, String> {
+    let mut at = 0;
+    let ndim = read_i32(raw, &mut at)?;
+    let _has_nulls = read_i32(raw, &mut at)?;
+    let element = read_i32(raw, &mut at)? as u32;
+    let mut dims = Vec::new();
+    for _ in 0..ndim {
+        dims.push((read_i32(raw, &mut at)?, read_i32(raw, &mut at)?));
+    }
+    let mut elements = Vec::new();
+    for _ in 0..dims.iter().map(|(len, _)| *len as usize).product::<usize>() {
+        let len = read_i32(raw, &mut at)?;
+        if len < 0 {
+            elements.push("NULL".to_string());
+            continue;
+        }
+        let start = at;
+        at += len as usize;
+        let bytes = raw
+            .get(start..at)
+            .ok_or_else(|| "truncated array".to_string())?;
+        elements.push(array_element(&decode_binary(element, Some(bytes))?));
+    }
+
+    let mut text = String::new();
+    if dims.iter().any(|(_, lower)| *lower != 1) {
+        for (len, lower) in &dims {
+            text.push_str(&format!("[{}:{}]", lower, lower + len - 1));
+        }
+        text.push('=');
+    }
+    let mut elements = elements.into_iter();
+    write_dimension(&dims, &mut elements, &mut text);
+    if dims.is_empty() {
+        text.push_str("{}");
+    }
+    Ok(text)
+}
+
+fn write_dimension(
+    dims: &[(i32, i32)],
+    elements: &mut impl Iterator<Item = String>,
+    text: &mut String,
+) {
+    let Some(((len, _), inner)) = dims.split_first() else {
+        return;
+    };
+    text.push('{');
+    for i in 0..*len {
+        if i > 0 {
+            text.push(',');
+        }
+        if inner.is_empty() {
+            text.push_str(&elements.next().unwrap_or_default());
+        } else {
+            write_dimension(inner, elements, text);
+        }
+    }
+    text.push('}');
+}
+
+/// An array element as PostgreSQL writes it, quoted when it would be ambiguous
+fn array_element(value: &Value) -> String {
+    let text = match value {
+        Value::Null => return "NULL".to_string(),
+        Value::Bool(b) => return if *b { "t" } else { "f" }.to_string(),
+        Value::Int(_) | Value::Float(_) => return value.to_string(),
+        Value::Bytes(bytes) => {
+            let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("\\x{}", hex)
+        }
+        Value::Text(s) => s.clone(),
+    };
+    let quote = text.is_empty()
+        || text.eq_ignore_ascii_case("null")
+        || text
+            .chars()
+            .any(|c| matches!(c, '{' | '}' | ',' | '"' | '\\') || c.is_whitespace());
+    if quote {
+        format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        text
+    }
+}
+
+/// Decode a value received in the text format
+#[cfg_attr(not(feature = "with-sqlx"), allow(dead_code))]
+pub(crate) fn decode_text(oid: u32, raw: Option<&str>) -> Value {
+    let Some(raw) = raw else {
+        return Value::Null;
+    };
+
+    match oid {
+        BOOL => Value::Bool(raw == "t"),
+        INT2 | INT4 | INT8 | OID => raw
+            .parse()
+            .map_or_else(|_| Value::Text(raw.to_string()), Value::Int),
+        FLOAT4 | FLOAT8 => raw
+            .parse()
+            .map_or_else(|_| Value::Text(raw.to_string()), Value::Float),
+        _ => Value::Text(raw.to_string()),
+    }
+}
+
+/// Encode a parameter in the binary format of the type PostgreSQL inferred for it
+///
+/// Returns true if the value is NULL and nothing was written.
+pub(crate) fn encode_binary(
+    value: &Value,
+    oid: u32,
+    type_name: &str,
+    out: &mut Vec<u8>,
+) -> Result<bool, String> {
+    let mismatch = || {
+        format!(
+            "can't bind {} value {} to a parameter of type {}",
+            value.kind(),
+            value,
+            type_name
+        )
+    };
+
+    match (value, oid) {
+        (Value::Null, _) => return Ok(true),
+        (Value::Bool(b), BOOL) => out.push(u8::from(*b)),
+        (Value::Int(i), INT2) => {
+            out.extend(i16::try_from(*i).map_err(|_| mismatch())?.to_be_bytes())
+        }
+        (Value::Int(i), INT4) => {
+            out.extend(i32::try_from(*i).map_err(|_| mismatch())?.to_be_bytes())
+        }
+        (Value::Int(i), INT8) => out.extend(i.to_be_bytes()),
+        (Value::Int(i), OID) => {
+            out.extend(u32::try_from(*i).map_err(|_| mismatch())?.to_be_bytes())
+        }
+        (Value::Int(i), FLOAT4) => out.extend((*i as f32).to_be_bytes()),
+        (Value::Int(i), FLOAT8) => out.extend((*i as f64).to_be_bytes()),
+        (Value::Int(i), NUMERIC) => encode_numeric(&i.to_string(), out)?,
+        (Value::Float(x), FLOAT4) => out.extend((*x as f32).to_be_bytes()),
+        (Value::Float(x), FLOAT8) => out.extend(x.to_be_bytes()),
+        (Value::Float(x), NUMERIC) => encode_numeric(&x.to_string(), out)?,
+        (Value::Bytes(bytes), BYTEA) => out.extend_from_slice(bytes),
+        (Value::Text(s), BYTEA) => out.extend_from_slice(s.as_bytes()),
+        (Value::Text(s), NUMERIC) => encode_numeric(s, out)?,
+        (Value::Text(s), JSONB) => {
+            out.push(1);
+            out.extend_from_slice(s.as_bytes());
+        }
+        (Value::Text(s), UUID) => {
+            let uuid = uuid::Uuid::parse_str(s).map_err(|_| mismatch())?;
+            out.extend_from_slice(uuid.as_bytes());
+        }
+        (Value::Text(s), BOOL | INT2 | INT4 | INT8 | OID | FLOAT4 | FLOAT8) => {
+            let parsed = match oid {
+                BOOL => match s.to_lowercase().as_str() {
+                    "t" | "true" | "1" => Value::Bool(true),
+                    "f" | "false" | "0" => Value::Bool(false),
+                    _ => return Err(mismatch()),
+                },
+                FLOAT4 | FLOAT8 => Value::Float(s.parse().map_err(|_| mismatch())?),
+                _ => Value::Int(s.parse().map_err(|_| mismatch())?),
+            };
+            return encode_binary(&parsed, oid, type_name, out);
+        }
+        (Value::Text(s), oid) if is_textual(oid) => out.extend_from_slice(s.as_bytes()),
+        (Value::Bytes(bytes), oid) if is_textual(oid) => out.extend_from_slice(bytes),
+        (Value::Bool(_) | Value::Int(_) | Value::Float(_), oid) if is_textual(oid) => {
+            out.extend_from_slice(value.to_string().as_bytes())
+        }
+        _ => return Err(mismatch()),
+    }
+
+    Ok(false)
+}
+
+fn fixed<const N: usize>(raw: &[u8]) -> Result<[u8; N], String> {
+    raw.try_into()
+        .map_err(|_| format!("expected {} bytes, got {}", N, raw.len()))
+}
+
+/// Read an `i32` and move past it
+fn read_i32(raw: &[u8], at: &mut usize) -> Result<i32, String> {
+    let bytes = raw
+        .get(*at..*at + 4)
+        .ok_or_else(|| "truncated array".to_string())?;
+    *at += 4;
+    Ok(i32::from_be_bytes(fixed(bytes)?))
+}
+
+fn read_u16(raw: &[u8], at: usize) -> Result<u16, String> {
+    raw.get(at..at + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| "truncated numeric".to_string())
+}
+
+/// Decode a NUMERIC into its decimal text form
+fn decode_numeric(raw: &[u8]) -> Result<String, String> {
+    let ndigits = read_u16(raw, 0)? as usize;
+    let weight = read_u16(raw, 2)? as i16 as i64;
+    let sign = read_u16(raw, 4)?;
+    let dscale = read_u16(raw, 6)? as usize;
+    let digits = (0..ndigits)
+        .map(|i| read_u16(raw, 8 + i * 2))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if sign == NUMERIC_NAN {
+        return Ok("NaN".to_string());
+    }
+
+    // Each digit holds four decimal digits, the first one is multiplied by 10000^weight
+    let digit = |i: i64| {
+        usize::try_from(i)
+            .ok()
+            .and_then(|i| digits.get(i))
+            .copied()
+            .unwrap_or(0)
+    };
+
+    let mut text = String::new();
+    if sign == NUMERIC_NEG {
+        text.push('-');
+    }
+
+    if weight < 0 {
+        text.push('0');
+    } else {
+        text.push_str(&digit(0).to_string());
+        for i in 1..=weight {
+            text.push_str(&format!("{:04}", digit(i)));
+        }
+    }
+
+    if dscale > 0 {
+        let mut fraction = String::new();
+        let mut i = weight + 1;
+        while fraction.len() < dscale {
+            fraction.push_str(&format!("{:04}", digit(i)));
+            i += 1;
+        }
+        fraction.truncate(dscale);
+        text.push('.');
+        text.push_str(&fraction);
+    }
+
+    Ok(text)
+}
+
+/// Encode a decimal string such as `-12.50` as a NUMERIC
+fn encode_numeric(text: &str, out: &mut Vec<u8>) -> Result<(), String> {
+    let invalid = || format!("invalid numeric value {}", text);
+
+    if text.eq_ignore_ascii_case("nan") {
+        out.extend([0, 0, 0, 0]);
+        out.extend(NUMERIC_NAN.to_be_bytes());
+        out.extend([0, 0]);
+        return Ok(());
+    }
+
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+
+    if (integer.is_empty() && fraction.is_empty())
+        || !integer
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+
+    let integer = integer.trim_start_matches('0');
+    let dscale = fraction.len();
+
+    // Pad both parts to whole groups of four decimal digits
+    let int_groups = integer.len().div_ceil(4);
+    let padded = format!(
+        "{:0>width$}{:0<frac$}",
+        integer,
+        fraction,
+        width = int_groups * 4,
+        frac = dscale.div_ceil(4) * 4
+    );
+    let mut groups: Vec<u16> = padded
+        .as_bytes()
+        .chunks(4)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap().parse().unwrap())
+        .collect();
+    let mut weight = int_groups as i64 - 1;
+
+    while groups.first() == Some(&0) {
+        groups.remove(0);
+        weight -= 1;
+    }
+    while groups.last() == Some(&0) {
+        groups.pop();
+    }
+    if groups.is_empty() {
+        weight = 0;
+    }
+
+    let ndigits = u16::try_from(groups.len()).map_err(|_| invalid())?;
+    let weight = i16::try_from(weight).map_err(|_| invalid())?;
+    let dscale = u16::try_from(dscale).map_err(|_| invalid())?;
+    let sign: u16 = if negative && !groups.is_empty() {
+        NUMERIC_NEG
+    } else {
+        0
+    };
+
+    out.extend(ndigits.to_be_bytes());
+    out.extend(weight.to_be_bytes());
+    out.extend(sign.to_be_bytes());
+    out.extend(dscale.to_be_bytes());
+    for group in groups {
+        out.extend(group.to_be_bytes());
+    }
+
+    Ok(())
+}
+
+/// Convert days since 1970-01-01 into a (year, month, day) civil date
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Format days since 2000-01-01 as `YYYY-MM-DD`
+fn format_date(days: i32) -> String {
+    match days {
+        i32::MAX => "infinity".to_string(),
+        i32::MIN => "-infinity".to_string(),
+        days => {
+            let (year, month, day) = civil_from_days(i64::from(days) + PG_EPOCH_DAYS);
+            format!("{:04}-{:02}-{:02}", year, month, day)
+        }
+    }
+}
+
+/// Format microseconds since midnight as `HH:MM:SS[.ffffff]`
+fn format_time(micros: i64) -> String {
+    let seconds = micros.div_euclid(1_000_000);
+    let fraction = micros.rem_euclid(1_000_000);
+    let mut text = format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    if fraction > 0 {
+        text.push_str(format!(".{:06}", fraction).trim_end_matches('0'));
+    }
+    text
+}
+
+/// Format an interval like PostgreSQL with the default `IntervalStyle`, such as
+/// `1 year 2 mons -3 days +04:05:06`
+fn format_interval(micros: i64, days: i32, months: i32) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    let mut negative = false;
+    let mut field = |value: i64, unit: &str, parts: &mut Vec<String>| {
+        if value == 0 {
+            return;
+        }
+        // A positive field after a negative one is signed, as signs don't carry over
+        let sign = if negative && value > 0 { "+" } else { "" };
+        let plural = if value == 1 { "" } else { "s" };
+        parts.push(format!("{}{} {}{}", sign, value, unit, plural));
+        negative = value < 0;
+    };
+    field(i64::from(months / 12), "year", &mut parts);
+    field(i64::from(months % 12), "mon", &mut parts);
+    field(i64::from(days), "day", &mut parts);
+
+    if micros != 0 || parts.is_empty() {
+        let sign = match (micros < 0, negative) {
+            (true, _) => "-",
+            (false, true) => "+",
+            (false, false) => "",
+        };
+        parts.push(format!("{}{}", sign, format_time(micros.abs())));
+    }
+    parts.join(" ")
+}
+
+/// Format microseconds since 2000-01-01 00:00:00 as `YYYY-MM-DD HH:MM:SS[.ffffff]`
+fn format_timestamp(micros: i64) -> String {
+    match micros {
+        i64::MAX => "infinity".to_string(),
+        i64::MIN => "-infinity".to_string(),
+        micros => {
+            let days = micros.div_euclid(86_400_000_000);
+            let (year, month, day) = civil_from_days(days + PG_EPOCH_DAYS);
+            format!(
+                "{:04}-{:02}-{:02} {}",
+                year,
+                month,
+                day,
+                format_time(micros.rem_euclid(86_400_000_000))
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numeric_round_trip(text: &str) -> String {
+        let mut out = Vec::new();
+        encode_numeric(text, &mut out).unwrap();
+        decode_numeric(&out).unwrap()
+    }
+
+    #[test]
+    fn test_numeric_round_trip() {
+        assert_eq!(numeric_round_trip("0"), "0");
+        assert_eq!(numeric_round_trip("12345.678"), "12345.678");
+        assert_eq!(numeric_round_trip("-0.0001"), "-0.0001");
+        assert_eq!(numeric_round_trip("100000000"), "100000000");
+        assert_eq!(numeric_round_trip("42.50"), "42.50");
+        assert!(encode_numeric("1e5", &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_format_dates() {
+        assert_eq!(format_date(0), "2000-01-01");
+        assert_eq!(format_date(-1), "1999-12-31");
+        assert_eq!(format_date(8825), "2024-02-29");
+        assert_eq!(
+            format_timestamp(8825 * 86_400_000_000 + 3_723_500_000),
+            "2024-02-29 01:02:03.5"
+        );
+        assert_eq!(format_time(45_296_000_001), "12:34:56.000001");
+    }
+
+    #[test]
+    fn test_format_interval() {
+        assert_eq!(format_interval(0, 0, 0), "00:00:00");
+        assert_eq!(
+            format_interval(3_600_000_000, 1, 14),
+            "1 year 2 mons 1 day 01:00:00"
+        );
+        assert_eq!(format_interval(-90_000_000, 0, 0), "-00:01:30");
+        assert_eq!(format_interval(0, -2, 1), "1 mon -2 days");
+        assert_eq!(format_interval(7_200_000_000, -1, 0), "-1 days +02:00:00");
+    }
+
+    #[test]
+    fn test_decode_array() {
+        // int4[] with the elements 1 and NULL
+        let mut raw = Vec::new();
+        for n in [1i32, 1, INT4 as i32, 2, 1, 4, 1] {
+            raw.extend(n.to_be_bytes());
+        }
+        raw.extend((-1i32).to_be_bytes());
+        assert_eq!(
+            decode_binary(1007, Some(&raw)).unwrap(),
+            Value::Text("{1,NULL}".into())
+        );
+        assert!(decode_binary(1007, Some(&raw[..10])).is_err());
+        assert!(decode_binary(3904, Some(b"abc")).is_err());
+    }
+}
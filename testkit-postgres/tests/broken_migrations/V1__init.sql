@@ -0,0 +1,4 @@
+CREATE TABLE users (id INT PRIMARY KEY);
+
+INSERT INTO users (id) VALUES (1);
+INSERT INTO missing (id) VALUES (1);
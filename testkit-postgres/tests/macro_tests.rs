@@ -0,0 +1,81 @@
+#![cfg(feature = "with-tokio-postgres")]
+
+use testkit_core::TestContext;
+use testkit_core::macros as testkit;
+use testkit_postgres::{PostgresBackend, PostgresConnection, PostgresError};
+
+async fn create_users(conn: &mut PostgresConnection) -> Result<(), PostgresError> {
+    conn.client()
+        .execute(
+            "CREATE TABLE users (id SERIAL PRIMARY KEY, email TEXT NOT NULL)",
+            &[],
+        )
+        .await?;
+    Ok(())
+}
+
+#[testkit::test(setup = create_users)]
+async fn test_macro_runs_setup(ctx: TestContext<PostgresBackend>) -> Result<(), PostgresError> {
+    let conn = ctx.db.acquire_connection().await?;
+    conn.client()
+        .execute("INSERT INTO users (email) VALUES ($1)", &[&"a@example.com"])
+        .await?;
+
+    let rows = conn.client().query("SELECT email FROM users", &[]).await?;
+    assert_eq!(rows.len(), 1);
+    Ok(())
+}
+
+async fn create_profiles(conn: &mut PostgresConnection) -> Result<(), PostgresError> {
+    conn.client()
+        .batch_execute(
+            "CREATE TABLE users (id SERIAL PRIMARY KEY, email TEXT NOT NULL, joined_on DATE, profile JSONB)",
+        )
+        .await?;
+    Ok(())
+}
+
+#[testkit::test(setup = create_profiles, fixtures = ["tests/fixtures/users.yaml"])]
+async fn test_macro_loads_fixtures(ctx: TestContext<PostgresBackend>) -> Result<(), PostgresError> {
+    let conn = ctx.db.acquire_connection().await?;
+    let rows = conn
+        .client()
+        .query("SELECT email FROM users ORDER BY id", &[])
+        .await?;
+    let emails: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
+    assert_eq!(emails, ["alice@example.com", "bob@example.com"]);
+
+    // The sequence was moved past the fixture ids
+    conn.client()
+        .execute(
+            "INSERT INTO users (email) VALUES ('carol@example.com')",
+            &[],
+        )
+        .await?;
+    Ok(())
+}
+
+#[testkit::test(backend = "postgres")]
+async fn test_macro_with_named_backend(ctx: TestContext) {
+    let conn = ctx.db.acquire_connection().await.unwrap();
+    let rows = conn
+        .client()
+        .query("SELECT current_database()", &[])
+        .await
+        .unwrap();
+
+    let name: String = rows[0].get(0);
+    assert_eq!(name, ctx.db.name().as_str());
+}
+
+#[testkit::test]
+async fn test_macro_drops_database_after_test(ctx: TestContext<PostgresBackend>) {
+    // The database stays alive for as long as any handle to it exists
+    let db = ctx.db.clone();
+    drop(ctx);
+
+    let conn = db.acquire_connection().await.unwrap();
+    let rows = conn.client().query("SELECT 1", &[]).await.unwrap();
+    assert_eq!(rows.len(), 1);
+    assert!(!db.is_kept());
+}
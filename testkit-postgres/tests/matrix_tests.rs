@@ -0,0 +1,1150 @@
+use testkit_core::macros as testkit;
+use testkit_core::{
+    CsvOptions, DatabaseTransaction, Explain, Factory, Fixtures, Introspect, IsolationLevel,
+    Migration, MigrationError, Migrator, ScriptError, SqlDialect, TestConnection, TestContext,
+    TransactionManager, TransactionalConnection, TxOptions, Value,
+};
+
+type TestResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+async fn create_users<C: TestConnection>(conn: &mut C) -> Result<(), C::Error> {
+    conn.batch_execute("CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR(50) NOT NULL)")
+        .await
+}
+
+#[testkit::matrix(setup = create_users)]
+async fn test_matrix_batch_execute(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+
+    conn.batch_execute(
+        "INSERT INTO users (id, name) VALUES (1, 'Alice'); INSERT INTO users (id, name) VALUES (2, 'Bob')",
+    )
+    .await?;
+
+    // The primary key is enforced on every backend
+    let duplicate = conn
+        .batch_execute("INSERT INTO users (id, name) VALUES (1, 'Carol')")
+        .await;
+    assert!(duplicate.is_err());
+
+    Ok(())
+}
+
+#[testkit::matrix]
+async fn test_matrix_dialect(ctx: TestContext<_>) {
+    let conn = ctx.db.acquire_connection().await.unwrap();
+    assert_eq!(conn.dialect(), SqlDialect::Postgres);
+}
+
+#[testkit::matrix(setup = create_users)]
+async fn test_matrix_query_params(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+    let dialect = conn.dialect();
+
+    let insert = format!(
+        "INSERT INTO users (id, name) VALUES ({}, {})",
+        dialect.placeholder(1),
+        dialect.placeholder(2)
+    );
+    assert_eq!(conn.execute(&insert, &[1.into(), "Alice".into()]).await?, 1);
+    assert_eq!(conn.execute(&insert, &[2.into(), "Bob".into()]).await?, 1);
+
+    let select = format!(
+        "SELECT id, name FROM users WHERE id >= {} ORDER BY id",
+        dialect.placeholder(1)
+    );
+    let rows = conn.query(&select, &[Value::Int(1)]).await?;
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].columns(), ["id", "name"]);
+    assert_eq!(rows[0].get::<i32>("id"), 1);
+    assert_eq!(rows[1].get::<String>(1), "Bob");
+
+    let updated = conn
+        .execute(
+            &format!("UPDATE users SET name = {}", dialect.placeholder(1)),
+            &["Carol".into()],
+        )
+        .await?;
+    assert_eq!(updated, 2);
+
+    Ok(())
+}
+
+#[testkit::matrix]
+async fn test_matrix_query_values(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+
+    let rows = conn
+        .query(
+            "SELECT CAST(NULL AS CHAR(1)) AS nothing, 42 AS answer, 1.5 AS ratio, 'hi' AS greeting",
+            &[],
+        )
+        .await?;
+    let row = &rows[0];
+    assert_eq!(row.value("nothing"), Some(&Value::Null));
+    assert_eq!(row.get::<i64>("answer"), 42);
+    assert_eq!(row.get::<f64>("ratio"), 1.5);
+    assert_eq!(row.get::<String>("greeting"), "hi");
+
+    let empty = conn.query("SELECT 1 AS one WHERE 1 = 0", &[]).await?;
+    assert!(empty.is_empty());
+
+    Ok(())
+}
+
+#[testkit::matrix]
+async fn test_matrix_postgres_types(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+    conn.batch_execute(
+        "CREATE TYPE mood AS ENUM ('happy', 'sad');
+         CREATE DOMAIN positive AS INT CHECK (VALUE > 0)",
+    )
+    .await?;
+
+    let rows = conn
+        .query(
+            "SELECT INTERVAL '1 year 2 months 3 days 04:05:06.5' AS long,
+                    INTERVAL '-1 day 02:00' AS mixed,
+                    ARRAY[1, NULL, 3]::int4[] AS numbers,
+                    ARRAY[['a', 'b c'], ['', NULL]] AS words,
+                    'sad'::mood AS mood,
+                    5::positive AS positive",
+            &[],
+        )
+        .await?;
+    let row = &rows[0];
+    assert_eq!(row.get::<String>("long"), "1 year 2 mons 3 days 04:05:06.5");
+    assert_eq!(row.get::<String>("mixed"), "-1 days +02:00:00");
+    assert_eq!(row.get::<String>("numbers"), "{1,NULL,3}");
+    assert_eq!(row.get::<String>("words"), r#"{{a,"b c"},{"",NULL}}"#);
+    assert_eq!(row.get::<String>("mood"), "sad");
+    assert_eq!(row.get::<i64>("positive"), 5);
+
+    // Enums bind from text
+    let rows = conn
+        .query("SELECT $1::mood = 'happy' AS happy", &["happy".into()])
+        .await?;
+    assert!(rows[0].get::<bool>("happy"));
+
+    // Types without a known binary format fail instead of decoding to garbage
+    let error = conn
+        .query("SELECT int4range(1, 5) AS span", &[])
+        .await
+        .expect_err("ranges aren't decoded");
+    assert!(
+        error.to_string().contains("cast the column to text"),
+        "{}",
+        error
+    );
+    let rows = conn
+        .query("SELECT int4range(1, 5)::text AS span", &[])
+        .await?;
+    assert_eq!(rows[0].get::<String>("span"), "[1,5)");
+
+    Ok(())
+}
+
+#[testkit::matrix(setup = create_users)]
+async fn test_matrix_transactions(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+    let count = || async {
+        let rows = conn.query("SELECT COUNT(*) AS n FROM users", &[]).await?;
+        Ok::<i64, Box<dyn std::error::Error + Send + Sync>>(rows[0].get("n"))
+    };
+
+    // Statements inside a rolled back transaction leave no trace
+    let mut tx = ctx.db.begin_transaction().await?;
+    tx.batch_execute("INSERT INTO users (id, name) VALUES (1, 'Alice')")
+        .await?;
+    let inside = tx.query("SELECT name FROM users", &[]).await?;
+    assert_eq!(inside.len(), 1);
+    tx.rollback().await?;
+    assert_eq!(count().await?, 0);
+
+    // A finished transaction can't be used again
+    assert!(tx.batch_execute("SELECT 1").await.is_err());
+    assert!(tx.commit().await.is_err());
+
+    let mut tx = ctx.db.begin_transaction().await?;
+    tx.batch_execute("INSERT INTO users (id, name) VALUES (2, 'Bob')")
+        .await?;
+    tx.commit().await?;
+    assert_eq!(count().await?, 1);
+
+    Ok(())
+}
+
+#[testkit::matrix(setup = create_users)]
+async fn test_matrix_transaction_rolls_back_on_drop(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+
+    // Bail out of a transaction without finishing it, as `?` would
+    {
+        let tx = conn.begin_transaction().await?;
+        tx.batch_execute("INSERT INTO users (id, name) VALUES (1, 'Alice')")
+            .await?;
+    }
+
+    // The connection is usable right away and the row never made it in
+    let rows = conn.query("SELECT COUNT(*) AS n FROM users", &[]).await?;
+    assert_eq!(rows[0].get::<i64>("n"), 0);
+    tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        conn.batch_execute("INSERT INTO users (id, name) VALUES (1, 'Alice')"),
+    )
+    .await??;
+
+    // Work started right after the drop isn't rolled back with it
+    {
+        let tx = conn.begin_transaction().await?;
+        tx.batch_execute("INSERT INTO users (id, name) VALUES (2, 'Bob')")
+            .await?;
+    }
+    let mut tx = conn.begin_transaction().await?;
+    tx.batch_execute("INSERT INTO users (id, name) VALUES (3, 'Carol')")
+        .await?;
+    tx.commit().await?;
+
+    // A connection released with a dropped transaction goes back to the pool clean
+    {
+        let tx = conn.begin_transaction().await?;
+        tx.batch_execute("INSERT INTO users (id, name) VALUES (4, 'Dave')")
+            .await?;
+    }
+    drop(conn);
+    let conn = ctx.db.acquire_connection().await?;
+    let rows = conn.query("SELECT id FROM users ORDER BY id", &[]).await?;
+    let ids: Vec<i32> = rows.iter().map(|row| row.get("id")).collect();
+    assert_eq!(ids, vec![1, 3]);
+
+    Ok(())
+}
+
+#[testkit::matrix(setup = create_users)]
+async fn test_matrix_transaction_options(ctx: TestContext) -> TestResult {
+    let options = TxOptions {
+        isolation: Some(IsolationLevel::Serializable),
+        read_only: true,
+        deferrable: false,
+    };
+    let mut tx = ctx.db.begin_transaction_with(options).await?;
+    assert_eq!(tx.options(), &options);
+
+    if tx.dialect() == SqlDialect::Postgres {
+        let rows = tx
+            .query(
+                "SELECT current_setting('transaction_isolation') AS isolation",
+                &[],
+            )
+            .await?;
+        assert_eq!(rows[0].get::<String>("isolation"), "serializable");
+    }
+
+    // Writes are rejected in a read-only transaction
+    let insert = tx
+        .batch_execute("INSERT INTO users (id, name) VALUES (1, 'Alice')")
+        .await;
+    assert!(insert.is_err());
+    tx.rollback().await.ok();
+
+    // The default options leave the transaction writable
+    let mut tx = ctx.db.begin_transaction().await?;
+    assert_eq!(tx.options(), &TxOptions::default());
+    tx.batch_execute("INSERT INTO users (id, name) VALUES (1, 'Alice')")
+        .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+#[testkit::matrix(migrations = "tests/migrations")]
+async fn test_matrix_migrations(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+
+    let rows = conn.query("SELECT name, email FROM users", &[]).await?;
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].get::<String>("email"), "alice@example.com");
+
+    // Versions and checksums of the applied migrations are recorded
+    let migrator = Migrator::from_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/migrations"))?;
+    let applied = migrator.applied(&conn).await?;
+    assert_eq!(applied.len(), 2);
+    assert_eq!(applied[1].description, "add email");
+    assert_eq!(applied[1].checksum, migrator.migrations()[1].checksum());
+
+    // Applied migrations are skipped and the down migrations revert them
+    assert!(migrator.run(&conn).await?.is_empty());
+    assert_eq!(migrator.undo(&conn, 0).await?, [2, 1]);
+    assert!(conn.query("SELECT * FROM users", &[]).await.is_err());
+
+    // The statements of a migration share one session
+    let staged = Migrator::new(vec![Migration::new(
+        3,
+        "staging",
+        "CREATE TEMPORARY TABLE staging (id INT);
+         INSERT INTO staging (id) VALUES (7);
+         CREATE TABLE kept AS SELECT id FROM staging;
+         DROP TABLE staging;",
+    )])?;
+    assert_eq!(staged.run(&conn).await?, [3]);
+    let rows = conn.query("SELECT id FROM kept", &[]).await?;
+    assert_eq!(rows[0].try_get::<i64>(0)?, 7);
+
+    Ok(())
+}
+
+#[testkit::matrix]
+async fn test_matrix_migration_errors(ctx: TestContext) -> TestResult {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/broken_migrations");
+    let err = ctx.db.migrate(&Migrator::from_dir(dir)?).await.unwrap_err();
+
+    // The error points at the file and the statement that failed
+    let message = err.to_string();
+    assert!(message.contains("V1__init.sql"), "{}", message);
+    assert!(message.contains("line 4"), "{}", message);
+    assert!(message.contains("INSERT INTO missing"), "{}", message);
+
+    // On PostgreSQL the failed migration is rolled back as a whole and can be fixed
+    let conn = ctx.db.acquire_connection().await?;
+    if conn.dialect() == SqlDialect::Postgres {
+        assert!(conn.query("SELECT * FROM users", &[]).await.is_err());
+        let applied = Migrator::default().applied(&conn).await?;
+        assert!(applied.is_empty());
+    }
+
+    // A migration changed after it was applied is rejected
+    Migrator::new(vec![Migration::new(1, "init", "CREATE TABLE t (id INT)")])?
+        .run(&conn)
+        .await?;
+    let changed = Migrator::new(vec![Migration::new(
+        1,
+        "init",
+        "CREATE TABLE t (id BIGINT)",
+    )])?;
+    assert!(matches!(
+        changed.run(&conn).await,
+        Err(MigrationError::ChecksumMismatch { version: 1, .. })
+    ));
+
+    Ok(())
+}
+
+#[testkit::matrix]
+async fn test_matrix_execute_script(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+    let script = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scripts/seed.sql");
+    conn.execute_script(&script).await?;
+
+    // The COPY rows are loaded with their escapes and NULLs
+    let rows = conn
+        .query(
+            "SELECT owner, opened_on::text FROM accounts ORDER BY id",
+            &[],
+        )
+        .await?;
+    let owners: Vec<String> = rows.iter().map(|r| r.get(0)).collect();
+    assert_eq!(owners, ["O'Neil", "Tab\tSeparated", "Line\nBreak"]);
+    assert_eq!(rows[1].get::<Option<String>>(1), None);
+
+    // The function body and the DO block ran as single statements
+    let rows = conn.query("SELECT total_balance()::text", &[]).await?;
+    assert_eq!(rows[0].get::<String>(0), "31.75");
+
+    // A failing statement is reported with its line
+    let err = conn
+        .execute_script(
+            "INSERT INTO accounts (id, owner) VALUES (4, 'D');\n\nINSERT INTO missing VALUES (1);",
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, ScriptError::Statement { line: 3, .. }),
+        "{}",
+        err
+    );
+    assert!(err.to_string().contains("INSERT INTO missing"), "{}", err);
+
+    // Session state set up by one statement is there for the next ones
+    conn.execute_script(
+        "SET application_name = 'seed';
+         CREATE TEMP TABLE staging (id INT, owner TEXT);
+         INSERT INTO staging VALUES (5, current_setting('application_name'));
+         INSERT INTO accounts (id, owner) SELECT id, owner FROM staging;",
+    )
+    .await?;
+    let rows = conn
+        .query("SELECT owner FROM accounts WHERE id = 5", &[])
+        .await?;
+    assert_eq!(rows[0].get::<String>(0), "seed");
+
+    Ok(())
+}
+
+async fn create_shop<C: TestConnection>(conn: &C) -> Result<(), C::Error> {
+    conn.batch_execute(
+        "CREATE TABLE users (id SERIAL PRIMARY KEY, email TEXT NOT NULL, joined_on DATE, profile JSONB);
+         CREATE TABLE orders (id SERIAL PRIMARY KEY, user_id INT NOT NULL REFERENCES users (id), total NUMERIC(10, 2))",
+    )
+    .await
+}
+
+#[testkit::matrix]
+async fn test_matrix_fixtures(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+    create_shop(&conn).await?;
+
+    // Orders are listed first but reference users, so users are inserted first
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let fixtures = Fixtures::from_files([dir.join("orders.json"), dir.join("users.yaml")])?;
+    ctx.db.load_fixtures(&fixtures).await?;
+
+    let rows = conn
+        .query(
+            "SELECT joined_on::text, profile->>'theme' FROM users ORDER BY id",
+            &[],
+        )
+        .await?;
+    assert_eq!(rows[0].get::<String>(0), "2024-01-31");
+    assert_eq!(rows[0].get::<String>(1), "dark");
+    assert_eq!(rows[1].get::<Option<String>>(0), None);
+
+    let rows = conn
+        .query("SELECT SUM(total)::text FROM orders", &[])
+        .await?;
+    assert_eq!(rows[0].get::<String>(0), "130.49");
+
+    // The sequences continue after the fixture ids
+    let rows = conn
+        .query(
+            "INSERT INTO users (email) VALUES ('carol@example.com') RETURNING id",
+            &[],
+        )
+        .await?;
+    assert_eq!(rows[0].get::<i64>(0), 3);
+    let rows = conn
+        .query("INSERT INTO orders (user_id) VALUES (3) RETURNING id", &[])
+        .await?;
+    assert_eq!(rows[0].get::<i64>(0), 12);
+
+    Ok(())
+}
+
+#[testkit::matrix]
+async fn test_matrix_load_csv(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+    conn.batch_execute(
+        "CREATE TABLE events (id BIGINT PRIMARY KEY, kind TEXT NOT NULL, amount NUMERIC, note TEXT)",
+    )
+    .await?;
+
+    // The header names the columns in an order different from the table
+    let path = std::env::temp_dir().join(format!("testkit_events_{}.csv", uuid::Uuid::new_v4()));
+    let mut csv = String::from("kind,id,note\n");
+    for id in 1..=50_000 {
+        let note = if id % 10 == 0 {
+            "NULL"
+        } else {
+            "\"a, \"\"quoted\"\" note\""
+        };
+        csv.push_str(&format!("click,{},{}\n", id, note));
+    }
+    std::fs::write(&path, csv)?;
+
+    let loaded = conn
+        .load_csv("events", &path, CsvOptions::default().with_null("NULL"))
+        .await?;
+    std::fs::remove_file(&path)?;
+    assert_eq!(loaded, 50_000);
+
+    let rows = conn
+        .query(
+            "SELECT COUNT(*), COUNT(note), COUNT(amount), MAX(note) FROM events",
+            &[],
+        )
+        .await?;
+    assert_eq!(rows[0].get::<i64>(0), 50_000);
+    assert_eq!(rows[0].get::<i64>(1), 45_000);
+    assert_eq!(rows[0].get::<i64>(2), 0);
+    assert_eq!(rows[0].get::<String>(3), "a, \"quoted\" note");
+
+    // Files without a header fill the columns of the table in order
+    let path = std::env::temp_dir().join(format!("testkit_events_{}.csv", uuid::Uuid::new_v4()));
+    std::fs::write(&path, "60001;view;1.5;\n60002;view;;late\n")?;
+    let loaded = conn
+        .load_csv(
+            "events",
+            &path,
+            CsvOptions::default().without_header().with_delimiter(';'),
+        )
+        .await?;
+    std::fs::remove_file(&path)?;
+    assert_eq!(loaded, 2);
+
+    let rows = conn
+        .query(
+            "SELECT amount::text, note FROM events WHERE kind = 'view' ORDER BY id",
+            &[],
+        )
+        .await?;
+    assert_eq!(rows[0].get::<String>(0), "1.5");
+    assert_eq!(rows[0].get::<Option<String>>(1), None);
+    assert_eq!(rows[1].get::<Option<String>>(0), None);
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, testkit::Factory)]
+#[factory(table = "members")]
+struct Member {
+    #[factory(generated)]
+    id: i32,
+    #[factory(sequence = "member{n}@example.com")]
+    email: String,
+    #[factory(default = "Alice")]
+    name: String,
+    admin: bool,
+}
+
+#[derive(Debug, Clone, testkit::Factory)]
+struct Purchase {
+    #[factory(generated)]
+    id: i32,
+    #[factory(association = Member)]
+    member_id: i32,
+    #[factory(sequence)]
+    quantity: i64,
+}
+
+#[testkit::matrix]
+async fn test_matrix_factories(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+    conn.batch_execute(
+        "CREATE TABLE members (id SERIAL PRIMARY KEY, email TEXT NOT NULL UNIQUE, name TEXT NOT NULL, admin BOOLEAN NOT NULL);
+         CREATE TABLE purchases (id SERIAL PRIMARY KEY, member_id INT NOT NULL REFERENCES members (id), quantity BIGINT NOT NULL)",
+    )
+    .await?;
+
+    let admins = Factory::<Member>::new()
+        .with(|m| m.admin = true)
+        .create_many(&conn, 10)
+        .await?;
+    assert_eq!(admins.len(), 10);
+    assert_eq!(admins[0].id, 1);
+    assert_eq!(admins[9].id, 10);
+    assert_eq!(admins[9].email, "member10@example.com");
+    assert!(admins.iter().all(|m| m.admin && m.name == "Alice"));
+
+    // A purchase creates the member it belongs to
+    let purchase = Factory::<Purchase>::new().create(&conn).await?;
+    assert_eq!(purchase.member_id, 11);
+    assert_eq!(purchase.quantity, 1);
+
+    let member_id = admins[0].id;
+    let purchases = Factory::<Purchase>::new()
+        .with(move |p| p.member_id = member_id)
+        .create_many(&conn, 2)
+        .await?;
+    assert!(purchases.iter().all(|p| p.member_id == 1));
+    assert_eq!(purchases[1].quantity, 3);
+
+    let rows = conn.query("SELECT COUNT(*) FROM members", &[]).await?;
+    assert_eq!(rows[0].get::<i64>(0), 11);
+
+    Ok(())
+}
+
+#[testkit::matrix]
+async fn test_matrix_fake_data(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+    conn.batch_execute(
+        "CREATE TABLE people (id INT PRIMARY KEY, name TEXT, email TEXT, token CHAR(36), joined_at TIMESTAMP)",
+    )
+    .await?;
+
+    // Every generator of a database starts from its seed
+    let mut fake = ctx.db.fake();
+    assert_eq!(fake.seed(), ctx.db.seed());
+    assert_eq!(fake.email(), ctx.db.fake().email());
+
+    let dialect = conn.dialect();
+    for id in 1..=3 {
+        let values = [
+            Value::from(id),
+            Value::from(fake.name()),
+            Value::from(fake.email()),
+            Value::from(fake.uuid().to_string()),
+            Value::from(fake.timestamp()),
+        ];
+        let literals: Vec<String> = values.iter().map(|v| dialect.literal(v)).collect();
+        conn.execute(
+            &format!("INSERT INTO people VALUES ({})", literals.join(", ")),
+            &[],
+        )
+        .await?;
+    }
+
+    let rows = conn
+        .query(
+            "SELECT COUNT(DISTINCT token) FROM people WHERE joined_at >= '2000-01-01'",
+            &[],
+        )
+        .await?;
+    assert_eq!(rows[0].get::<i64>(0), 3);
+
+    Ok(())
+}
+
+#[testkit::matrix]
+async fn test_matrix_table_snapshot(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+    conn.batch_execute(
+        "CREATE TABLE orders (id INT PRIMARY KEY, customer VARCHAR(50) NOT NULL, quantity INT, placed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP)",
+    )
+    .await?;
+    conn.batch_execute(
+        "INSERT INTO orders (id, customer, quantity) VALUES (2, 'Bob', NULL), (1, 'Alice \"Al\"', 3)",
+    )
+    .await?;
+
+    // The values are rendered the same on every backend, so the backends share a snapshot
+    testkit_core::assert_table_snapshot!(
+        conn,
+        "orders",
+        order_by = "id",
+        redact = ["placed_at"],
+        name = "matrix_orders",
+    );
+
+    Ok(())
+}
+
+#[testkit::matrix]
+async fn test_matrix_schema_snapshot(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+    conn.batch_execute(
+        "CREATE TABLE accounts (id INT PRIMARY KEY, email VARCHAR(100) NOT NULL UNIQUE, score INT DEFAULT 0 CHECK (score >= 0));
+         CREATE TABLE sessions (id INT PRIMARY KEY, account_id INT NOT NULL REFERENCES accounts (id) ON DELETE CASCADE, token VARCHAR(36));
+         CREATE INDEX sessions_token_idx ON sessions (token)",
+    )
+    .await?;
+    if conn.dialect() == SqlDialect::Postgres {
+        conn.execute_script(
+            "CREATE FUNCTION touch() RETURNS trigger AS $$ BEGIN RETURN NEW; END $$ LANGUAGE plpgsql;
+             CREATE TRIGGER sessions_touch BEFORE UPDATE ON sessions FOR EACH ROW EXECUTE FUNCTION touch();",
+        )
+        .await?;
+    }
+
+    // Applied migrations don't show up in the snapshot
+    let migrator = Migrator::new(vec![Migration::new(1, "noop", "SELECT 1")])?;
+    ctx.db.migrate(&migrator).await?;
+
+    testkit_core::assert_schema_snapshot!(ctx);
+
+    Ok(())
+}
+
+#[testkit::matrix]
+async fn test_matrix_introspection(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+    let serial = match conn.dialect() {
+        SqlDialect::Postgres => "SERIAL",
+        SqlDialect::MySql => "INT AUTO_INCREMENT",
+    };
+    conn.batch_execute(&format!(
+        "CREATE TABLE authors (id {} PRIMARY KEY, name VARCHAR(50) NOT NULL, bio TEXT);
+         CREATE TABLE books (id INT PRIMARY KEY, author_id INT NOT NULL, title VARCHAR(100),
+             FOREIGN KEY (author_id) REFERENCES authors (id) ON DELETE CASCADE);
+         CREATE UNIQUE INDEX books_author_title_idx ON books (author_id, title)",
+        serial
+    ))
+    .await?;
+
+    assert_eq!(conn.tables().await?, vec!["authors", "books"]);
+
+    let columns = conn.columns("authors").await?;
+    let names: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["id", "name", "bio"]);
+    assert!(!columns[1].nullable);
+    assert!(columns[2].nullable);
+
+    let foreign_keys = conn.foreign_keys().await?;
+    assert_eq!(foreign_keys.len(), 1);
+    let key = &foreign_keys[0];
+    assert_eq!(key.table, "books");
+    assert_eq!(key.columns, vec!["author_id"]);
+    assert_eq!(key.referenced_table, "authors");
+    assert_eq!(key.referenced_columns, vec!["id"]);
+    assert_eq!(key.on_delete, "CASCADE");
+
+    let indexes = conn.indexes("books").await?;
+    let index = indexes
+        .iter()
+        .find(|i| i.name == "books_author_title_idx")
+        .expect("the unique index");
+    assert_eq!(index.columns, vec!["author_id", "title"]);
+    assert!(index.unique && !index.primary);
+    assert!(indexes.iter().any(|i| i.primary && i.columns == ["id"]));
+
+    let sequences = conn.sequences().await?;
+    assert_eq!(sequences.len(), 1);
+    assert_eq!(sequences[0].table.as_deref(), Some("authors"));
+    assert_eq!(sequences[0].column.as_deref(), Some("id"));
+
+    Ok(())
+}
+
+#[testkit::matrix]
+async fn test_matrix_reset(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+    let serial = match conn.dialect() {
+        SqlDialect::Postgres => "SERIAL",
+        SqlDialect::MySql => "INT AUTO_INCREMENT",
+    };
+    let migrator = Migrator::new(vec![Migration::new(
+        1,
+        "tables",
+        format!(
+            "CREATE TABLE countries (code VARCHAR(2) PRIMARY KEY);
+             CREATE TABLE customers (id {} PRIMARY KEY, country VARCHAR(2) REFERENCES countries (code));
+             CREATE TABLE invoices (id {} PRIMARY KEY, customer_id INT NOT NULL REFERENCES customers (id));
+             INSERT INTO countries (code) VALUES ('de'), ('fr')",
+            serial, serial
+        ),
+    )])?;
+    ctx.db.migrate(&migrator).await?;
+
+    conn.batch_execute(
+        "INSERT INTO customers (country) VALUES ('de'), ('fr');
+         INSERT INTO invoices (customer_id) VALUES (1), (2)",
+    )
+    .await?;
+    let count = |table: &str| format!("SELECT COUNT(*) FROM {}", table);
+
+    // Keeping invoices would leave them without their customers
+    let error = ctx
+        .db
+        .reset(&["countries", "invoices"])
+        .await
+        .expect_err("invoices reference customers");
+    assert!(
+        error
+            .to_string()
+            .contains("the excluded table invoices references customers"),
+        "{}",
+        error
+    );
+    let rows = conn.query(&count("customers"), &[]).await?;
+    assert_eq!(rows[0].try_get::<i64>(0)?, 2);
+
+    ctx.db.reset(&["countries"]).await?;
+
+    for (table, expected) in [("countries", 2), ("customers", 0), ("invoices", 0)] {
+        let rows = conn.query(&count(table), &[]).await?;
+        assert_eq!(rows[0].try_get::<i64>(0)?, expected, "rows in {}", table);
+    }
+
+    // The identity counters start over and the migrations stay recorded
+    conn.batch_execute("INSERT INTO customers (country) VALUES ('fr')")
+        .await?;
+    let rows = conn.query("SELECT id FROM customers", &[]).await?;
+    assert_eq!(rows[0].try_get::<i64>(0)?, 1);
+    assert!(ctx.db.migrate(&migrator).await.is_ok());
+    let rows = conn.query(&count("_testkit_migrations"), &[]).await?;
+    assert_eq!(rows[0].try_get::<i64>(0)?, 1);
+
+    Ok(())
+}
+
+#[testkit::matrix(setup = create_users)]
+async fn test_matrix_statement_capture(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+    ctx.db.clear_queries();
+
+    conn.execute(
+        "INSERT INTO users (id, name) VALUES (1, 'Alice'), (2, 'Bob')",
+        &[],
+    )
+    .await?;
+    conn.query("SELECT name FROM users WHERE id = 2", &[])
+        .await?;
+    assert!(conn.query("SELECT missing FROM users", &[]).await.is_err());
+
+    let queries = ctx.queries();
+    assert_eq!(queries.len(), 3);
+    assert_eq!(queries[0].rows, Some(2));
+    assert_eq!(queries[1].sql, "SELECT name FROM users WHERE id = 2");
+    assert_eq!(queries[1].rows, Some(1));
+    assert!(queries[2].error.is_some());
+
+    // One query per user is flagged, one query for all users is not
+    let names = testkit_core::assert_query_count!(ctx, <= 1, {
+        conn.query("SELECT name FROM users ORDER BY id", &[]).await?
+    });
+    assert_eq!(names.len(), 2);
+    testkit_core::assert_no_n_plus_one!(ctx, {
+        conn.query("SELECT id FROM users", &[]).await?;
+    });
+
+    let before = ctx.queries().len();
+    for id in 1..=3 {
+        conn.query(&format!("SELECT name FROM users WHERE id = {}", id), &[])
+            .await?;
+    }
+    let repeated = testkit_core::n_plus_one(&ctx.queries()[before..], 3);
+    assert_eq!(repeated.len(), 1);
+    assert_eq!(repeated[0].shape, "SELECT name FROM users WHERE id = ?");
+
+    Ok(())
+}
+
+#[testkit::matrix]
+async fn test_matrix_query_plans(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+    conn.batch_execute(
+        "CREATE TABLE members (id INT PRIMARY KEY, email VARCHAR(100) NOT NULL, note VARCHAR(100));
+         CREATE INDEX members_email_idx ON members (email)",
+    )
+    .await?;
+    // Enough rows that an index lookup beats reading the table
+    let (fill, analyze) = match conn.dialect() {
+        SqlDialect::Postgres => (
+            "INSERT INTO members (id, email)
+             SELECT n, 'member' || n || '@example.com' FROM generate_series(1, 1000) n",
+            "ANALYZE members",
+        ),
+        SqlDialect::MySql => (
+            "INSERT INTO members (id, email)
+             WITH RECURSIVE seq (n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 1000)
+             SELECT n, CONCAT('member', n, '@example.com') FROM seq",
+            "ANALYZE TABLE members",
+        ),
+    };
+    conn.batch_execute(fill).await?;
+    conn.query(analyze, &[]).await?;
+
+    let lookup = "SELECT id FROM members WHERE email = 'member42@example.com'";
+    let plan = testkit_core::assert_plan!(conn, lookup, uses_index = "members_email_idx");
+    assert!(plan.full_scans().is_empty(), "{}", plan);
+    testkit_core::assert_no_seq_scan!(conn, lookup);
+
+    let plan = conn
+        .explain("SELECT id FROM members WHERE note = 'vip'")
+        .await?;
+    assert_eq!(plan.full_scans(), vec!["members"], "{}", plan);
+    assert!(!plan.uses_index("members_email_idx"));
+
+    assert!(conn.explain("SELECT missing FROM members").await.is_err());
+
+    Ok(())
+}
+
+#[testkit::matrix]
+async fn test_matrix_capture_changes(ctx: TestContext) -> TestResult {
+    let conn = ctx.db.acquire_connection().await?;
+    conn.batch_execute(
+        "CREATE TABLE wallets (id INT PRIMARY KEY, owner VARCHAR(50) NOT NULL, balance INT NOT NULL);
+         CREATE TABLE ledger (wallet_id INT NOT NULL, amount INT NOT NULL);
+         CREATE TABLE untouched (id INT PRIMARY KEY);
+         INSERT INTO wallets (id, owner, balance) VALUES (1, 'Alice', 100), (2, 'Bob', 50), (3, 'Carol', 0);
+         INSERT INTO untouched (id) VALUES (1)",
+    )
+    .await?;
+
+    let changes = ctx
+        .capture_changes(|conn| async move {
+            conn.batch_execute(
+                "UPDATE wallets SET balance = balance - 30 WHERE id = 1;
+                 UPDATE wallets SET balance = balance + 30 WHERE id = 2;
+                 DELETE FROM wallets WHERE id = 3;
+                 INSERT INTO wallets (id, owner, balance) VALUES (4, 'Dan', 0);
+                 INSERT INTO ledger (wallet_id, amount) VALUES (1, -30), (2, 30)",
+            )
+            .await
+        })
+        .await?;
+
+    assert_eq!(
+        changes.changed_tables(),
+        vec!["ledger", "wallets"],
+        "{}",
+        changes
+    );
+    assert_eq!(changes.table("ledger").unwrap().inserted.len(), 2);
+
+    let wallets = changes.table("wallets").unwrap();
+    assert_eq!(wallets.updated.len(), 2);
+    assert_eq!(wallets.updated[0].changed_columns(), vec!["balance"]);
+    assert_eq!(wallets.updated[0].after.try_get::<i64>("balance")?, 70);
+    assert_eq!(wallets.inserted[0].try_get::<String>("owner")?, "Dan");
+    assert_eq!(wallets.deleted[0].try_get::<String>("owner")?, "Carol");
+
+    // Nothing changes without writes, and failures surface as errors
+    let changes = ctx
+        .capture_changes(|conn| async move {
+            conn.query("SELECT * FROM wallets", &[]).await.map(|_| ())
+        })
+        .await?;
+    assert!(changes.is_empty(), "{}", changes);
+    let failed = ctx
+        .capture_changes(|conn| async move { conn.batch_execute("DELETE FROM missing").await })
+        .await;
+    assert!(failed.is_err());
+
+    // The snapshots don't count towards the statements of the test
+    let changes = testkit_core::assert_query_count!(ctx, == 1, {
+        ctx.capture_changes(|conn| async move { conn.batch_execute("DELETE FROM ledger").await })
+            .await?
+    });
+    assert_eq!(changes.table("ledger").unwrap().deleted.len(), 2);
+
+    Ok(())
+}
+
+#[testkit::matrix(setup = create_users)]
+async fn test_matrix_query_assertions(ctx: TestContext) -> TestResult {
+    use futures::FutureExt;
+    use std::panic::AssertUnwindSafe;
+
+    let conn = ctx.db.acquire_connection().await?;
+    conn.batch_execute(
+        "CREATE TABLE prices (id INT PRIMARY KEY, amount DECIMAL(8, 2), active BOOLEAN);
+         INSERT INTO users (id, name) VALUES (1, 'Alice'), (2, 'Bob');
+         INSERT INTO prices (id, amount, active) VALUES (1, 9.50, TRUE), (2, NULL, FALSE)",
+    )
+    .await?;
+
+    testkit_core::assert_rows!(
+        conn,
+        "SELECT id, name FROM users ORDER BY id",
+        [(1, "Alice"), (2, "Bob")]
+    );
+    testkit_core::assert_rows!(conn, "SELECT name FROM users ORDER BY id", ["Alice", "Bob"]);
+    testkit_core::assert_rows!(
+        conn,
+        "SELECT amount, active FROM prices ORDER BY id",
+        [(9.5, true), (Value::Null, false)]
+    );
+    testkit_core::assert_rows!(conn, "SELECT id FROM users WHERE id > 5", []);
+    testkit_core::assert_scalar!(conn, "SELECT COUNT(*) FROM users", 2);
+    testkit_core::assert_scalar!(conn, "SELECT amount FROM prices WHERE id = 1", 9.5);
+    testkit_core::assert_exists!(conn, "SELECT 1 FROM users WHERE name = 'Bob'");
+    testkit_core::assert_empty!(conn, "SELECT * FROM users WHERE name = 'Carol'");
+
+    let message = |result: std::thread::Result<()>| {
+        let payload = result.expect_err("the assertion should fail");
+        payload
+            .downcast_ref::<String>()
+            .cloned()
+            .unwrap_or_default()
+    };
+    let failure = AssertUnwindSafe(async {
+        testkit_core::assert_rows!(
+            conn,
+            "SELECT id, name FROM users ORDER BY id",
+            [(1, "Alice"), (2, "Robert")]
+        );
+    })
+    .catch_unwind()
+    .await;
+    let diff = message(failure);
+    assert!(
+        diff.contains("- | 2  | \"Robert\" |\n+ | 2  | \"Bob\"    |"),
+        "{}",
+        diff
+    );
+
+    let failure = AssertUnwindSafe(async {
+        testkit_core::assert_empty!(conn, "SELECT id, name FROM users WHERE id = 1");
+    })
+    .catch_unwind()
+    .await;
+    assert!(message(failure).contains("| 1  | \"Alice\" |"));
+
+    let failure = AssertUnwindSafe(async {
+        testkit_core::assert_scalar!(conn, "SELECT id FROM users", 1);
+    })
+    .catch_unwind()
+    .await;
+    assert!(message(failure).starts_with("expected a single value"));
+
+    Ok(())
+}
+
+#[testkit::matrix(setup = create_users)]
+async fn test_matrix_wait_until(ctx: TestContext) -> TestResult {
+    use std::time::Duration;
+
+    let conn = ctx.db.acquire_connection().await?;
+    let count = |rows: &[testkit_core::Row]| rows[0].try_get::<i64>(0).unwrap_or_default();
+
+    // A write landing while polling ends the wait
+    let insert = async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        conn.execute("INSERT INTO users (id, name) VALUES (1, 'Alice')", &[])
+            .await
+    };
+    let wait = ctx.wait_until(
+        "SELECT COUNT(*) FROM users",
+        |rows| count(rows) == 1,
+        Duration::from_secs(5),
+        Duration::from_millis(20),
+    );
+    let (inserted, rows) = tokio::join!(insert, wait);
+    inserted?;
+    assert_eq!(count(&rows?), 1);
+
+    // On timeout the error shows the last result
+    let error = ctx
+        .wait_until(
+            "SELECT name FROM users",
+            |rows| rows.len() == 2,
+            Duration::from_millis(100),
+            Duration::from_millis(20),
+        )
+        .await
+        .expect_err("the condition never holds");
+    let message = error.to_string();
+    assert!(
+        message.contains("condition not met after 100ms"),
+        "{}",
+        message
+    );
+    assert!(message.contains("| \"Alice\" |"), "{}", message);
+
+    let failed = ctx
+        .wait_until(
+            "SELECT * FROM missing",
+            |_| true,
+            Duration::from_millis(100),
+            Duration::from_millis(20),
+        )
+        .await;
+    assert!(failed.is_err());
+
+    Ok(())
+}
+
+#[testkit::matrix]
+async fn test_matrix_listen_notify(ctx: TestContext) -> TestResult {
+    use std::time::Duration;
+    use testkit_postgres::Listen;
+
+    let conn = ctx.db.acquire_connection().await?;
+    let mut subscriber = ctx.listen("cache_invalidation").await?;
+    assert_eq!(subscriber.channel(), "cache_invalidation");
+    assert_eq!(
+        subscriber
+            .next_notification(Duration::from_millis(50))
+            .await?,
+        None
+    );
+
+    conn.execute("SELECT pg_notify('cache_invalidation', 'user:1')", &[])
+        .await?;
+    let notification = subscriber
+        .next_notification(Duration::from_secs(5))
+        .await?
+        .expect("a notification");
+    assert_eq!(notification.channel, "cache_invalidation");
+    assert_eq!(notification.payload, "user:1");
+
+    // Notifications sent in a transaction arrive on commit, other payloads are skipped
+    conn.batch_execute(
+        "BEGIN;
+         NOTIFY cache_invalidation, 'user:2';
+         NOTIFY other_channel, 'ignored';
+         NOTIFY cache_invalidation, 'user:3';
+         COMMIT",
+    )
+    .await?;
+    let notification = testkit_postgres::assert_notified!(subscriber, "user:3");
+    assert_eq!(notification.payload, "user:3");
+    assert!(subscriber.received()?.is_empty());
+
+    conn.execute("SELECT pg_notify('cache_invalidation', '')", &[])
+        .await?;
+    testkit_postgres::assert_notified!(subscriber, timeout = Duration::from_secs(5));
+
+    let missing = subscriber
+        .expect_notification(Some("user:4"), Duration::from_millis(50))
+        .await
+        .expect_err("nothing was sent");
+    assert_eq!(
+        missing,
+        "expected a notification \"user:4\" on channel cache_invalidation within 50ms"
+    );
+
+    Ok(())
+}
+
+#[testkit::matrix(setup = create_users)]
+async fn test_matrix_change_stream(ctx: TestContext) -> TestResult {
+    use std::time::Duration;
+    use testkit_postgres::{ChangeEvent, LogicalDecoding};
+
+    let conn = ctx.db.acquire_connection().await?;
+    let stream = match ctx.change_stream().await {
+        Ok(stream) => stream,
+        Err(e) if e.to_string().contains("wal_level") => {
+            eprintln!("skipping the change stream test: {}", e);
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    assert!(stream.poll().await?.is_empty());
+
+    conn.batch_execute(
+        "INSERT INTO users (id, name) VALUES (1, 'Alice'), (2, 'Bob');
+         UPDATE users SET name = 'Robert' WHERE id = 2;
+         DELETE FROM users WHERE id = 1",
+    )
+    .await?;
+    let events = stream.next_events(Duration::from_secs(5)).await?;
+    let changes: Vec<String> = events
+        .iter()
+        .filter(|event| event.table().is_some())
+        .map(|event| event.to_string())
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            "INSERT users: id=1 name=Alice",
+            "INSERT users: id=2 name=Bob",
+            "UPDATE users: id=2 name=Robert",
+            "DELETE users: id=1",
+        ]
+    );
+    assert!(matches!(events.first(), Some(ChangeEvent::Begin { .. })));
+    assert!(matches!(events.last(), Some(ChangeEvent::Commit { .. })));
+    assert_eq!(events[1].value("name"), Some(&Value::from("Alice")));
+
+    // Changes are consumed as they are read
+    assert!(stream.poll().await?.is_empty());
+    conn.batch_execute("TRUNCATE users").await?;
+    let events = stream.next_events(Duration::from_secs(5)).await?;
+    assert_eq!(
+        events[1],
+        ChangeEvent::Truncate {
+            tables: vec!["users".to_string()]
+        }
+    );
+
+    // A second stream gets its own slot, and slots go away when streams are closed or dropped
+    let other = ctx.change_stream().await?;
+    assert_ne!(other.slot(), stream.slot());
+    let slots = [stream.slot().to_string(), other.slot().to_string()];
+    stream.close().await?;
+    drop(other);
+    for slot in slots {
+        let rows = conn
+            .query(
+                "SELECT slot_name::text FROM pg_replication_slots WHERE slot_name = $1",
+                &[slot.into()],
+            )
+            .await?;
+        assert!(rows.is_empty());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,5 @@
+-- Users of the application
+CREATE TABLE users (
+    id INT PRIMARY KEY,
+    name VARCHAR(50) NOT NULL
+);
@@ -0,0 +1,3 @@
+ALTER TABLE users ADD COLUMN email VARCHAR(100);
+
+INSERT INTO users (id, name, email) VALUES (1, 'Alice', 'alice@example.com');
@@ -4,7 +4,8 @@
 use std::future::Future;
 use std::pin::Pin;
 use testkit_core::{
-    DatabaseBackend, DatabaseConfig, DatabasePool, TestDatabaseInstance, with_boxed_database,
+    DatabaseBackend, DatabaseConfig, DatabasePool, Migrator, TestDatabaseInstance,
+    with_boxed_database,
 };
 use testkit_postgres::{PostgresBackend, PostgresError, postgres_backend_with_config};
 
@@ -704,3 +705,32 @@ async fn test_postgres_with_connection() {
 
     assert!(result.is_ok(), "with_postgres_connection should succeed");
 }
+
+#[tokio::test]
+async fn test_builder_migrations() {
+    let backend = match test_backend().await {
+        Ok(backend) => backend,
+        Err(e) if is_connection_error(&e) => {
+            println!("Skipping test: PostgreSQL appears to be unavailable");
+            return;
+        }
+        Err(e) => panic!("Failed to create backend: {}", e),
+    };
+    let migrator = Migrator::from_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/migrations"))
+        .expect("Failed to read migrations");
+
+    // The migrations are applied right after the database is created
+    let ctx = with_boxed_database(backend)
+        .migrate(migrator)
+        .execute()
+        .await
+        .expect("Failed to create migrated database");
+
+    let conn = ctx.db.acquire_connection().await.unwrap();
+    let rows = conn
+        .client()
+        .query("SELECT email FROM users WHERE id = 1", &[])
+        .await
+        .expect("Failed to query migrated table");
+    assert_eq!(rows[0].get::<_, String>(0), "alice@example.com");
+}
@@ -0,0 +1,28 @@
+-- Schema and data in the shape pg_dump writes them
+CREATE TABLE accounts (
+    id INT PRIMARY KEY,
+    owner TEXT NOT NULL,
+    balance NUMERIC(10, 2),
+    opened_on DATE
+);
+
+CREATE FUNCTION total_balance() RETURNS NUMERIC AS $$
+DECLARE
+    total NUMERIC;
+BEGIN
+    SELECT COALESCE(SUM(balance), 0) INTO total FROM accounts;
+    RETURN total;
+END;
+$$ LANGUAGE plpgsql;
+
+COPY public.accounts (id, owner, balance, opened_on) FROM stdin;
+1	O'Neil	10.50	2024-01-31
+2	Tab\tSeparated	20.25	\N
+3	Line\nBreak	\N	2024-02-29
+\.
+
+DO $body$
+BEGIN
+    UPDATE accounts SET balance = balance + 1 WHERE id = 1;
+END
+$body$;
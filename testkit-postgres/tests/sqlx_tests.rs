@@ -361,3 +361,80 @@ async fn test_sqlx_boxed_api() {
         .await
         .expect("Failed to release connection");
 }
+
+static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!("tests/migrations");
+
+#[tokio::test]
+async fn test_sqlx_migrator() {
+    let backend = match test_backend().await {
+        Ok(backend) => backend,
+        Err(e) if is_connection_error(&e) => {
+            println!("Skipping test: PostgreSQL appears to be unavailable");
+            return;
+        }
+        Err(e) => panic!("Failed to create backend: {}", e),
+    };
+
+    // Both databases are copies of the same migrated template
+    let first = with_boxed_database(backend.clone())
+        .migrate(&MIGRATOR)
+        .execute()
+        .await
+        .expect("Failed to create migrated database");
+    let second = with_boxed_database(backend)
+        .migrate(&MIGRATOR)
+        .execute()
+        .await
+        .expect("Failed to create migrated database");
+
+    for ctx in [&first, &second] {
+        let conn = ctx.db.acquire_connection().await.unwrap();
+        let email: String = sqlx::query_scalar("SELECT email FROM users WHERE id = 1")
+            .fetch_one(conn.pool_connection())
+            .await
+            .expect("Failed to query migrated table");
+        assert_eq!(email, "alice@example.com");
+
+        let applied: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM _sqlx_migrations")
+            .fetch_one(conn.pool_connection())
+            .await
+            .unwrap();
+        assert_eq!(applied, 2);
+    }
+
+    let conn = first.db.acquire_connection().await.unwrap();
+    let templates: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM pg_database WHERE datname LIKE 'testkit_template_%'",
+    )
+    .fetch_one(conn.pool_connection())
+    .await
+    .unwrap();
+    assert!(templates >= 1);
+}
+
+#[tokio::test]
+async fn test_sqlx_migrations_from_config() {
+    let config =
+        test_config().with_migrations(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/migrations"));
+    let backend = match postgres_backend_with_config(config).await {
+        Ok(backend) => backend,
+        Err(e) if is_connection_error(&e) => {
+            println!("Skipping test: PostgreSQL appears to be unavailable");
+            return;
+        }
+        Err(e) => panic!("Failed to create backend: {}", e),
+    };
+
+    // The backend reads the migrations from the configured directory
+    let ctx = with_boxed_database(backend)
+        .execute()
+        .await
+        .expect("Failed to create migrated database");
+
+    let conn = ctx.db.acquire_connection().await.unwrap();
+    let email: String = sqlx::query_scalar("SELECT email FROM users WHERE id = 1")
+        .fetch_one(conn.pool_connection())
+        .await
+        .expect("Failed to query migrated table");
+    assert_eq!(email, "alice@example.com");
+}